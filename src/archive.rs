@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use object::ObjectModule;
+
+/// A static library: a bundle of object modules with an index of the labels they export.
+///
+/// The linker only pulls in the members that define a symbol the program
+/// is missing. The format wraps the members' own textual format:
+///
+/// ```text
+/// hack-archive 1
+/// index MULT math.o
+/// index DIV math.o
+/// member math.o
+/// hack-object 1
+/// ...
+/// end
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Archive {
+	pub members: Vec<(String, ObjectModule)>,
+	/// Each exported label and the position of the member that exports it.
+	symbols: HashMap<String, usize>,
+}
+
+const HEADER: &str = "hack-archive 1";
+
+impl Archive {
+	pub fn new() -> Archive {
+		Archive::default()
+	}
+
+	/// Adds a member. `name` is usually the file name of the object module.
+	pub fn add_member(&mut self, name: &str, module: ObjectModule) {
+		for (symbol, _) in &module.exports {
+			self.symbols.entry(symbol.clone()).or_insert(self.members.len());
+		}
+		self.members.push((name.to_string(), module));
+	}
+
+	/// Returns the position of the member that exports `symbol`, if any.
+	pub fn find(&self, symbol: &str) -> Option<usize> {
+		self.symbols.get(symbol).copied()
+	}
+
+	/// Returns the symbol index: every exported label with the member that exports it.
+	pub fn index(&self) -> Vec<(&str, &str)> {
+		let mut index = Vec::new();
+		for (member, module) in &self.members {
+			for (symbol, _) in &module.exports {
+				index.push((symbol.as_str(), member.as_str()));
+			}
+		}
+		index
+	}
+
+	/// Parses the textual archive format.
+	pub fn parse(content: &str) -> Result<Archive, String> {
+		let mut lines = content.lines();
+		match lines.next() {
+			Some(header) if header.trim() == HEADER => {},
+			_ => return Err("not a hack archive".to_string()),
+		}
+
+		let mut archive = Archive::new();
+		let mut index = Vec::new();
+		let mut member: Option<(String, String)> = None;
+		for line in lines {
+			match member.take() {
+				Some((name, mut body)) => {
+					if line.trim() == "end" {
+						let module = ObjectModule::parse(&body).map_err(|why| format!("{}: {}", name, why))?;
+						archive.add_member(&name, module);
+					} else {
+						body.push_str(line);
+						body.push('\n');
+						member = Some((name, body));
+					}
+				},
+				None => {
+					let fields: Vec<&str> = line.split_whitespace().collect();
+					match fields.as_slice() {
+						[] => {},
+						["index", symbol, name] => index.push((symbol.to_string(), name.to_string())),
+						["member", name] => member = Some((name.to_string(), String::new())),
+						_ => return Err(format!("malformed '{}'", line)),
+					}
+				},
+			}
+		}
+
+		if let Some((name, _)) = member {
+			return Err(format!("member {} is missing its end", name))
+		}
+		archive.check_index(&index)?;
+		Ok(archive)
+	}
+
+	/// Checks the index read from a file against the members, so an archive
+	/// whose members were edited without rebuilding the index is rejected.
+	fn check_index(&self, index: &[(String, String)]) -> Result<(), String> {
+		let stale = |why: String| Err(format!("stale index: {}; rebuild the archive with hack ar", why));
+		let actual = self.index();
+		for (symbol, member) in index {
+			if !actual.contains(&(symbol.as_str(), member.as_str())) {
+				return stale(format!("it lists {} in {}, which doesn't export it", symbol, member))
+			}
+		}
+		for (symbol, member) in actual {
+			if !index.iter().any(|(s, m)| s == symbol && m == member) {
+				return stale(format!("{} exports {}, which it doesn't list", member, symbol))
+			}
+		}
+		Ok(())
+	}
+
+	/// Reads an archive file.
+	pub fn load(filename: &str) -> Result<Archive, String> {
+		let path = Path::new(filename);
+		let content = fs::read_to_string(path).map_err(|why| format!("couldn't read {}: {}", path.display(), why))?;
+		Archive::parse(&content).map_err(|why| format!("{}: {}", path.display(), why))
+	}
+}
+
+impl fmt::Display for Archive {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{}", HEADER)?;
+		for (symbol, member) in self.index() {
+			writeln!(f, "index {} {}", symbol, member)?;
+		}
+		for (name, module) in &self.members {
+			writeln!(f, "member {}", name)?;
+			write!(f, "{}", module)?;
+			writeln!(f, "end")?;
+		}
+		Ok(())
+	}
+}
@@ -2,22 +2,28 @@ use std::collections::HashMap;
 use std::fmt;
 use std::fs::File;
 use std::fs::OpenOptions;
-use std::io::BufReader;
-use std::io::Lines;
 use std::io::prelude::*;
-use std::error::Error;
 use std::path::Path;
 
-enum CommandType {
-	ACommand,
-	CCommand,
-	LCommand,
-}
+use diagnostic::Diagnostic;
+use flow::{self, Flow};
+use instruction::{self, Instruction, Statement, COMP_ALIASES, SHIFTS};
+use lint;
+use map::{MemoryMap, ROM_SIZE, VARIABLES_START};
+use object::{ObjectModule, Relocation};
+use optimizer;
+use preprocessor::{Preprocessor, SourceLine};
+use pseudo;
+use sourcemap::SourceMap;
 
 /// Main module that puts everything together and drives the entire translation process.
 ///
 /// Made up of the following components:
-///		* Parser
+///		* Preprocessor
+///		* Pseudo-instruction expansion
+///		* Peephole optimiser (opt-in)
+///		* Control-flow analysis
+///		* Encoder, shared with `Program`
 ///		* Code module
 /// 	* Symbol table
 ///
@@ -31,301 +37,542 @@ enum CommandType {
 /// been bound to memory locations and recorded in the symbol table. Thus the 
 /// assembler can replace each symbol with its corresponding meaning (numeric addr)
 /// and generate the final binary code.
+///
+/// In object mode the second pass instead produces a relocatable `.o` module,
+/// leaving label, import and variable addresses for the linker to fix up.
+#[allow(clippy::tabs_in_doc_comments, clippy::doc_lazy_continuation, clippy::four_forward_slashes)]
 pub struct Assembler {
-	parser: Parser,
 	input_filename: String,
-	symbol_table: SymbolTable,
+	preprocessor: Preprocessor,
+	source: Vec<SourceLine>,
+	pseudo_instructions: bool,
+	listing: bool,
+	object: bool,
+	map: bool,
+	source_map: bool,
+	optimize: bool,
+	words_saved: usize,
+	strip_unreachable: bool,
+	warnings: Vec<Diagnostic>,
+	quiet: bool,
+	extended: bool,
+	rom_size: usize,
+	exports: Vec<String>,
+	imports: Vec<String>,
 }
 
 impl Assembler {
 	pub fn new(filename: &str) -> Assembler {
 		Assembler {
-			parser: Parser::new(filename),
 			input_filename: filename.to_string(),
-			symbol_table: SymbolTable::new(),
+			preprocessor: Preprocessor::new(),
+			source: Vec::new(),
+			pseudo_instructions: true,
+			listing: false,
+			object: false,
+			map: false,
+			source_map: false,
+			optimize: false,
+			words_saved: 0,
+			strip_unreachable: false,
+			warnings: Vec::new(),
+			quiet: false,
+			extended: false,
+			rom_size: 0,
+			exports: Vec::new(),
+			imports: Vec::new(),
 		}
 	}
 
+	/// Enables writing a `<name_of_prog>.map` describing ROM and RAM usage.
+	pub fn set_map(&mut self, enabled: bool) {
+		self.map = enabled;
+	}
+
+	/// Enables writing a `<name_of_prog>.sourcemap.json` mapping ROM addresses to source lines.
+	///
+	/// Not available in object mode, as the linker doesn't relocate source maps.
+	pub fn set_source_map(&mut self, enabled: bool) {
+		self.source_map = enabled;
+	}
+
+	/// Enables writing a relocatable `<name_of_prog>.o` module instead of a `.hack` program.
+	pub fn set_object(&mut self, enabled: bool) {
+		self.object = enabled;
+	}
+
+	/// Returns the name of the `.asm` file being translated.
+	pub fn input_filename(&self) -> &str {
+		&self.input_filename
+	}
+
+	/// Enables or disables pseudo-instructions such as `GOTO label`.
+	///
+	/// They are enabled by default. Disable them for strict book compatibility.
+	pub fn set_pseudo_instructions(&mut self, enabled: bool) {
+		self.pseudo_instructions = enabled;
+	}
+
+	/// Enables writing a `<name_of_prog>.lst` listing next to the `.hack` output.
+	pub fn set_listing(&mut self, enabled: bool) {
+		self.listing = enabled;
+	}
+
+	/// Enables the peephole optimiser.
+	pub fn set_optimize(&mut self, enabled: bool) {
+		self.optimize = enabled;
+	}
+
+	/// Returns the number of words the optimiser removed from the program.
+	pub fn words_saved(&self) -> usize {
+		self.words_saved
+	}
+
+	/// Enables removing the instructions the control-flow analysis finds unreachable.
+	pub fn set_strip_unreachable(&mut self, enabled: bool) {
+		self.strip_unreachable = enabled;
+	}
+
+	/// Silences the progress output of both passes, leaving only the warnings.
+	pub fn set_quiet(&mut self, enabled: bool) {
+		self.quiet = enabled;
+	}
+
+	/// Enables the shift instructions of the extended instruction set, such as `D=D<<`.
+	pub fn set_extended(&mut self, enabled: bool) {
+		self.extended = enabled;
+	}
+
+	/// Returns `true` when the shift instructions are enabled.
+	pub fn extended(&self) -> bool {
+		self.extended
+	}
+
+	/// Returns the number of words of ROM the program takes up.
+	pub fn rom_size(&self) -> usize {
+		self.rom_size
+	}
+
+	/// Returns the symbols the program imports with `.import`.
+	pub fn imports(&self) -> &[String] {
+		&self.imports
+	}
+
+	/// Returns the warnings found while translating.
+	pub fn warnings(&self) -> &[Diagnostic] {
+		&self.warnings
+	}
+
+	/// Defines a constant for the conditional assembly directives, as `-D NAME=value` does.
+	pub fn define(&mut self, name: &str, value: i32) {
+		self.preprocessor.define(name, value);
+	}
+
 	/// Put together all the steps of the translation process.
 	///
-	/// Inialise Symbol Table -> First Pass -> Second Pass
+	/// Panics on the first error; see `try_translate`.
 	pub fn translate(&mut self)  {
-		self.symbol_table.initialise();
-		println!("----------------- FIRST PASS ----------------");
-		self.first_pass();
-		println!("----------------- SECOND PASS ----------------");
-		self.second_pass();
+		if let Err(why) = self.try_translate() {
+			panic!("{}", why);
+		}
+	}
+
+	/// Put together all the steps of the translation process, returning the
+	/// first error, such as `Foo.asm: line 3: unknown comp D+2`.
+	///
+	/// Preprocess -> Expand Pseudo-Instructions -> Optimise -> Inialise Symbol Table -> First Pass -> Second Pass
+	pub fn try_translate(&mut self) -> Result<(), String> {
+		if self.object && self.source_map {
+			return Err(format!("{}: a source map can't be written for an object module, whose addresses change when it's linked", self.input_filename))
+		}
+		self.preprocess()?;
+		// a module's entry points are only known once it is linked
+		if !self.object {
+			self.analyze_flow();
+		}
+		self.check_variables();
+		let encoding = self.encode()?;
+		self.write(&encoding)
+	}
+
+	/// Reads the input file and evaluates its conditional assembly directives.
+	fn preprocess(&mut self) -> Result<(), String> {
+		let path = Path::new(&self.input_filename);
+		let mut content = String::new();
+		match File::open(path) {
+			Err(why) => return Err(format!("couldn't open {}: {}", path.display(), why)),
+			Ok(mut file) => {
+				if let Err(why) = file.read_to_string(&mut content) {
+					return Err(format!("couldn't read {}: {}", path.display(), why))
+				}
+			},
+		}
+
+		self.source = self.preprocessor.process(&content).map_err(|why| format!("{}: {}", path.display(), why))?;
+
+		if self.pseudo_instructions {
+			let lines = self.source.split_off(0);
+			self.source = pseudo::expand(lines).map_err(|why| format!("{}: {}", path.display(), why))?;
+		}
+
+		let (lines, exports, imports) = linker_directives(self.source.split_off(0));
+		self.source = lines;
+		self.exports.extend(exports);
+		self.imports.extend(imports);
+
+		if self.optimize {
+			let lines = self.source.split_off(0);
+			let (lines, saved) = optimizer::optimize(lines);
+			self.source = lines;
+			self.words_saved = saved;
+			if !self.quiet {
+				println!("Optimiser saved {} words", saved);
+			}
+		}
+		Ok(())
+	}
+
+	/// Returns the source lines the first pass would see, after the
+	/// directives and pseudo-instructions have been processed.
+	///
+	/// Used by tools that analyse a program without translating it.
+	pub fn read_source(&mut self) -> Result<Vec<SourceLine>, String> {
+		self.preprocess()?;
+		Ok(self.source.clone())
+	}
+
+	/// Warns about unreachable code and unused labels, and strips the
+	/// unreachable code if asked to.
+	fn analyze_flow(&mut self) {
+		let statements = instruction::parse(&self.source);
+		self.warnings = Flow::new(&statements).diagnostics();
+		for warning in &self.warnings {
+			eprintln!("{}: {}", self.input_filename, warning);
+		}
+
+		if self.strip_unreachable {
+			self.source = flow::strip_unreachable(&statements).into_iter().map(|s| s.line).collect();
+		}
+	}
+
+	/// Warns about variables that look like misspelt labels, since unknown
+	/// symbols are silently allocated in RAM.
+	fn check_variables(&mut self) {
+		let warnings = lint::check(&self.source, &self.imports, &lint::VARIABLE_RULES);
+		for warning in &warnings {
+			eprintln!("{}: {}", self.input_filename, warning);
+		}
+		self.warnings.extend(warnings);
+		self.warnings.sort_by_key(|w| w.line);
 	}
 
 	/// Open a file
 	///
 	/// returns the opened has been opened
-	fn open_file(filename: &str) -> File{
+	#[allow(clippy::needless_borrows_for_generic_args)]
+	fn open_file(filename: &str) -> Result<File, String> {
 		let path = Path::new(filename);
 		let display = path.display();
 
-		let file = match OpenOptions::new()
+		match OpenOptions::new()
 		.read(true)
 		.write(true)
 		.create(true)
+		.truncate(true)
 		.open(&path) {
-			Err(why) => panic!("Error on file {}: {}", display, Error::description(&why)),
-			Ok(file) => file,		
-		};
-		file
+			Err(why) => Err(format!("Error on file {}: {}", display, why)),
+			Ok(file) => Ok(file),
+		}
 	}
 
 	/// Write to file
-	fn write_to_file(mut file: &File, code: u16) {		
-		match file.write_fmt(format_args!("{:016b}\n", code)) {
-			Err(why) => panic!("couldn't write to file: {}", Error::description(&why)),
-			Ok(_) => (),
-		}
+	fn write_to_file(mut file: &File, code: u16) -> Result<(), String> {
+		file.write_fmt(format_args!("{:016b}\n", code)).map_err(|why| format!("couldn't write to file: {}", why))
 	}
 
+	#[allow(clippy::let_and_return)]
 	fn generate_output_filename(&self) -> String {
 		let v: Vec<&str> = self.input_filename.rsplitn(2, ".asm").collect();
-		let output_ext = ".hack";
+		let output_ext = if self.object { ".o" } else { ".hack" };
 		let output_filename = v[1].to_string() + output_ext;
 		output_filename
 	}
 
-	/// Go through the entire assembly program, line by line, and build the symbol table.
-	///
-	/// Doesn't generate any code.
-	fn first_pass(&mut self) {
-		let mut rom_address: isize = -1;
-		loop {
-			match self.parser.input_lines.next() {
-				Some(line) => {
-					let content = line.unwrap().trim().to_string();
-
-					// ignore line comments and empty lines
-					if content.starts_with("//") || content == "\n" || content == "" {
-						continue
-					}
+	fn generate_listing_filename(&self) -> String {
+		let v: Vec<&str> = self.input_filename.rsplitn(2, ".asm").collect();
+		v[1].to_string() + ".lst"
+	}
 
-					// remove inline commnents
-					let mut content_without_inline = String::new();
-					if content.contains("//") {
-						let v: Vec<&str> = content.split("//").collect();
-						content_without_inline = v[0].trim().to_string();
-					}
+	fn generate_map_filename(&self) -> String {
+		let v: Vec<&str> = self.input_filename.rsplitn(2, ".asm").collect();
+		v[1].to_string() + ".map"
+	}
 
-					// decide whether to use content_without_inline or content
-					if content_without_inline != String::new() {
-						self.parser.current_command = content_without_inline;
-					} else {
-						self.parser.current_command = content;
-					}
+	fn generate_source_map_filename(&self) -> String {
+		let v: Vec<&str> = self.input_filename.rsplitn(2, ".asm").collect();
+		v[1].to_string() + ".sourcemap.json"
+	}
 
-					match self.parser.command_type() {
-						CommandType::ACommand | CommandType::CCommand => {
-							rom_address += 1;
-						},
-						CommandType::LCommand => {
-							let l_cmd_sym = self.parser.symbol();
-							let next_instr_rom_address = (rom_address + 1) as u16;
-							println!("({0}, {1})", l_cmd_sym.clone(), next_instr_rom_address.clone());
-							self.symbol_table.add_entry(l_cmd_sym, next_instr_rom_address);
-						},
-					}
-				},
-				None => break,
+	/// Write a line of the listing: ROM address, binary code, source line number and text.
+	///
+	/// The first instruction expanded from a pseudo-instruction is preceded by
+	/// the pseudo-instruction itself, and expanded instructions are marked with `+`.
+	fn write_listing(mut file: &File, rom_address: Option<u16>, code: Option<u16>, line: &SourceLine, first_of_expansion: bool) -> Result<(), String> {
+		let mut entry = String::new();
+		if first_of_expansion {
+			if let Some(ref origin) = line.origin {
+				entry += &format!("{:5}  {:16}  {:5}  {}\n", "", "", line.line, origin.trim());
 			}
 		}
+		let address = rom_address.map_or(String::new(), |a| format!("{:05}", a));
+		let bits = code.map_or(String::new(), |c| format!("{:016b}", c));
+		let marker = if line.origin.is_some() { "  + " } else { "" };
+		entry += &format!("{:5}  {:16}  {:5}  {}{}\n", address, bits, line.line, marker, line.text.trim());
+
+		file.write_all(entry.as_bytes()).map_err(|why| format!("couldn't write to file: {}", why))
 	}
 
-	/// Puts everything in motion.
-	///
-	/// Contains the main program logic.
-	///		Are there any more commands in the input.
-	///			Reads the next command from the input and makes it the current command.
-	fn second_pass(&mut self) {
-		// get a new parser
-		self.parser = Parser::new(&self.input_filename);
-
-		// file where the translated assembly will be written to. 
-		let output_filename = self.generate_output_filename();
+	/// Validates and encodes the program with `encode`, then checks that it
+	/// fits in ROM and that every exported symbol is a label.
+	fn encode(&self) -> Result<Encoding, String> {
+		let imports = match self.object {
+			true => Some(self.imports.as_slice()),
+			false => None,
+		};
+		let encoding = encode(instruction::parse(&self.source), self.extended, imports)
+			.map_err(|errors| format!("{}: line {}: {}", self.input_filename, errors[0].line, errors[0].message))?;
 
- 		// closed when binding goes out of scope.
-		let output_file = Assembler::open_file(&output_filename); 
+		if !self.quiet {
+			println!("----------------- FIRST PASS ----------------");
+			for (label, address) in &encoding.labels {
+				println!("({0}, {1})", label, address);
+			}
+			println!("----------------- SECOND PASS ----------------");
+			for statement in &encoding.statements {
+				match statement.instruction {
+					Instruction::A(_) => println!("CommandType::ACommand: {0}", statement.instruction),
+					_ => println!("CommandType::CCommand: {0}", statement.instruction),
+				}
+			}
+		}
 
-		// user variable addr
-		let mut user_defined_var_addr: u16 = 16;
+		let rom_size = encoding.code.len();
+		if rom_size > ROM_SIZE {
+			return Err(format!("{}: program is {} words, exceeding the ROM capacity of {} words by {}",
+				self.input_filename, rom_size, ROM_SIZE, rom_size - ROM_SIZE))
+		}
 
-		loop {
-			match self.parser.input_lines.next() { 
-				Some(line) => { // has_more_command.true
-					let content = line.unwrap().trim().to_string();
+		for symbol in &self.exports {
+			if !encoding.labels.iter().any(|l| l.0 == *symbol) || SymbolTable::is_predefined(symbol) {
+				return Err(format!("{}: exported symbol {} is not a label", self.input_filename, symbol))
+			}
+		}
+		Ok(encoding)
+	}
 
-					// ignore line comments and empty lines
-					if content.starts_with("//") || content == "\n" || content == "" {
-						continue
-					}
+	/// Writes the encoded program out, along with the listing, map and
+	/// source map when they are enabled.
+	fn write(&mut self, encoding: &Encoding) -> Result<(), String> {
+		// file where the translated assembly will be written to.
+		let output_filename = self.generate_output_filename();
 
-					// remove inline commnents
-					let mut content_without_inline = String::new();
-					if content.contains("//") {
-						let v: Vec<&str> = content.split("//").collect();
-						content_without_inline = v[0].trim().to_string();
-					}
+		// closed when binding goes out of scope.
+		let output_file = Assembler::open_file(&output_filename)?;
+		let listing_file = match self.listing {
+			true => Some(Assembler::open_file(&self.generate_listing_filename())?),
+			false => None,
+		};
 
-					// decide whether to use content_with_inline or content
-					if content_without_inline != String::new() {
-						self.parser.current_command = content_without_inline;
-					} else {
-						self.parser.current_command = content;
-					}
+		let mut rom_address: u16 = 0;
+		let mut previous_line: Option<usize> = None;
+		let mut module = ObjectModule::new();
+		let mut source_map = SourceMap::new(&self.input_filename);
+		let mut words = encoding.code.iter();
+
+		for statement in instruction::parse(&self.source) {
+			let code = match statement.instruction.is_code() {
+				true => words.next(),
+				false => None,
+			};
+			let line = &statement.line;
+
+			if let Some(ref listing) = listing_file {
+				let first_of_expansion = previous_line != Some(line.line);
+				let address = code.map(|_| rom_address);
+				Assembler::write_listing(listing, address, code.map(|c| c.0), line, first_of_expansion)?;
+			}
+			previous_line = Some(line.line);
 
-					match self.parser.command_type() {
-						CommandType::ACommand => {
-							println!("CommandType::ACommand: {0}", self.parser.current_command);
-
-							let a_cmd_symbol = self.parser.symbol();
-
-							match a_cmd_symbol.parse::<u16>() {
-								Ok(address) => Assembler::write_to_file(&output_file, address),
-								Err(_) => { // symbol aka @symbol
-									match self.symbol_table.contains(&a_cmd_symbol) {
-										true => { // found the symbol in the table
-											let address = self.symbol_table.get_address(&a_cmd_symbol);
-											Assembler::write_to_file(&output_file, address);
-										},
-										false => { // couldn't find the symbol in the table. Find the next available addr.
-											self.symbol_table.add_entry(a_cmd_symbol, user_defined_var_addr);
-											Assembler::write_to_file(&output_file, user_defined_var_addr);
-											user_defined_var_addr += 1;
-										},
-									}
-								}
-							}
-						},
-						CommandType::CCommand => {
-							println!("CommandType::CCommand: {0}", self.parser.current_command);
-
-							let dest = Code::dest(&self.parser.dest());
-							let comp = Code::comp(&self.parser.comp());
-							let jump = Code::jump(&self.parser.jump());
-
-							let c_instr = "111".to_string() + &(comp.to_string()) + &(dest.to_string()) + &(jump.to_string());
-
-							Assembler::write_to_file(&output_file, u16::from_str_radix(&c_instr, 2).unwrap());
-						},
-						CommandType::LCommand => {},
-					}
-				},
-				None => break,
+			if let Some((code, relocation)) = code {
+				source_map.push(rom_address, line);
+				match self.object {
+					true => module.push(*code, relocation.clone()),
+					false => Assembler::write_to_file(&output_file, *code)?,
+				}
+				rom_address += 1;
 			}
 		}
-	}
-}
-
-struct Parser {
-	input_lines: Lines<BufReader<File>>,
-	current_command: String,
-}
+		self.rom_size = rom_address as usize;
+
+		if self.map {
+			let mut map = MemoryMap::new();
+			map.add_regions(None, &encoding.labels, 0, self.rom_size);
+			map.variables = encoding.variables.clone();
+			map.rom_size = self.rom_size;
+			Assembler::open_file(&self.generate_map_filename())?.write_all(map.to_string().as_bytes())
+				.map_err(|why| format!("couldn't write to file: {}", why))?;
+		}
 
-impl Parser {
-	/// Opens the input file/stream and gets ready to parse it.
-	fn new(filename: &str) -> Parser {
-		let path = Path::new(filename);
-		let file = match File::open(&path) {
-			Err(why) => panic!("couldn't open {}: {}", path.display(), Error::description(&why)),
-			Ok(file) => file,
-		};
+		if self.source_map {
+			Assembler::open_file(&self.generate_source_map_filename())?.write_all(source_map.to_string().as_bytes())
+				.map_err(|why| format!("couldn't write to file: {}", why))?;
+		}
 
-		let lines = BufReader::new(file).lines(); // iterator
-		Parser {
-			input_lines: lines, 
-			current_command: String::new(),
+		if self.object {
+			for symbol in &self.exports {
+				let address = encoding.labels.iter().find(|l| l.0 == *symbol).map_or(0, |l| l.1);
+				module.exports.push((symbol.clone(), address));
+			}
+			module.imports = self.imports.clone();
+			(&output_file).write_all(module.to_string().as_bytes()).map_err(|why| format!("couldn't write to file: {}", why))?;
 		}
+		Ok(())
 	}
+}
 
-	/// Returns the type of the current command.
-	///
-	/// * ACommand: For @xxx where xxx is either a symbol or a decimal number
-	/// * CCommand: For dest=comp;jump
-	/// * LCommand: Pseudo-Command. For (xxx) where xxx is a symbol
-	fn command_type(&self) -> CommandType {
-		if self.current_command.starts_with("@") {
-			return CommandType::ACommand
-		} else if self.current_command.starts_with("(") {
-			return CommandType::LCommand
+/// Separates the `.export` and `.import` directives from the code,
+/// returning the code, the exported symbols and the imported symbols.
+pub fn linker_directives(lines: Vec<SourceLine>) -> (Vec<SourceLine>, Vec<String>, Vec<String>) {
+	let mut code = Vec::new();
+	let mut exports = Vec::new();
+	let mut imports = Vec::new();
+	for line in lines {
+		let content = match line.text.find("//") {
+			Some(i) => line.text[..i].trim(),
+			None => line.text.trim(),
+		};
+		if let Some(symbol) = directive(content, ".export") {
+			exports.push(symbol.to_string());
+		} else if let Some(symbol) = directive(content, ".import") {
+			imports.push(symbol.to_string());
+		} else {
+			code.push(line);
 		}
-		CommandType::CCommand
 	}
+	(code, exports, imports)
+}
 
-	/// Returns the symbol or decimal xxx of the current command @xxx or (xxx).
-	///
-	/// Should be called only when command_type() is CommandType::ACommand or CommandType::LCommand.
-	fn symbol(&self) -> String {
-		let pattern: &[_] = &['(', ')', '@'];
-		self.current_command.trim_matches(pattern).to_string()
-	}
+/// Returns the argument of `line` when it is the directive `name`, which
+/// must be followed by whitespace, so `.exports` isn't taken for `.export`.
+fn directive<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+	line.strip_prefix(name).filter(|rest| rest.starts_with(char::is_whitespace)).map(|rest| rest.trim())
+}
 
-	/// Returns the dest mnemonic in the current CommandType::CCommand. dest=comp;jump
-	///  
-	/// 8 posibilities.
-	/// Should only be called when cammand_type() is CommandType::CCommand.
-	fn dest(&self) -> String {
-		// dest or jump field may be empy
-		// if dest is empty, the '=' is omitted.
-		// if jump is empty, the ';' is omitted.
-		match self.current_command.contains('=') {
-			true => {
-				let v: Vec<&str> = self.current_command.split('=').collect();
-				return v[0].to_string()
+/// A program encoded by `encode`.
+pub struct Encoding {
+	/// The machine code at each ROM address, and how the linker relocates it.
+	pub code: Vec<(u16, Relocation)>,
+	/// The instruction at each ROM address.
+	pub statements: Vec<Statement>,
+	/// Each label and its ROM address, in source order.
+	pub labels: Vec<(String, u16)>,
+	/// Each variable and its RAM address, in order of allocation.
+	pub variables: Vec<(String, u16)>,
+}
+
+/// Validates and encodes the statements of a program in two passes, for
+/// the `Assembler` to write out and `Program` to keep in memory.
+///
+/// The first pass binds each label to the ROM address of the instruction
+/// after it, and the second resolves the symbols and encodes each
+/// instruction, allocating a variable for every unknown symbol. When
+/// assembling an object module, `imports` holds the symbols it imports, and
+/// labels, imports and variables are left for the linker to relocate.
+///
+/// Returns every error found, in source order.
+pub fn encode(statements: Vec<Statement>, extended: bool, imports: Option<&[String]>) -> Result<Encoding, Vec<Diagnostic>> {
+	let mut errors = Vec::new();
+	let mut symbol_table = SymbolTable::new();
+	symbol_table.initialise();
+
+	// first pass
+	let mut labels = Vec::new();
+	let mut address = 0;
+	for statement in &statements {
+		if let Err(why) = statement.instruction.validate_extended(extended) {
+			errors.push(Diagnostic::error(statement.line.line, &why));
+		}
+		match statement.instruction {
+			Instruction::Label(ref label) if labels.iter().any(|l: &(String, u16)| l.0 == *label) => {
+				errors.push(Diagnostic::error(statement.line.line, &format!("label {} is defined twice", label)));
 			},
-			false => "null".to_string(),
+			Instruction::Label(ref label) => {
+				labels.push((label.clone(), address as u16));
+				symbol_table.add_entry(label.clone(), address as u16);
+			},
+			_ => address += 1,
 		}
 	}
+	if !errors.is_empty() {
+		return Err(errors)
+	}
 
-	/// Returns the comp mnemonic in the current CommandType::CCommand. dest=comp;jump
-	///
-	/// 28 possibilities.
-	/// Should only be called when cammand_type() is CommandType::CCommand.
-	fn comp(&self) -> String {
-		match self.current_command.contains('=') {
-			true => {
-				match self.current_command.contains(';') {
-					true => { // dest=comp;jump	
-						let v: Vec<&str> = self.current_command.split(|c: char| c == ';' || c == '=' ).collect();
-						v[1].to_string()					
-					},
-					false => { // dest=comp
-						let v: Vec<&str> = self.current_command.split('=').collect();
-						v[1].to_string()
+	// second pass
+	let object = imports.is_some();
+	let mut code = Vec::new();
+	let mut variables = Vec::new();
+	let mut next_variable = VARIABLES_START;
+	let mut code_statements = Vec::new();
+	for statement in statements {
+		let word = match statement.instruction {
+			Instruction::Label(_) => continue,
+			Instruction::A(ref value) => match value.parse::<u16>() {
+				Ok(value) => (value, Relocation::Absolute),
+				Err(_) if imports.is_some_and(|imports| imports.contains(value)) => (0, Relocation::Import(value.clone())),
+				Err(_) if symbol_table.contains(value) => {
+					match object && !SymbolTable::is_predefined(value) {
+						true => (symbol_table.get_address(value), Relocation::Label),
+						false => (symbol_table.get_address(value), Relocation::Absolute),
 					}
-				}
+				},
+				// variables of a module are allocated by the linker
+				Err(_) if object => (0, Relocation::Variable(value.clone())),
+				Err(_) => {
+					variables.push((value.clone(), next_variable));
+					symbol_table.add_entry(value.clone(), next_variable);
+					next_variable += 1;
+					(next_variable - 1, Relocation::Absolute)
+				},
 			},
-			false => {
-				match self.current_command.contains(';') {
-					true => { // comp;jump
-						let v: Vec<&str> = self.current_command.split(';').collect();
-						v[0].to_string()
-					},
-					false => { // comp
-						self.current_command.clone()
-					},
-				}
-			}
-		}
-	}
-
-	/// Returns the jump mnemonic in the current CommandType::CCommand. dest=com;jump
-	///
-	/// 8-possibilities.
-	/// Should only be called when cammand_type() is CommandType::CCommand.
-	fn jump(&self) -> String {
-		match self.current_command.contains(';') {
-			true => {
-				let v: Vec<&str> = self.current_command.split(';').collect();
-				return v[1].to_string()
+			Instruction::C { ref dest, ref comp, ref jump } => {
+				(encode_c(dest.as_deref(), comp, jump.as_deref()), Relocation::Absolute)
 			},
-			false => "null".to_string(),
-		}	
+		};
+		code.push(word);
+		code_statements.push(statement);
 	}
+
+	Ok(Encoding { code, statements: code_statements, labels, variables })
+}
+
+/// Returns the machine code of the C-instruction `dest=comp;jump`.
+///
+/// The mnemonics must be valid; see `Instruction::validate`.
+pub fn encode_c(dest: Option<&str>, comp: &str, jump: Option<&str>) -> u16 {
+	let prefix = Code::prefix(comp);
+	let (comp, dest, jump) = c_fields(dest, comp, jump);
+	let c_instr = prefix.to_string() + &comp + &dest + &jump;
+	u16::from_str_radix(&c_instr, 2).unwrap()
+}
+
+/// Returns the bits of the comp (`acccccc`), dest (`ddd`) and jump (`jjj`)
+/// fields of the C-instruction `dest=comp;jump`.
+pub fn c_fields(dest: Option<&str>, comp: &str, jump: Option<&str>) -> (String, String, String) {
+	let comp = COMP_ALIASES.iter().find(|&&(alias, _)| alias == comp).map_or(comp, |&(_, comp)| comp);
+	let dest = Code::dest(dest.unwrap_or("null"));
+	let comp = Code::comp(comp);
+	let jump = Code::jump(jump.unwrap_or("null"));
+	(comp.to_string(), dest.to_string(), jump.to_string())
 }
 
 /// Translate Hack assembly language mnemonic into binary codes
@@ -531,11 +778,36 @@ impl Code {
 				comp_bits.c5 = 0;
 				comp_bits.c6 = 1;
 			},
+			// the shifts of the extended instruction set
+			"A<<" | "M<<" => {
+				if mnemonic == "M<<" {comp_bits.a = 1;}
+				comp_bits.c1 = 1;
+			},
+			"D<<" => {
+				comp_bits.c1 = 1;
+				comp_bits.c2 = 1;
+			},
+			"A>>" => {}, // every bit is clear
+			"M>>" => {
+				comp_bits.a = 1;
+			},
+			"D>>" => {
+				comp_bits.c2 = 1;
+			},
 			_ => {},
 		}
 		comp_bits
 	}
 
+	/// Returns the first 3 bits of a C-instruction: `101` for the shifts of
+	/// the extended instruction set, and `111` otherwise.
+	fn prefix(comp: &str) -> &'static str {
+		match SHIFTS.contains(&comp) {
+			true => "101",
+			false => "111",
+		}
+	}
+
 	/// Returns the binary code of the jump mnemonic
 	///
 	/// returns 3 bits
@@ -578,7 +850,7 @@ impl Code {
 /// Change the bits at indexes in index to values in dest
 ///
 /// Takes a hashmap such as bit_index -> bit_value
-#[allow(dead_code)]
+#[allow(dead_code, clippy::clone_on_copy)]
 fn set_bits(mut word:i16, index_bitvalue: HashMap<i16, i16>) -> i16 {
 	for index in index_bitvalue.keys() {
 		let bit_value: i16 = match index_bitvalue.get(index) {
@@ -669,20 +941,20 @@ impl fmt::Display for Jump {
 ///
 /// Hack instructions can contain symbols that each must be resolved into
 /// actual addresses as part of the translation process.
-struct SymbolTable {
+pub struct SymbolTable {
 	table: HashMap<String, u16>,
 }
 
 impl SymbolTable {
 	/// Creates a new empty symbol table
-	fn new() -> SymbolTable {
+	pub fn new() -> SymbolTable {
 		SymbolTable {
 			table: HashMap::new(),
 		}
 	}
 
 	/// Initialise the symbol table with predefined symbols.
-	fn initialise(&mut self) {
+	pub fn initialise(&mut self) {
 		self.table.insert("SP".to_string(), 0);
 		self.table.insert("LCL".to_string(), 1);
 		self.table.insert("ARG".to_string(), 2);
@@ -696,20 +968,47 @@ impl SymbolTable {
 	}
 
 	/// Adds the pair (symbol, address) to the table
-	fn add_entry(&mut self, symbol: String, address: u16) {
+	pub fn add_entry(&mut self, symbol: String, address: u16) {
 		self.table.insert(symbol, address);
 	}
 
+	/// Determines whether `symbol` is one of the predefined symbols.
+	pub fn is_predefined(symbol: &str) -> bool {
+		match symbol {
+			"SP" | "LCL" | "ARG" | "THIS" | "THAT" | "SCREEN" | "KBD" => true,
+			_ => {
+				symbol.starts_with('R') && match symbol[1..].parse::<u16>() {
+					Ok(i) => i < 16 && symbol[1..] == i.to_string(),
+					Err(_) => false,
+				}
+			},
+		}
+	}
+
 	/// Determines whether the symbol table contain the given symbol
-	fn contains(&self, symbol: &str) -> bool {
+	pub fn contains(&self, symbol: &str) -> bool {
 		self.table.contains_key(symbol)
 	}
 
 	/// Returns the address associated with the `symbol`.
-	fn get_address(&self, symbol: &str) -> u16 {
+	#[allow(clippy::clone_on_copy)]
+	pub fn get_address(&self, symbol: &str) -> u16 {
 		let tmp = self.table.get(symbol).unwrap();
 		tmp.clone()
 	}
+
+	/// Returns every symbol and its address, sorted by name.
+	pub fn entries(&self) -> Vec<(String, u16)> {
+		let mut entries: Vec<(String, u16)> = self.table.iter().map(|(s, a)| (s.clone(), *a)).collect();
+		entries.sort();
+		entries
+	}
+}
+
+impl Default for SymbolTable {
+	fn default() -> SymbolTable {
+		SymbolTable::new()
+	}
 }
 
 // #[cfg(test)]
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::slice;
+
+use assembler::Assembler;
+use compiler;
+use jack;
+use vm;
+
+/// Returns the `.jack` files of a directory, sorted, or the file itself.
+pub fn jack_files(path: &Path) -> Result<Vec<PathBuf>, String> {
+	if !path.is_dir() {
+		return Ok(vec![path.to_path_buf()])
+	}
+	let entries = fs::read_dir(path).map_err(|why| format!("couldn't read {}: {}", path.display(), why))?;
+	let mut files: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == "jack")).collect();
+	files.sort();
+	Ok(files)
+}
+
+/// Compiles `Foo.jack` into `Foo.vm` next to it, returning the path written.
+pub fn compile_jack(file: &Path) -> Result<PathBuf, String> {
+	let source = read(file)?;
+	let code = compiler::compile(&source).map_err(|why| format!("{}: {}", file.display(), why))?;
+	write(&file.with_extension("vm"), &code)
+}
+
+/// Writes the tokens of `Foo.jack` to `FooT.xml` and its parse tree to
+/// `Foo.xml`, to compare with the files of nand2tetris project 10.
+pub fn write_xml(file: &Path) -> Result<(PathBuf, PathBuf), String> {
+	let source = read(file)?;
+	let located = |why: String| format!("{}: {}", file.display(), why);
+	let tokens = jack::tokenize(&source).map_err(located)?;
+	let tree = jack::parse(&source).map_err(located)?;
+	let stem = file.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+	let tokens_path = write(&file.with_file_name(format!("{}T.xml", stem)), &jack::tokens_xml(&tokens))?;
+	let tree_path = write(&file.with_extension("xml"), &tree.to_xml())?;
+	Ok((tokens_path, tree_path))
+}
+
+/// The directory, inside a project, where `hack build` keeps intermediate
+/// files and the content hashes of the last build.
+pub const CACHE: &str = ".hack-build";
+
+const MANIFEST_HEADER: &str = "hack-build 1";
+
+/// A stage of the pipeline from Jack to Hack.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stage {
+	/// `Foo.jack` to `Foo.vm`.
+	Compile,
+	/// Every `.vm` file to `Prog.asm`.
+	Translate,
+	/// `Foo.asm` to `Foo.hack`.
+	Assemble,
+}
+
+impl Stage {
+	pub fn verb(&self) -> &'static str {
+		match *self {
+			Stage::Compile => "compiled",
+			Stage::Translate => "translated",
+			Stage::Assemble => "assembled",
+		}
+	}
+}
+
+/// A stage a build ran, or skipped because its inputs hadn't changed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+	pub stage: Stage,
+	pub input: String,
+	pub output: PathBuf,
+	pub ran: bool,
+}
+
+/// Builds a directory of Jack classes, VM files and assembly into `.hack`
+/// programs, rerunning only the stages whose inputs changed.
+///
+/// Each `Foo.jack` is compiled to `Foo.vm`, every `.vm` file is translated
+/// into one `Prog.asm`, named after the directory, and that is assembled into
+/// `Prog/Prog.hack`. Any other `Foo.asm` is assembled into its own `Foo.hack`.
+/// The intermediate files are kept in the `.hack-build` directory, along with
+/// a manifest of the content hashes of each stage's inputs and output.
+pub struct Builder {
+	directory: PathBuf,
+	intermediates: bool,
+	force: bool,
+}
+
+impl Builder {
+	pub fn new(directory: &Path) -> Builder {
+		Builder {
+			directory: directory.to_path_buf(),
+			intermediates: false,
+			force: false,
+		}
+	}
+
+	/// Enables writing the intermediate `.vm` and `.asm` files next to the sources too.
+	pub fn set_intermediates(&mut self, enabled: bool) {
+		self.intermediates = enabled;
+	}
+
+	/// Enables rerunning every stage, whether or not its inputs changed.
+	pub fn set_force(&mut self, enabled: bool) {
+		self.force = enabled;
+	}
+
+	/// Runs the build, returning the steps in the order they were considered.
+	pub fn build(&self) -> Result<Vec<Step>, String> {
+		if !self.directory.is_dir() {
+			return Err(format!("{} is not a directory", self.directory.display()))
+		}
+		let cache = self.directory.join(CACHE);
+		fs::create_dir_all(&cache).map_err(|why| format!("couldn't create {}: {}", cache.display(), why))?;
+		let manifest_path = cache.join("manifest");
+		let previous = match self.force {
+			true => HashMap::new(),
+			false => fs::read_to_string(&manifest_path).map(|m| parse_manifest(&m)).unwrap_or_default(),
+		};
+
+		// the manifest records the stages that succeeded, even if a later one failed
+		let mut manifest = HashMap::new();
+		let result = self.run(&cache, &previous, &mut manifest);
+		let mut outputs: Vec<&String> = manifest.keys().collect();
+		outputs.sort();
+		let mut contents = format!("{}\n", MANIFEST_HEADER);
+		for output in outputs {
+			let (inputs, hash) = manifest[output];
+			contents.push_str(&format!("{:016x} {:016x} {}\n", inputs, hash, output));
+		}
+		write(&manifest_path, &contents)?;
+		result
+	}
+
+	fn run(&self, cache: &Path, previous: &Manifest, manifest: &mut Manifest) -> Result<Vec<Step>, String> {
+		let name = self.directory.canonicalize().ok().and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string())).unwrap_or_default();
+		let Sources { jack, mut vm, asm } = self.sources(&name)?;
+		let mut steps = Vec::new();
+
+		for file in &jack {
+			let output = cache.join(file_name(file)).with_extension("vm");
+			steps.push(self.stage(Stage::Compile, slice::from_ref(file), &output, previous, manifest, || {
+				let code = compiler::compile(&read(file)?).map_err(|why| format!("{}: {}", file.display(), why))?;
+				write(&output, &code).map(|_| ())
+			})?);
+			self.keep(&output)?;
+			vm.push(output);
+		}
+
+		if !vm.is_empty() {
+			vm.sort_by_key(|file| file_name(file));
+			let output = cache.join(format!("{}.asm", name));
+			steps.push(self.stage(Stage::Translate, &vm, &output, previous, manifest, || {
+				let sources: Result<Vec<(String, String)>, String> = vm.iter().map(|file| Ok((file_name(file), read(file)?))).collect();
+				let translator = vm::translate_sources(&sources?)?;
+				translator.check_calls()?;
+				write(&output, &translator.output()).map(|_| ())
+			})?);
+			self.keep(&output)?;
+
+			let program = self.directory.join(format!("{}.hack", name));
+			steps.push(self.stage(Stage::Assemble, slice::from_ref(&output), &program, previous, manifest, || {
+				assemble(&output)?;
+				fs::rename(output.with_extension("hack"), &program).map_err(|why| format!("couldn't write {}: {}", program.display(), why))
+			})?);
+		}
+
+		for file in &asm {
+			let output = file.with_extension("hack");
+			steps.push(self.stage(Stage::Assemble, slice::from_ref(file), &output, previous, manifest, || assemble(file))?);
+		}
+		Ok(steps)
+	}
+
+	/// Returns the `.jack`, `.vm` and `.asm` sources of the directory, sorted,
+	/// leaving out the files a build of them would write.
+	fn sources(&self, name: &str) -> Result<Sources, String> {
+		let entries = fs::read_dir(&self.directory).map_err(|why| format!("couldn't read {}: {}", self.directory.display(), why))?;
+		let mut files: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| p.is_file()).collect();
+		files.sort();
+		let with = |extension: &str| files.iter().filter(|p| p.extension().is_some_and(|e| e == extension)).cloned().collect::<Vec<PathBuf>>();
+
+		let jack = with("jack");
+		let vm: Vec<PathBuf> = with("vm").into_iter().filter(|p| !jack.contains(&p.with_extension("jack"))).collect();
+		let program = format!("{}.asm", name);
+		let asm: Vec<PathBuf> = with("asm").into_iter().filter(|p| (jack.is_empty() && vm.is_empty()) || file_name(p) != program).collect();
+		if jack.is_empty() && vm.is_empty() && asm.is_empty() {
+			return Err(format!("no .jack, .vm or .asm files in {}", self.directory.display()))
+		}
+		Ok(Sources { jack, vm, asm })
+	}
+
+	/// Runs a stage unless the hashes of its inputs and output match the last build.
+	fn stage<F>(&self, stage: Stage, inputs: &[PathBuf], output: &Path, previous: &Manifest, manifest: &mut Manifest, run: F) -> Result<Step, String>
+		where F: FnOnce() -> Result<(), String>
+	{
+		let key = file_name(output);
+		let inputs_hash = hash(inputs)?;
+		let output_hash = match output.exists() {
+			true => Some(hash(&[output.to_path_buf()])?),
+			false => None,
+		};
+		let ran = match (previous.get(&key), output_hash) {
+			(Some(&(inputs, hash)), Some(output_hash)) => inputs != inputs_hash || hash != output_hash,
+			_ => true,
+		};
+		if ran {
+			run()?;
+		}
+		manifest.insert(key, (inputs_hash, hash(&[output.to_path_buf()])?));
+
+		let input = match stage {
+			Stage::Translate => format!("{} .vm files", inputs.len()),
+			_ => file_name(&inputs[0]),
+		};
+		Ok(Step { stage, input, output: output.to_path_buf(), ran })
+	}
+
+	/// Copies an intermediate file next to the sources, when they're wanted.
+	fn keep(&self, file: &Path) -> Result<(), String> {
+		if self.intermediates {
+			let copy = self.directory.join(file_name(file));
+			fs::copy(file, &copy).map_err(|why| format!("couldn't write {}: {}", copy.display(), why))?;
+		}
+		Ok(())
+	}
+}
+
+struct Sources {
+	jack: Vec<PathBuf>,
+	vm: Vec<PathBuf>,
+	asm: Vec<PathBuf>,
+}
+
+/// The hashes of each output's inputs and of the output itself, by file name.
+type Manifest = HashMap<String, (u64, u64)>;
+
+/// Reads a manifest, ignoring one that's unreadable so everything is rebuilt.
+fn parse_manifest(contents: &str) -> Manifest {
+	let mut lines = contents.lines();
+	if lines.next() != Some(MANIFEST_HEADER) {
+		return HashMap::new()
+	}
+	lines.filter_map(|line| {
+		let mut fields = line.splitn(3, ' ');
+		let inputs = u64::from_str_radix(fields.next()?, 16).ok()?;
+		let output = u64::from_str_radix(fields.next()?, 16).ok()?;
+		Some((fields.next()?.to_string(), (inputs, output)))
+	}).collect()
+}
+
+/// Returns the FNV-1a hash of the names and contents of some files.
+fn hash(files: &[PathBuf]) -> Result<u64, String> {
+	let mut hash: u64 = 0xcbf29ce484222325;
+	for file in files {
+		let contents = fs::read(file).map_err(|why| format!("couldn't read {}: {}", file.display(), why))?;
+		for byte in file_name(file).bytes().chain([0]).chain(contents).chain([0]) {
+			hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+		}
+	}
+	Ok(hash)
+}
+
+fn assemble(file: &Path) -> Result<(), String> {
+	let mut assembler = Assembler::new(&file.to_string_lossy());
+	assembler.set_quiet(true);
+	assembler.try_translate().map_err(|why| format!("{}: {}", file.display(), why))
+}
+
+fn file_name(file: &Path) -> String {
+	file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn read(file: &Path) -> Result<String, String> {
+	fs::read_to_string(file).map_err(|why| format!("couldn't read {}: {}", file.display(), why))
+}
+
+fn write(file: &Path, contents: &str) -> Result<PathBuf, String> {
+	fs::write(file, contents).map_err(|why| format!("couldn't write {}: {}", file.display(), why))?;
+	Ok(file.to_path_buf())
+}
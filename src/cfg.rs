@@ -0,0 +1,159 @@
+use flow::{self, Flow, Target};
+use instruction::{Instruction, Statement};
+
+/// How control gets from one basic block to another.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EdgeKind {
+	/// Execution continues with the next instruction.
+	FallThrough,
+	/// A jump to an address loaded by `@xxx`.
+	Jump,
+	/// A jump to a computed address, assumed to reach any label used as data.
+	Computed,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edge {
+	/// Index of the block control goes to.
+	pub to: usize,
+	pub kind: EdgeKind,
+	/// The jump mnemonic, e.g. `JGT`, for jump edges.
+	pub condition: Option<String>,
+}
+
+/// A straight-line run of instructions, entered only at the top and left only at the bottom.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BasicBlock {
+	/// ROM address of the first instruction.
+	pub start: usize,
+	/// ROM address after the last instruction.
+	pub end: usize,
+	/// The labels and instructions in the block, with their source lines.
+	pub statements: Vec<Statement>,
+	pub edges: Vec<Edge>,
+	pub reachable: bool,
+}
+
+/// Control-flow graph of a program's basic blocks.
+///
+/// Blocks start at ROM address 0, at labels, at jump targets and after
+/// C-instructions with jump bits.
+pub struct Cfg {
+	pub blocks: Vec<BasicBlock>,
+}
+
+impl Cfg {
+	pub fn new(statements: &[Statement]) -> Cfg {
+		let flow = Flow::new(statements);
+		let size = flow.code.len();
+
+		let mut leaders = vec![false; size + 1];
+		leaders[0] = true;
+		for &address in flow.labels.values() {
+			leaders[address.min(size)] = true;
+		}
+		for address in 0..size {
+			if flow.code[address].instruction.jump().is_some() {
+				leaders[address + 1] = true;
+				if let Target::Address(target) = flow.target(address) {
+					leaders[target.min(size)] = true;
+				}
+			}
+		}
+
+		// the block of every ROM address
+		let mut block_of = vec![0; size];
+		let mut blocks: Vec<BasicBlock> = Vec::new();
+		for address in 0..size {
+			if leaders[address] {
+				blocks.push(BasicBlock {
+					start: address,
+					end: address,
+					statements: Vec::new(),
+					edges: Vec::new(),
+					reachable: flow.reachable[address],
+				});
+			}
+			let block = blocks.len() - 1;
+			blocks[block].end = address + 1;
+			block_of[address] = block;
+		}
+
+		// labels go with the instruction they refer to
+		let mut address = 0;
+		for statement in statements {
+			let block = match block_of.get(address) {
+				Some(&block) => block,
+				None => break, // labels at the very end refer to no instruction
+			};
+			blocks[block].statements.push(statement.clone());
+			if statement.instruction.is_code() {
+				address += 1;
+			}
+		}
+
+		for block in blocks.iter_mut() {
+			let last = block.end - 1;
+			let instruction = &flow.code[last].instruction;
+			let kind = match flow.target(last) {
+				Target::Unknown => EdgeKind::Computed,
+				_ => EdgeKind::Jump,
+			};
+			for successor in flow.successors(last) {
+				let fall_through = successor == last + 1 && !flow::always_jumps(instruction);
+				let edge = match fall_through && !is_jump_to(&flow, last, successor) {
+					true => Edge { to: block_of[successor], kind: EdgeKind::FallThrough, condition: None },
+					false => Edge { to: block_of[successor], kind, condition: instruction.jump().map(|j| j.to_string()) },
+				};
+				block.edges.push(edge);
+			}
+		}
+
+		Cfg { blocks }
+	}
+
+	/// Renders the graph in Graphviz DOT, with the source lines of each block.
+	///
+	/// Unreachable blocks are greyed out and computed jumps are dotted.
+	pub fn to_dot(&self, name: &str) -> String {
+		let mut dot = format!("digraph \"{}\" {{\n", escape(name));
+		dot += "\tnode [shape=box, fontname=\"monospace\"];\n";
+		for (i, block) in self.blocks.iter().enumerate() {
+			let mut label = format!("ROM {}-{}\\l", block.start, block.end - 1);
+			for statement in &block.statements {
+				let text = match statement.instruction {
+					Instruction::Label(_) => statement.instruction.to_string(),
+					_ => format!("    {}", statement.instruction),
+				};
+				label += &format!("{:>4}: {}\\l", statement.line.line, escape(&text));
+			}
+			let style = if block.reachable { "" } else { ", style=filled, fillcolor=lightgrey" };
+			dot += &format!("\tb{} [label=\"{}\"{}];\n", i, label, style);
+		}
+		for (i, block) in self.blocks.iter().enumerate() {
+			for edge in &block.edges {
+				let attributes = match (edge.kind, &edge.condition) {
+					(EdgeKind::FallThrough, _) => String::new(),
+					(EdgeKind::Jump, Some(condition)) => format!(" [label=\"{}\"]", condition),
+					(EdgeKind::Computed, Some(condition)) => format!(" [label=\"{}\", style=dotted]", condition),
+					(_, None) => String::new(),
+				};
+				dot += &format!("\tb{} -> b{}{};\n", i, edge.to, attributes);
+			}
+		}
+		dot += "}\n";
+		dot
+	}
+}
+
+/// Returns `true` if the jump at `address` targets `successor`.
+fn is_jump_to(flow: &Flow, address: usize, successor: usize) -> bool {
+	match flow.target(address) {
+		Target::Address(target) => target == successor,
+		_ => false,
+	}
+}
+
+fn escape(text: &str) -> String {
+	text.replace('\\', "\\\\").replace('"', "\\\"")
+}
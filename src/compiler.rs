@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+
+use jack::{self, Node};
+
+/// Where a variable lives, and the VM segment it's pushed from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+	Static,
+	Field,
+	Argument,
+	Local,
+}
+
+impl Kind {
+	fn segment(&self) -> &'static str {
+		match *self {
+			Kind::Static => "static",
+			Kind::Field => "this",
+			Kind::Argument => "argument",
+			Kind::Local => "local",
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+struct Variable {
+	kind: Kind,
+	type_name: String,
+	index: usize,
+}
+
+/// Compiles a Jack class into VM code, as in nand2tetris project 11.
+///
+/// Follows the book's conventions: `this` is `pointer 0`, arrays are accessed
+/// through `pointer 1` and `that 0`, constructors allocate their fields with
+/// `Memory.alloc`, and strings are built with `String.new` and
+/// `String.appendChar`, so the program needs the Jack OS to run.
+pub fn compile(source: &str) -> Result<String, String> {
+	let class = jack::parse(source)?;
+	let mut compiler = Compiler {
+		class: String::new(),
+		class_scope: HashMap::new(),
+		scope: HashMap::new(),
+		fields: 0,
+		statics: 0,
+		subroutine_kind: String::new(),
+		labels: 0,
+		lines: Vec::new(),
+	};
+	compiler.class(&class)?;
+	Ok(compiler.lines.join("\n") + "\n")
+}
+
+struct Compiler {
+	class: String,
+	class_scope: HashMap<String, Variable>,
+	scope: HashMap<String, Variable>,
+	fields: usize,
+	statics: usize,
+	/// `constructor`, `function` or `method`.
+	subroutine_kind: String,
+	labels: usize,
+	lines: Vec<String>,
+}
+
+impl Compiler {
+	fn emit(&mut self, command: String) {
+		let indent = match command.starts_with("function") {
+			true => "",
+			false => "    ",
+		};
+		self.lines.push(format!("{}{}", indent, command));
+	}
+
+	fn class(&mut self, class: &Node) -> Result<(), String> {
+		let nodes = class.children();
+		self.class = nodes[1].text().to_string();
+		for node in &nodes[3..nodes.len() - 1] {
+			match node.kind() {
+				"classVarDec" => self.declare(node.children(), if node.children()[0].text() == "static" { Kind::Static } else { Kind::Field })?,
+				_ => self.subroutine(node)?,
+			}
+		}
+		Ok(())
+	}
+
+	/// Declares the variables of a `static`, `field` or `var` declaration.
+	fn declare(&mut self, nodes: &[Node], kind: Kind) -> Result<(), String> {
+		let type_name = nodes[1].text().to_string();
+		for name in nodes[2..].iter().step_by(2) {
+			self.define(name, &type_name, kind)?;
+		}
+		Ok(())
+	}
+
+	fn define(&mut self, name: &Node, type_name: &str, kind: Kind) -> Result<(), String> {
+		let count = match kind {
+			Kind::Static => &mut self.statics,
+			Kind::Field => &mut self.fields,
+			_ => return self.define_local(name, type_name, kind),
+		};
+		let variable = Variable { kind, type_name: type_name.to_string(), index: *count };
+		*count += 1;
+		match self.class_scope.insert(name.text().to_string(), variable) {
+			Some(_) => Err(format!("line {}: {} is already declared", name.line(), name.text())),
+			None => Ok(()),
+		}
+	}
+
+	fn define_local(&mut self, name: &Node, type_name: &str, kind: Kind) -> Result<(), String> {
+		let index = self.scope.values().filter(|v| v.kind == kind).count();
+		let variable = Variable { kind, type_name: type_name.to_string(), index };
+		match self.scope.insert(name.text().to_string(), variable) {
+			Some(_) => Err(format!("line {}: {} is already declared", name.line(), name.text())),
+			None => Ok(()),
+		}
+	}
+
+	fn lookup(&self, name: &str) -> Option<Variable> {
+		self.scope.get(name).or_else(|| self.class_scope.get(name)).cloned()
+	}
+
+	/// Returns the segment and index of a variable, which must exist.
+	fn variable(&self, name: &Node) -> Result<(String, usize), String> {
+		match self.lookup(name.text()) {
+			Some(ref variable) if variable.kind == Kind::Field && self.subroutine_kind == "function" => {
+				Err(format!("line {}: field {} can't be used in a function", name.line(), name.text()))
+			},
+			Some(variable) => Ok((variable.kind.segment().to_string(), variable.index)),
+			None => Err(format!("line {}: undefined variable {}", name.line(), name.text())),
+		}
+	}
+
+	fn subroutine(&mut self, subroutine: &Node) -> Result<(), String> {
+		let nodes = subroutine.children();
+		self.subroutine_kind = nodes[0].text().to_string();
+		self.scope.clear();
+		self.labels = 0;
+		if self.subroutine_kind == "method" {
+			// the object is the hidden first argument
+			self.scope.insert("this".to_string(), Variable { kind: Kind::Argument, type_name: self.class.clone(), index: 0 });
+		}
+		let parameters = nodes[4].children();
+		for parameter in parameters.chunks(3) {
+			self.define_local(&parameter[1], parameter[0].text(), Kind::Argument)?;
+		}
+
+		let body = nodes[6].children();
+		for declaration in body.iter().filter(|n| n.kind() == "varDec") {
+			self.declare(declaration.children(), Kind::Local)?;
+		}
+		let locals = self.scope.values().filter(|v| v.kind == Kind::Local).count();
+		self.emit(format!("function {}.{} {}", self.class, nodes[2].text(), locals));
+		match self.subroutine_kind.as_str() {
+			"constructor" => {
+				self.emit(format!("push constant {}", self.fields));
+				self.emit("call Memory.alloc 1".to_string());
+				self.emit("pop pointer 0".to_string());
+			},
+			"method" => {
+				self.emit("push argument 0".to_string());
+				self.emit("pop pointer 0".to_string());
+			},
+			_ => {},
+		}
+		self.statements(&body[body.len() - 2])
+	}
+
+	fn statements(&mut self, statements: &Node) -> Result<(), String> {
+		for statement in statements.children() {
+			let nodes = statement.children();
+			match statement.kind() {
+				"letStatement" => self.let_statement(nodes)?,
+				"ifStatement" => {
+					let n = self.next_label();
+					self.expression(&nodes[2])?;
+					self.emit("not".to_string());
+					self.emit(format!("if-goto IF_FALSE{}", n));
+					self.statements(&nodes[5])?;
+					self.emit(format!("goto IF_END{}", n));
+					self.emit(format!("label IF_FALSE{}", n));
+					if nodes.len() > 7 {
+						self.statements(&nodes[9])?;
+					}
+					self.emit(format!("label IF_END{}", n));
+				},
+				"whileStatement" => {
+					let n = self.next_label();
+					self.emit(format!("label WHILE_EXP{}", n));
+					self.expression(&nodes[2])?;
+					self.emit("not".to_string());
+					self.emit(format!("if-goto WHILE_END{}", n));
+					self.statements(&nodes[5])?;
+					self.emit(format!("goto WHILE_EXP{}", n));
+					self.emit(format!("label WHILE_END{}", n));
+				},
+				"doStatement" => {
+					self.call(&nodes[1..nodes.len() - 1])?;
+					self.emit("pop temp 0".to_string());
+				},
+				_ => { // returnStatement
+					match nodes.len() {
+						3 => self.expression(&nodes[1])?,
+						_ => self.emit("push constant 0".to_string()),
+					}
+					self.emit("return".to_string());
+				},
+			}
+		}
+		Ok(())
+	}
+
+	fn next_label(&mut self) -> usize {
+		self.labels += 1;
+		self.labels - 1
+	}
+
+	fn let_statement(&mut self, nodes: &[Node]) -> Result<(), String> {
+		let (segment, index) = self.variable(&nodes[1])?;
+		match nodes[2].text() {
+			"[" => {
+				self.emit(format!("push {} {}", segment, index));
+				self.expression(&nodes[3])?;
+				self.emit("add".to_string());
+				self.expression(&nodes[6])?;
+				// the value may itself index an array, so set THAT only now
+				self.emit("pop temp 0".to_string());
+				self.emit("pop pointer 1".to_string());
+				self.emit("push temp 0".to_string());
+				self.emit("pop that 0".to_string());
+			},
+			_ => {
+				self.expression(&nodes[3])?;
+				self.emit(format!("pop {} {}", segment, index));
+			},
+		}
+		Ok(())
+	}
+
+	fn expression(&mut self, expression: &Node) -> Result<(), String> {
+		let nodes = expression.children();
+		self.term(&nodes[0])?;
+		for pair in nodes[1..].chunks(2) {
+			self.term(&pair[1])?;
+			let command = match pair[0].text() {
+				"+" => "add",
+				"-" => "sub",
+				"*" => "call Math.multiply 2",
+				"/" => "call Math.divide 2",
+				"&" => "and",
+				"|" => "or",
+				"<" => "lt",
+				">" => "gt",
+				_ => "eq",
+			};
+			self.emit(command.to_string());
+		}
+		Ok(())
+	}
+
+	fn term(&mut self, term: &Node) -> Result<(), String> {
+		let nodes = term.children();
+		let first = &nodes[0];
+		match first.kind() {
+			"integerConstant" => self.emit(format!("push constant {}", first.text())),
+			"stringConstant" => {
+				let text = first.text();
+				self.emit(format!("push constant {}", text.chars().count()));
+				self.emit("call String.new 1".to_string());
+				for c in text.chars() {
+					self.emit(format!("push constant {}", c as u32));
+					self.emit("call String.appendChar 2".to_string());
+				}
+			},
+			"keyword" => match first.text() {
+				"true" => {
+					self.emit("push constant 0".to_string());
+					self.emit("not".to_string());
+				},
+				"this" if self.subroutine_kind == "function" => {
+					return Err(format!("line {}: this can't be used in a function", first.line()))
+				},
+				"this" => self.emit("push pointer 0".to_string()),
+				_ => self.emit("push constant 0".to_string()), // false and null
+			},
+			"identifier" => match nodes.get(1).map(|n| n.text()) {
+				Some("[") => {
+					let (segment, index) = self.variable(first)?;
+					self.emit(format!("push {} {}", segment, index));
+					self.expression(&nodes[2])?;
+					self.emit("add".to_string());
+					self.emit("pop pointer 1".to_string());
+					self.emit("push that 0".to_string());
+				},
+				Some(_) => self.call(nodes)?,
+				None => {
+					let (segment, index) = self.variable(first)?;
+					self.emit(format!("push {} {}", segment, index));
+				},
+			},
+			_ => match first.text() {
+				"(" => self.expression(&nodes[1])?,
+				unary => {
+					self.term(&nodes[1])?;
+					self.emit(if unary == "-" { "neg" } else { "not" }.to_string());
+				},
+			},
+		}
+		Ok(())
+	}
+
+	/// Compiles `name(arguments)` or `target.name(arguments)`.
+	fn call(&mut self, nodes: &[Node]) -> Result<(), String> {
+		let (function, mut arguments, expressions) = match nodes[1].text() {
+			"." => {
+				let target = &nodes[0];
+				match self.lookup(target.text()) {
+					Some(variable) => { // a method of the object in the variable
+						let (segment, index) = self.variable(target)?;
+						self.emit(format!("push {} {}", segment, index));
+						(format!("{}.{}", variable.type_name, nodes[2].text()), 1, &nodes[4])
+					},
+					None => (format!("{}.{}", target.text(), nodes[2].text()), 0, &nodes[4]),
+				}
+			},
+			_ => { // a method of this object
+				if self.subroutine_kind == "function" {
+					return Err(format!("line {}: method {} can't be called from a function without an object", nodes[0].line(), nodes[0].text()))
+				}
+				self.emit("push pointer 0".to_string());
+				(format!("{}.{}", self.class, nodes[0].text()), 1, &nodes[2])
+			},
+		};
+		for expression in expressions.children().iter().step_by(2) {
+			self.expression(expression)?;
+			arguments += 1;
+		}
+		self.emit(format!("call {} {}", function, arguments));
+		Ok(())
+	}
+}
@@ -0,0 +1,138 @@
+/// Words of RAM the A register can address.
+pub const RAM_SIZE: usize = 32768;
+
+/// Emulation of the Hack CPU and its data memory.
+///
+/// Instructions are executed from their machine code, so the emulator runs
+/// whatever the assembler or linker produced, including the shifts of the
+/// extended instruction set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cpu {
+	pub a: u16,
+	pub d: u16,
+	pub pc: u16,
+	pub ram: Vec<u16>,
+}
+
+impl Cpu {
+	pub fn new() -> Cpu {
+		Cpu {
+			a: 0,
+			d: 0,
+			pc: 0,
+			ram: vec![0; RAM_SIZE],
+		}
+	}
+
+	/// Returns the value of M, the RAM word A points at.
+	pub fn m(&self) -> u16 {
+		self.ram[(self.a & 0x7fff) as usize]
+	}
+
+	/// Executes one instruction and advances PC, as the CPU does in a clock cycle.
+	///
+	/// Like the hardware, a C-instruction reads M and jumps through A as they
+	/// were before the instruction, and writes M at the old address.
+	pub fn execute(&mut self, instruction: u16) {
+		if instruction & 0x8000 == 0 {
+			self.a = instruction;
+			self.pc = self.pc.wrapping_add(1);
+			return
+		}
+
+		let y = match instruction & 0x1000 {
+			0 => self.a,
+			_ => self.m(),
+		};
+		let out = match instruction & 0xe000 {
+			0xa000 => shift(self.d, y, ((instruction >> 6) & 0x3f) as u8),
+			_ => alu(self.d, y, ((instruction >> 6) & 0x3f) as u8),
+		};
+		let address = (self.a & 0x7fff) as usize;
+		let target = self.a;
+
+		if instruction & 0x08 != 0 {
+			self.ram[address] = out;
+		}
+		if instruction & 0x10 != 0 {
+			self.d = out;
+		}
+		if instruction & 0x20 != 0 {
+			self.a = out;
+		}
+
+		let negative = (out as i16) < 0;
+		let jump = (instruction & 0x04 != 0 && negative)
+			|| (instruction & 0x02 != 0 && out == 0)
+			|| (instruction & 0x01 != 0 && !negative && out != 0);
+		self.pc = match jump {
+			true => target,
+			false => self.pc.wrapping_add(1),
+		};
+	}
+
+	/// Fetches and executes the instruction at PC.
+	///
+	/// Returns `false` without doing anything when PC is past the end of the program.
+	pub fn step(&mut self, rom: &[u16]) -> bool {
+		match rom.get(self.pc as usize) {
+			Some(&instruction) => {
+				self.execute(instruction);
+				true
+			},
+			None => false,
+		}
+	}
+}
+
+impl Default for Cpu {
+	fn default() -> Cpu {
+		Cpu::new()
+	}
+}
+
+/// Reads the machine code of a `.hack` file, one 16-digit binary word per line.
+pub fn parse_hack(source: &str) -> Result<Vec<u16>, String> {
+	let mut rom = Vec::new();
+	for (i, line) in source.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() {
+			continue
+		}
+		match u16::from_str_radix(line, 2) {
+			Ok(word) if line.len() == 16 => rom.push(word),
+			_ => return Err(format!("line {}: expected 16 binary digits but found '{}'", i + 1, line)),
+		}
+	}
+	Ok(rom)
+}
+
+/// Computes the result of a shift of the extended instruction set, whose
+/// comp field is `a c1 c2 0 0 0 0`: c1 shifts left rather than right, and c2
+/// shifts D rather than `y` (A or M). Shifting right keeps the sign.
+pub fn shift(x: u16, y: u16, control: u8) -> u16 {
+	let operand = match control & 0x10 {
+		0 => y,
+		_ => x,
+	};
+	match control & 0x20 {
+		0 => ((operand as i16) >> 1) as u16,
+		_ => operand << 1,
+	}
+}
+
+/// Computes the ALU output for inputs `x` (D) and `y` (A or M) and the six
+/// control bits `zx nx zy ny f no`, most significant first.
+pub fn alu(x: u16, y: u16, control: u8) -> u16 {
+	let bit = |n: u8| control & (1 << (5 - n)) != 0;
+	let mut x = if bit(0) { 0 } else { x };
+	if bit(1) {
+		x = !x;
+	}
+	let mut y = if bit(2) { 0 } else { y };
+	if bit(3) {
+		y = !y;
+	}
+	let out = if bit(4) { x.wrapping_add(y) } else { x & y };
+	if bit(5) { !out } else { out }
+}
@@ -0,0 +1,404 @@
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use assembler::{Assembler, SymbolTable};
+use debugger::{Debugger, Event, Mode};
+use flow;
+use instruction::{self, Instruction};
+use json::Json;
+use lsp::{read_message, write_message};
+use preprocessor;
+use program::Program;
+use sourcemap::SourceMap;
+
+/// The only thread a Hack program has.
+const THREAD: i64 = 1;
+
+// variablesReference of each scope
+const REGISTERS: i64 = 1;
+const VARIABLES: i64 = 2;
+
+/// Debug adapter for Hack assembly, speaking the Debug Adapter Protocol over stdio.
+///
+/// The program runs on the emulated CPU, assembled with the options of the
+/// launch request, and a source map takes ROM addresses back to `.asm`
+/// lines. Lines are numbered from 1. The program runs to completion of each
+/// request, so `pause` has nothing to interrupt.
+pub struct Server {
+	seq: i64,
+	session: Option<Session>,
+	/// Breakpoint lines, kept to set again when the program is launched.
+	breakpoints: Vec<usize>,
+	configured: bool,
+	disconnected: bool,
+}
+
+struct Session {
+	debugger: Debugger,
+	path: String,
+	stop_on_entry: bool,
+	started: bool,
+}
+
+impl Server {
+	pub fn new() -> Server {
+		Server {
+			seq: 0,
+			session: None,
+			breakpoints: Vec::new(),
+			configured: false,
+			disconnected: false,
+		}
+	}
+
+	/// Serves requests from `input` until the client disconnects or closes the stream.
+	pub fn run<R: BufRead, W: Write>(&mut self, input: &mut R, output: &mut W) -> Result<(), String> {
+		while let Some(message) = read_message(input)? {
+			for reply in self.handle(&message) {
+				write_message(output, &reply)?;
+			}
+			if self.disconnected {
+				break
+			}
+		}
+		Ok(())
+	}
+
+	/// Handles a request, returning the response followed by any events to send.
+	pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+		let command = message.get("command").as_str().unwrap_or("").to_string();
+		let arguments = message.get("arguments");
+		let mut events = Vec::new();
+
+		let result = match command.as_str() {
+			"initialize" => {
+				events.push(("initialized", Json::Null));
+				Ok(capabilities())
+			},
+			"launch" => self.launch(arguments).map(|_| {
+				events.extend(self.start());
+				Json::Null
+			}),
+			"setBreakpoints" => Ok(self.set_breakpoints(arguments)),
+			"setExceptionBreakpoints" => Ok(Json::object(vec![("breakpoints", Json::Array(Vec::new()))])),
+			"configurationDone" => {
+				self.configured = true;
+				events.extend(self.start());
+				Ok(Json::Null)
+			},
+			"threads" => Ok(Json::object(vec![("threads", Json::Array(vec![
+				Json::object(vec![("id", Json::from(THREAD)), ("name", Json::from("main"))]),
+			]))])),
+			"pause" => Ok(Json::Null),
+			"disconnect" | "terminate" => {
+				self.disconnected = true;
+				Ok(Json::Null)
+			},
+			_ => match self.session {
+				Some(ref mut session) => session.handle(&command, arguments, &mut events),
+				None => Err(format!("unknown command {} or no program launched", command)),
+			},
+		};
+
+		let mut replies = vec![self.response(message, &command, result)];
+		for (event, body) in events {
+			replies.push(self.event(event, body));
+		}
+		replies
+	}
+
+	fn launch(&mut self, arguments: &Json) -> Result<(), String> {
+		let path = arguments.get("program").as_str().ok_or("launch needs the path of the program")?;
+		let source = fs::read_to_string(path).map_err(|why| format!("couldn't read {}: {}", path, why))?;
+
+		// assemble as `hack` would with the same options, so addresses match the .hack file
+		let mut assembler = Assembler::new(path);
+		assembler.set_quiet(true);
+		assembler.set_pseudo_instructions(!arguments.get("strict").as_bool().unwrap_or(false));
+		assembler.set_extended(arguments.get("extended").as_bool().unwrap_or(false));
+		assembler.set_optimize(arguments.get("optimize").as_bool().unwrap_or(false));
+		for define in arguments.get("defines").as_array() {
+			let define = define.as_str().ok_or("defines must be strings such as \"DEBUG\" or \"LEVEL=2\"")?;
+			let (name, value) = preprocessor::parse_define(define)?;
+			assembler.define(&name, value);
+		}
+		let mut lines = assembler.read_source()?;
+		if arguments.get("stripUnreachable").as_bool().unwrap_or(false) {
+			lines = flow::strip_unreachable(&instruction::parse(&lines)).into_iter().map(|s| s.line).collect();
+		}
+		let program = Program::assemble_extended(&lines, assembler.extended()).map_err(|errors| {
+			let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+			format!("{} doesn't assemble:\n{}", path, errors.join("\n"))
+		})?;
+
+		let mut debugger = Debugger::new(program, &source);
+		if let Some(file) = arguments.get("sourceMap").as_str() {
+			let text = fs::read_to_string(file).map_err(|why| format!("couldn't read {}: {}", file, why))?;
+			let source_map = SourceMap::parse(&text).map_err(|why| format!("{}: {}", file, why))?;
+			debugger.set_source_map(source_map).map_err(|why| format!("{}: {}", file, why))?;
+		}
+
+		let mut session = Session {
+			debugger,
+			path: path.to_string(),
+			stop_on_entry: arguments.get("stopOnEntry").as_bool().unwrap_or(false),
+			started: false,
+		};
+		for &line in &self.breakpoints {
+			let _ = session.debugger.add_breakpoint(&line.to_string());
+		}
+		self.session = Some(session);
+		Ok(())
+	}
+
+	/// Starts the program once it's launched and the client has set its breakpoints.
+	fn start(&mut self) -> Vec<(&'static str, Json)> {
+		let session = match self.session {
+			Some(ref mut session) if self.configured && !session.started => session,
+			_ => return Vec::new(),
+		};
+		session.started = true;
+		match session.stop_on_entry {
+			true => vec![stopped("entry", None)],
+			false => session.resume(Mode::Continue),
+		}
+	}
+
+	fn set_breakpoints(&mut self, arguments: &Json) -> Json {
+		self.breakpoints = arguments.get("breakpoints").as_array().iter()
+			.filter_map(|b| b.get("line").as_i64())
+			.map(|line| line as usize)
+			.collect();
+
+		let breakpoints: Vec<Json> = match self.session {
+			Some(ref mut session) => {
+				session.debugger.clear_breakpoints();
+				self.breakpoints.iter().map(|&line| match session.debugger.add_breakpoint(&line.to_string()) {
+					Ok(_) => breakpoint(true, line, None),
+					Err(why) => breakpoint(false, line, Some(why)),
+				}).collect()
+			},
+			None => self.breakpoints.iter().map(|&line| breakpoint(false, line, Some("no program launched".to_string()))).collect(),
+		};
+		Json::object(vec![("breakpoints", Json::Array(breakpoints))])
+	}
+
+	fn response(&mut self, request: &Json, command: &str, result: Result<Json, String>) -> Json {
+		self.seq += 1;
+		let mut members = vec![
+			("seq", Json::from(self.seq)),
+			("type", Json::from("response")),
+			("request_seq", request.get("seq").clone()),
+			("command", Json::from(command)),
+		];
+		match result {
+			Ok(body) => {
+				members.push(("success", Json::from(true)));
+				if !body.is_null() {
+					members.push(("body", body));
+				}
+			},
+			Err(message) => {
+				members.push(("success", Json::from(false)));
+				members.push(("message", Json::from(message)));
+			},
+		}
+		Json::object(members)
+	}
+
+	fn event(&mut self, event: &str, body: Json) -> Json {
+		self.seq += 1;
+		let mut members = vec![("seq", Json::from(self.seq)), ("type", Json::from("event")), ("event", Json::from(event))];
+		if !body.is_null() {
+			members.push(("body", body));
+		}
+		Json::object(members)
+	}
+}
+
+impl Default for Server {
+	fn default() -> Server {
+		Server::new()
+	}
+}
+
+impl Session {
+	/// Handles the requests that need a launched program.
+	fn handle(&mut self, command: &str, arguments: &Json, events: &mut Vec<(&'static str, Json)>) -> Result<Json, String> {
+		match command {
+			"stackTrace" => Ok(self.stack_trace()),
+			"scopes" => Ok(Json::object(vec![("scopes", Json::Array(vec![
+				scope("Registers", REGISTERS),
+				scope("Variables", VARIABLES),
+			]))])),
+			"variables" => Ok(self.variables(arguments.get("variablesReference").as_i64().unwrap_or(0))),
+			"setVariable" => {
+				let name = arguments.get("name").as_str().unwrap_or("");
+				let value = arguments.get("value").as_str().unwrap_or("");
+				self.debugger.set(name, Some(value))?;
+				Ok(Json::object(vec![("value", Json::from(self.value(name)?))]))
+			},
+			"evaluate" => {
+				let expression = arguments.get("expression").as_str().unwrap_or("").trim();
+				Ok(Json::object(vec![
+					("result", Json::from(self.debugger.print(expression)?)),
+					("variablesReference", Json::from(0i64)),
+				]))
+			},
+			"continue" => {
+				events.extend(self.resume(Mode::Continue));
+				Ok(Json::object(vec![("allThreadsContinued", Json::from(true))]))
+			},
+			"stepOut" => {
+				events.extend(self.resume(Mode::Return));
+				Ok(Json::Null)
+			},
+			"next" => {
+				events.extend(self.resume(Mode::Line));
+				Ok(Json::Null)
+			},
+			"stepIn" => {
+				events.extend(self.resume(Mode::Instruction));
+				Ok(Json::Null)
+			},
+			"stepBack" => match self.debugger.step_back() {
+				true => {
+					events.push(stopped("step", None));
+					Ok(Json::Null)
+				},
+				false => Err("no more history to step back through".to_string()),
+			},
+			_ => Err(format!("unknown command {}", command)),
+		}
+	}
+
+	/// Runs the program, returning the events that say why it stopped.
+	fn resume(&mut self, mode: Mode) -> Vec<(&'static str, Json)> {
+		match self.debugger.run(mode) {
+			Event::Step => vec![stopped("step", None)],
+			Event::Breakpoint(_) if mode == Mode::Line => vec![stopped("step", None)],
+			Event::Breakpoint(why) => vec![stopped("breakpoint", Some(why))],
+			Event::Watchpoint(why) => vec![stopped("data breakpoint", Some(why))],
+			Event::Limit => vec![stopped("pause", Some("the program ran too long without stopping".to_string()))],
+			Event::Halted | Event::Ended => vec![
+				("exited", Json::object(vec![("exitCode", Json::from(0i64))])),
+				("terminated", Json::Null),
+			],
+		}
+	}
+
+	fn stack_trace(&self) -> Json {
+		let program = &self.debugger.program;
+		let pc = self.debugger.cpu.pc;
+		// the function is whichever label the code at PC follows
+		let name = program.labels.iter()
+			.filter(|l| l.1 <= pc)
+			.max_by_key(|l| l.1)
+			.map(|l| l.0.clone())
+			.unwrap_or_else(|| "main".to_string());
+
+		let mut frame = vec![
+			("id", Json::from(0i64)),
+			("name", Json::from(name)),
+			("instructionPointerReference", Json::from(pc.to_string())),
+		];
+		match self.debugger.line(pc) {
+			Some(line) => {
+				let file = Path::new(&self.path).file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+				frame.push(("source", Json::object(vec![("name", Json::from(file)), ("path", Json::from(self.path.as_str()))])));
+				frame.push(("line", Json::from(line)));
+			},
+			None => frame.push(("line", Json::from(0i64))),
+		}
+		frame.push(("column", Json::from(1i64)));
+		Json::object(vec![("stackFrames", Json::Array(vec![Json::object(frame)])), ("totalFrames", Json::from(1i64))])
+	}
+
+	fn variables(&self, reference: i64) -> Json {
+		let cpu = &self.debugger.cpu;
+		let variables: Vec<(String, u16)> = match reference {
+			REGISTERS => vec![
+				("A".to_string(), cpu.a),
+				("D".to_string(), cpu.d),
+				("M".to_string(), cpu.m()),
+				("PC".to_string(), cpu.pc),
+			],
+			VARIABLES => self.names().into_iter().map(|(name, address)| (name, cpu.ram[address as usize])).collect(),
+			_ => Vec::new(),
+		};
+		let variables: Vec<Json> = variables.into_iter().map(|(name, value)| Json::object(vec![
+			("name", Json::from(name)),
+			("value", Json::from((value as i16).to_string())),
+			("variablesReference", Json::from(0i64)),
+		])).collect();
+		Json::object(vec![("variables", Json::Array(variables))])
+	}
+
+	/// Returns the program's variables, then the predefined symbols it uses, with their RAM addresses.
+	fn names(&self) -> Vec<(String, u16)> {
+		let program = &self.debugger.program;
+		let mut names = program.variables.clone();
+		for statement in &program.statements {
+			if let Instruction::A(ref symbol) = statement.instruction {
+				if SymbolTable::is_predefined(symbol) && !names.iter().any(|n| n.0 == *symbol) {
+					names.push((symbol.clone(), program.symbol(symbol).unwrap_or(0)));
+				}
+			}
+		}
+		names
+	}
+
+	fn value(&self, name: &str) -> Result<String, String> {
+		let cpu = &self.debugger.cpu;
+		let value = match name {
+			"A" => cpu.a,
+			"D" => cpu.d,
+			"M" => cpu.m(),
+			"PC" => cpu.pc,
+			_ => match self.names().iter().find(|n| n.0 == name) {
+				Some(&(_, address)) => cpu.ram[address as usize],
+				None => return Err(format!("unknown variable {}", name)),
+			},
+		};
+		Ok((value as i16).to_string())
+	}
+}
+
+fn capabilities() -> Json {
+	Json::object(vec![
+		("supportsConfigurationDoneRequest", Json::from(true)),
+		("supportsStepBack", Json::from(true)),
+		("supportsSetVariable", Json::from(true)),
+		("supportsEvaluateForHovers", Json::from(true)),
+		("supportsTerminateRequest", Json::from(true)),
+	])
+}
+
+fn stopped(reason: &str, description: Option<String>) -> (&'static str, Json) {
+	let mut body = vec![
+		("reason", Json::from(reason)),
+		("threadId", Json::from(THREAD)),
+		("allThreadsStopped", Json::from(true)),
+	];
+	if let Some(description) = description {
+		body.push(("description", Json::from(description)));
+	}
+	("stopped", Json::object(body))
+}
+
+fn scope(name: &str, reference: i64) -> Json {
+	Json::object(vec![
+		("name", Json::from(name)),
+		("variablesReference", Json::from(reference)),
+		("expensive", Json::from(false)),
+	])
+}
+
+fn breakpoint(verified: bool, line: usize, message: Option<String>) -> Json {
+	let mut members = vec![("verified", Json::from(verified)), ("line", Json::from(line))];
+	if let Some(message) = message {
+		members.push(("message", Json::from(message)));
+	}
+	Json::object(members)
+}
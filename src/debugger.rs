@@ -0,0 +1,425 @@
+use std::collections::VecDeque;
+
+use cpu::Cpu;
+use program::Program;
+use sourcemap::SourceMap;
+
+/// How many instructions `reverse-step` can undo.
+pub const HISTORY_SIZE: usize = 100_000;
+
+/// How many instructions `continue` runs before giving up on reaching a stop.
+pub const STEP_LIMIT: usize = 10_000_000;
+
+const HELP: &str = "break LABEL|LINE    stop before the label or source line (b)\n\
+	delete N            remove breakpoint or watchpoint N (d)\n\
+	watch ADDRESS|NAME  stop when a RAM word changes (w)\n\
+	step                run one instruction (s)\n\
+	next                run to the next source line (n)\n\
+	continue            run to a breakpoint, watchpoint or the end (c)\n\
+	reverse-step        undo the last instruction (rs)\n\
+	registers           show A, D, M and PC (r)\n\
+	print A|D|M|PC|NAME|ADDRESS  show a register or RAM word (p)\n\
+	x ADDRESS [COUNT]   show COUNT words of RAM\n\
+	set A|D|PC|NAME|ADDRESS VALUE  change a register or RAM word\n\
+	list                show the source around PC (l)\n\
+	info                list breakpoints and watchpoints (i)\n\
+	quit                leave (q)";
+
+/// Where execution should stop.
+enum Stop {
+	/// Before executing the instruction at this ROM address.
+	Breakpoint { address: u16, description: String },
+	/// When the RAM word changes.
+	Watchpoint { address: u16, description: String, value: u16 },
+}
+
+/// How far `Debugger::run` goes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+	/// One instruction.
+	Instruction,
+	/// To the next source line, e.g. over the expansion of a pseudo-instruction.
+	Line,
+	/// To a breakpoint, a watchpoint or the end of the program.
+	Continue,
+	/// Past the next jump to a computed address, which is how a function
+	/// returns, e.g. with `@R13`, `A=M`, `0;JMP`.
+	Return,
+}
+
+/// Why `Debugger::run` stopped.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+	/// The step finished.
+	Step,
+	/// PC reached a breakpoint; describes it.
+	Breakpoint(String),
+	/// Watched RAM words changed; describes the changes.
+	Watchpoint(String),
+	/// PC reached the loop the program ends with.
+	Halted,
+	/// PC is past the last instruction.
+	Ended,
+	/// `STEP_LIMIT` instructions ran without stopping.
+	Limit,
+}
+
+/// What an instruction changed, so it can be undone.
+struct Undo {
+	pc: u16,
+	a: u16,
+	d: u16,
+	ram: Option<(usize, u16)>,
+}
+
+/// Source-level debugger running a program on the emulated CPU.
+///
+/// Breakpoints stop before an instruction executes, watchpoints after the
+/// instruction that changed the word. The last `HISTORY_SIZE` instructions
+/// can be undone one at a time. Source lines are resolved through a
+/// `SourceMap`.
+pub struct Debugger {
+	pub program: Program,
+	pub cpu: Cpu,
+	pub source_map: SourceMap,
+	source: Vec<String>,
+	stops: Vec<Option<Stop>>,
+	history: VecDeque<Undo>,
+}
+
+impl Debugger {
+	/// Creates a debugger for `program`, assembled from `source`.
+	pub fn new(program: Program, source: &str) -> Debugger {
+		Debugger {
+			source_map: SourceMap::from_program(&program, ""),
+			program,
+			cpu: Cpu::new(),
+			source: source.lines().map(|l| l.to_string()).collect(),
+			stops: Vec::new(),
+			history: VecDeque::new(),
+		}
+	}
+
+	/// Resolves source lines through `source_map`, such as one read from the
+	/// `.sourcemap.json` written by `--source-map`, rather than the map of
+	/// the program's own statements.
+	pub fn set_source_map(&mut self, source_map: SourceMap) -> Result<(), String> {
+		let size = self.program.code.len();
+		match source_map.mappings.iter().find(|m| m.address as usize >= size) {
+			Some(mapping) => Err(format!("the source map maps ROM {}, but the program is {} words", mapping.address, size)),
+			None => {
+				self.source_map = source_map;
+				Ok(())
+			},
+		}
+	}
+
+	/// Returns the source line of the instruction at `address`.
+	pub fn line(&self, address: u16) -> Option<usize> {
+		self.source_map.lookup(address).map(|m| m.line)
+	}
+
+	/// Runs a debugger command, returning the text to show.
+	pub fn execute(&mut self, command: &str) -> String {
+		let words: Vec<&str> = command.split_whitespace().collect();
+		let argument = words.get(1).cloned().unwrap_or("");
+		let result = match words.first().cloned().unwrap_or("") {
+			"help" | "h" => Ok(HELP.to_string()),
+			"break" | "b" => self.add_breakpoint(argument),
+			"watch" | "w" => self.add_watchpoint(argument),
+			"delete" | "d" => self.delete(argument),
+			"info" | "i" => Ok(self.info()),
+			"step" | "s" => Ok(self.report(Mode::Instruction)),
+			"next" | "n" => Ok(self.report(Mode::Line)),
+			"continue" | "c" => Ok(self.report(Mode::Continue)),
+			"reverse-step" | "rs" => Ok(self.reverse_step()),
+			"registers" | "r" => Ok(self.registers()),
+			"print" | "p" => self.print(argument),
+			"x" => self.examine(argument, words.get(2).cloned()),
+			"set" => self.set(argument, words.get(2).cloned()),
+			"list" | "l" => Ok(self.list()),
+			"" => Ok(String::new()),
+			other => Err(format!("unknown command {}, try help", other)),
+		};
+		result.unwrap_or_else(|why| format!("error: {}", why))
+	}
+
+	/// Describes the instruction at PC.
+	pub fn location(&self) -> String {
+		match self.program.statements.get(self.cpu.pc as usize) {
+			Some(statement) => format!("ROM {}, line {}: {}", self.cpu.pc, self.line(self.cpu.pc).unwrap_or(0), statement.instruction),
+			None => format!("ROM {}: past the end of the program", self.cpu.pc),
+		}
+	}
+
+	/// Adds a breakpoint on a label or source line, describing where it is.
+	pub fn add_breakpoint(&mut self, target: &str) -> Result<String, String> {
+		let (address, description) = match target.parse::<usize>() {
+			Ok(line) => match self.source_map.addresses(&self.source_map.file, line).first() {
+				Some(&address) => (address, format!("line {}", line)),
+				None => return Err(format!("no instruction on line {}", line)),
+			},
+			Err(_) => match self.program.labels.iter().find(|l| l.0 == target) {
+				Some(&(ref label, address)) => (address, label.clone()),
+				None => return Err(format!("expected a label or line number but found '{}'", target)),
+			},
+		};
+		let line = self.line(address).map(|l| format!(" (ROM {}, line {})", address, l)).unwrap_or_default();
+		self.stops.push(Some(Stop::Breakpoint { address, description: description.clone() }));
+		Ok(format!("breakpoint {} at {}{}", self.stops.len(), description, line))
+	}
+
+	fn add_watchpoint(&mut self, target: &str) -> Result<String, String> {
+		let address = self.address(target)?;
+		let description = match target.parse::<u16>() {
+			Ok(_) => format!("RAM[{}]", address),
+			Err(_) => format!("{} (RAM[{}])", target, address),
+		};
+		let value = self.cpu.ram[address as usize];
+		self.stops.push(Some(Stop::Watchpoint { address, description: description.clone(), value }));
+		Ok(format!("watchpoint {} on {}", self.stops.len(), description))
+	}
+
+	fn delete(&mut self, number: &str) -> Result<String, String> {
+		match number.parse::<usize>() {
+			Ok(n) if n >= 1 && n <= self.stops.len() && self.stops[n - 1].is_some() => {
+				self.stops[n - 1] = None;
+				Ok(format!("deleted {}", n))
+			},
+			_ => Err(format!("no breakpoint or watchpoint {}", number)),
+		}
+	}
+
+	fn info(&self) -> String {
+		let lines: Vec<String> = self.stops.iter().enumerate().filter_map(|(i, stop)| match *stop {
+			Some(Stop::Breakpoint { address, ref description }) => Some(format!("{}: breakpoint at {} (ROM {})", i + 1, description, address)),
+			Some(Stop::Watchpoint { ref description, .. }) => Some(format!("{}: watchpoint on {}", i + 1, description)),
+			None => None,
+		}).collect();
+		match lines.is_empty() {
+			true => "no breakpoints or watchpoints".to_string(),
+			false => lines.join("\n"),
+		}
+	}
+
+	/// Returns the RAM address of a number or a variable or predefined symbol.
+	fn address(&self, target: &str) -> Result<u16, String> {
+		let address = match target.parse::<u16>() {
+			Ok(address) => address,
+			Err(_) if self.program.labels.iter().any(|l| l.0 == target) => {
+				return Err(format!("{} is a label, not a variable", target))
+			},
+			Err(_) => self.program.symbol(target).ok_or(format!("unknown variable {}", target))?,
+		};
+		match (address as usize) < self.cpu.ram.len() {
+			true => Ok(address),
+			false => Err(format!("address {} is outside RAM", address)),
+		}
+	}
+
+	/// Executes one instruction, recording how to undo it.
+	///
+	/// Returns `false` if PC is past the end of the program.
+	fn execute_one(&mut self) -> bool {
+		let word = match self.program.code.get(self.cpu.pc as usize) {
+			Some(&word) => word,
+			None => return false,
+		};
+		let writes_m = word & 0x8000 != 0 && word & 0x08 != 0;
+		let address = (self.cpu.a & 0x7fff) as usize;
+		let ram = match writes_m {
+			true => Some((address, self.cpu.ram[address])),
+			false => None,
+		};
+		if self.history.len() == HISTORY_SIZE {
+			self.history.pop_front();
+		}
+		self.history.push_back(Undo { pc: self.cpu.pc, a: self.cpu.a, d: self.cpu.d, ram });
+		self.cpu.execute(word);
+		true
+	}
+
+	/// Returns why execution should stop after the last instruction, if it should.
+	fn check_watchpoints(&mut self) -> Option<String> {
+		let mut reasons = Vec::new();
+		for (i, stop) in self.stops.iter_mut().enumerate() {
+			if let Some(Stop::Watchpoint { address, ref description, ref mut value }) = *stop {
+				let current = self.cpu.ram[address as usize];
+				if current != *value {
+					reasons.push(format!("watchpoint {}: {} changed from {} to {}", i + 1, description, *value as i16, current as i16));
+					*value = current;
+				}
+			}
+		}
+		match reasons.is_empty() {
+			true => None,
+			false => Some(reasons.join("\n")),
+		}
+	}
+
+	fn breakpoint_at(&self, pc: u16) -> Option<String> {
+		self.stops.iter().enumerate().find_map(|(i, stop)| match *stop {
+			Some(Stop::Breakpoint { address, ref description }) if address == pc => Some(format!("breakpoint {} at {}", i + 1, description)),
+			_ => None,
+		})
+	}
+
+	/// Runs the program as far as `mode` says, or until something stops it.
+	pub fn run(&mut self, mode: Mode) -> Event {
+		let line = self.line(self.cpu.pc);
+		for _ in 0..STEP_LIMIT {
+			if matches!(mode, Mode::Continue | Mode::Return) && self.program.is_halt(self.cpu.pc as usize) {
+				return Event::Halted
+			}
+			let pc = self.cpu.pc;
+			if !self.execute_one() {
+				return Event::Ended
+			}
+			if let Some(watch) = self.check_watchpoints() {
+				return Event::Watchpoint(watch)
+			}
+			if mode == Mode::Instruction {
+				return Event::Step
+			}
+			if mode == Mode::Return && self.cpu.pc != pc + 1 && self.jumps_to_computed(pc) {
+				return Event::Step
+			}
+			if let Some(breakpoint) = self.breakpoint_at(self.cpu.pc) {
+				return Event::Breakpoint(breakpoint)
+			}
+			if mode == Mode::Line && self.line(self.cpu.pc) != line {
+				return Event::Step
+			}
+		}
+		Event::Limit
+	}
+
+	/// Returns `true` if the instruction at `address` jumps, and A wasn't
+	/// loaded by an A-instruction right before it.
+	fn jumps_to_computed(&self, address: u16) -> bool {
+		let jumps = self.program.code.get(address as usize).is_some_and(|&word| word & 0x8000 != 0 && word & 7 != 0);
+		let loaded = address > 0 && self.program.code[address as usize - 1] & 0x8000 == 0;
+		jumps && !loaded
+	}
+
+	/// Undoes the last instruction, returning `false` when there is no history left.
+	pub fn step_back(&mut self) -> bool {
+		let undo = match self.history.pop_back() {
+			Some(undo) => undo,
+			None => return false,
+		};
+		self.cpu.pc = undo.pc;
+		self.cpu.a = undo.a;
+		self.cpu.d = undo.d;
+		if let Some((address, value)) = undo.ram {
+			self.cpu.ram[address] = value;
+		}
+		// don't report the undone change as a watchpoint hit
+		for stop in self.stops.iter_mut() {
+			if let Some(Stop::Watchpoint { address, ref mut value, .. }) = *stop {
+				*value = self.cpu.ram[address as usize];
+			}
+		}
+		true
+	}
+
+	/// Removes every breakpoint, leaving watchpoints in place.
+	pub fn clear_breakpoints(&mut self) {
+		for stop in self.stops.iter_mut() {
+			if let Some(Stop::Breakpoint { .. }) = *stop {
+				*stop = None;
+			}
+		}
+	}
+
+	fn report(&mut self, mode: Mode) -> String {
+		let reason = match self.run(mode) {
+			Event::Step => return self.location(),
+			Event::Breakpoint(_) if mode == Mode::Line => return self.location(),
+			Event::Breakpoint(why) | Event::Watchpoint(why) => why,
+			Event::Halted => "the program has halted".to_string(),
+			Event::Ended => "the program has ended".to_string(),
+			Event::Limit => format!("stopped after {} instructions", STEP_LIMIT),
+		};
+		format!("{}\n{}", reason, self.location())
+	}
+
+	fn reverse_step(&mut self) -> String {
+		match self.step_back() {
+			true => self.location(),
+			false => "error: no more history to reverse".to_string(),
+		}
+	}
+
+	fn registers(&self) -> String {
+		format!("A={} D={} M={} PC={}", self.cpu.a as i16, self.cpu.d as i16, self.cpu.m() as i16, self.cpu.pc)
+	}
+
+	/// Describes the value of a register, variable or RAM word.
+	pub fn print(&self, target: &str) -> Result<String, String> {
+		let value = match target {
+			"A" => self.cpu.a,
+			"D" => self.cpu.d,
+			"M" => self.cpu.m(),
+			"PC" => self.cpu.pc,
+			"" => return Err("expected print A|D|M|PC|NAME|ADDRESS".to_string()),
+			_ => {
+				let address = self.address(target)?;
+				return Ok(format!("{} = RAM[{}] = {}", target, address, self.cpu.ram[address as usize] as i16))
+			},
+		};
+		Ok(format!("{} = {}", target, value as i16))
+	}
+
+	/// Changes a register, variable or RAM word, describing its new value.
+	pub fn set(&mut self, target: &str, value: Option<&str>) -> Result<String, String> {
+		let value = match value.map(|v| v.parse::<i32>()) {
+			Some(Ok(value)) if (-32768..=65535).contains(&value) => value as u16,
+			_ => return Err("expected set A|D|PC|NAME|ADDRESS VALUE".to_string()),
+		};
+		match target {
+			"A" => self.cpu.a = value,
+			"D" => self.cpu.d = value,
+			"PC" => self.cpu.pc = value,
+			_ => {
+				let address = self.address(target)? as usize;
+				self.cpu.ram[address] = value;
+				// a change made by hand isn't a watchpoint hit
+				for stop in self.stops.iter_mut() {
+					if let Some(Stop::Watchpoint { address: watched, ref mut value, .. }) = *stop {
+						if watched as usize == address {
+							*value = self.cpu.ram[address];
+						}
+					}
+				}
+			},
+		}
+		self.print(target)
+	}
+
+	fn examine(&self, target: &str, count: Option<&str>) -> Result<String, String> {
+		let start = self.address(target)? as usize;
+		let count = match count.map(|c| c.parse::<usize>()) {
+			Some(Ok(count)) => count,
+			Some(Err(_)) => return Err("expected x ADDRESS [COUNT]".to_string()),
+			None => 1,
+		};
+		let end = start.saturating_add(count).min(self.cpu.ram.len());
+		let lines: Vec<String> = (start..end).map(|a| format!("RAM[{}] = {}", a, self.cpu.ram[a] as i16)).collect();
+		Ok(lines.join("\n"))
+	}
+
+	fn list(&self) -> String {
+		let current = match self.line(self.cpu.pc) {
+			Some(line) => line,
+			None => return self.location(),
+		};
+		let first = current.saturating_sub(5).max(1);
+		let last = (current + 5).min(self.source.len());
+		let lines: Vec<String> = (first..=last).map(|l| {
+			let marker = if l == current { "=>" } else { "  " };
+			format!("{} {:>4}  {}", marker, l, self.source[l - 1])
+		}).collect();
+		lines.join("\n")
+	}
+}
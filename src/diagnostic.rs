@@ -0,0 +1,53 @@
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+	Warning,
+	Error,
+}
+
+/// A problem found in a program, tied to the source line it was found on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+	pub severity: Severity,
+	pub line: usize,
+	pub message: String,
+}
+
+impl Diagnostic {
+	pub fn warning(line: usize, message: &str) -> Diagnostic {
+		Diagnostic {
+			severity: Severity::Warning,
+			line,
+			message: message.to_string(),
+		}
+	}
+
+	pub fn error(line: usize, message: &str) -> Diagnostic {
+		Diagnostic {
+			severity: Severity::Error,
+			line,
+			message: message.to_string(),
+		}
+	}
+
+	/// Turns an error of the preprocessor or the pseudo-instruction expander,
+	/// such as `line 3: .endif without .if`, into a diagnostic.
+	pub fn from_error(error: &str) -> Diagnostic {
+		let located = error.strip_prefix("line ").and_then(|rest| rest.split_once(": "));
+		match located.and_then(|(line, message)| line.parse::<usize>().ok().map(|line| (line, message))) {
+			Some((line, message)) => Diagnostic::error(line, message),
+			None => Diagnostic::error(1, error),
+		}
+	}
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let severity = match self.severity {
+			Severity::Warning => "warning",
+			Severity::Error => "error",
+		};
+		write!(f, "line {}: {}: {}", self.line, severity, self.message)
+	}
+}
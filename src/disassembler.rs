@@ -0,0 +1,46 @@
+use assembler;
+use instruction::{COMPS, DESTS, JUMPS, SHIFTS};
+
+/// Returns the assembly of a word of machine code, such as `@21` or
+/// `AM=M+1;JGT`, or `None` when it isn't an instruction the assembler makes.
+///
+/// The shifts of the extended instruction set, with the `101` prefix, are
+/// understood too, so `1010110000010000` is `D=D<<`.
+pub fn disassemble(word: u16) -> Option<String> {
+	if word & 0x8000 == 0 {
+		return Some(format!("@{}", word))
+	}
+	let comps: &[&str] = match word & 0xe000 {
+		0xe000 => &COMPS,
+		0xa000 => &SHIFTS,
+		_ => return None,
+	};
+	let comp = comps.iter().find(|comp| assembler::encode_c(None, comp, None) & 0x1fc0 == word & 0x1fc0)?;
+
+	let mut text = String::new();
+	let dest = ((word >> 3) & 7) as usize;
+	if dest != 0 {
+		text += DESTS[dest - 1];
+		text.push('=');
+	}
+	text += comp;
+	let jump = (word & 7) as usize;
+	if jump != 0 {
+		text.push(';');
+		text += JUMPS[jump - 1];
+	}
+	Some(text)
+}
+
+/// Disassembles a program, one instruction per line, failing at the first
+/// word that isn't an instruction.
+pub fn disassemble_program(code: &[u16]) -> Result<String, String> {
+	let mut text = String::new();
+	for (address, &word) in code.iter().enumerate() {
+		match disassemble(word) {
+			Some(instruction) => text += &format!("{}\n", instruction),
+			None => return Err(format!("ROM {}: {:016b} isn't an instruction", address, word)),
+		}
+	}
+	Ok(text)
+}
@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+
+use assembler::{self, SymbolTable};
+use instruction::{Instruction, SHIFTS};
+use program::Program;
+
+/// The ALU control bits, in the order they appear in the comp field.
+const CONTROL_BITS: [(&str, &str); 6] = [
+	("zx", "x=0"),
+	("nx", "x=!x"),
+	("zy", "y=0"),
+	("ny", "y=!y"),
+	("f", "out=x+y"),
+	("no", "out=!out"),
+];
+
+/// Describes how every instruction of a program is encoded, field by field.
+pub fn explain(program: &Program) -> String {
+	let labels: HashSet<&str> = program.labels.iter().map(|l| l.0.as_str()).collect();
+	let mut allocated: HashSet<&str> = HashSet::new();
+
+	let mut text = String::new();
+	for (address, statement) in program.statements.iter().enumerate() {
+		let word = program.code[address];
+		text += &format!("ROM {} (line {}): {}\n", address, statement.line.line, statement.instruction);
+		match statement.instruction {
+			Instruction::A(ref value) => {
+				text += &format!("  0 {:015b}\n", word);
+				let resolution = match value.parse::<u16>() {
+					Ok(_) => format!("constant {}", word),
+					Err(_) if labels.contains(value.as_str()) => format!("label {}, at ROM {}", value, word),
+					Err(_) if SymbolTable::is_predefined(value) => format!("predefined symbol {} = {}", value, word),
+					Err(_) if allocated.insert(value) => format!("new variable {}, allocated at RAM {}", value, word),
+					Err(_) => format!("variable {}, at RAM {}", value, word),
+				};
+				text += &format!("  A-instruction: A = {}\n", resolution);
+			},
+			Instruction::C { ref dest, ref comp, ref jump } => {
+				let (comp_bits, dest_bits, jump_bits) = assembler::c_fields(dest.as_deref(), comp, jump.as_deref());
+				text += &format!("  {:03b} {} {} {} {}\n", word >> 13, &comp_bits[..1], &comp_bits[1..], dest_bits, jump_bits);
+				text += &format!("  a    {}       {}\n", &comp_bits[..1], match &comp_bits[..1] {
+					"1" => "y is M",
+					_ => "y is A",
+				});
+				match SHIFTS.contains(&comp.as_str()) {
+					true => text += &format!("  comp {}  {}, out = {}\n", &comp_bits[1..], shift(&comp_bits[1..]), comp),
+					false => text += &format!("  comp {}  {}  x is D, out = {}\n", &comp_bits[1..], control(&comp_bits[1..]), comp),
+				}
+				text += &format!("  dest {}     {}\n", dest_bits, written(dest.as_deref()));
+				text += &format!("  jump {}     {}\n", jump_bits, condition(jump.as_deref()));
+			},
+			Instruction::Label(_) => {},
+		}
+	}
+	text
+}
+
+/// Names the ALU control bits that are set, e.g. `zx nx f (x=0, x=!x, out=x+y)`.
+fn control(bits: &str) -> String {
+	let set: Vec<&(&str, &str)> = CONTROL_BITS.iter().zip(bits.chars()).filter(|(_, b)| *b == '1').map(|(c, _)| c).collect();
+	let f = match bits.chars().nth(4) {
+		Some('1') => "",
+		_ => ", out=x&y",
+	};
+	if set.is_empty() {
+		return "no control bits set (out=x&y)".to_string()
+	}
+	let names: Vec<&str> = set.iter().map(|c| c.0).collect();
+	let meanings: Vec<&str> = set.iter().map(|c| c.1).collect();
+	format!("{} ({}{})", names.join(" "), meanings.join(", "), f)
+}
+
+/// Describes the comp bits of an extended shift, e.g. `shifts D left (101 prefix)`.
+fn shift(bits: &str) -> String {
+	let operand = match bits.chars().nth(1) {
+		Some('1') => "D",
+		_ => "y",
+	};
+	let direction = match bits.chars().next() {
+		Some('1') => "left",
+		_ => "right, keeping the sign",
+	};
+	format!("shifts {} {} (101 prefix: extended instruction set)", operand, direction)
+}
+
+fn written(dest: Option<&str>) -> String {
+	match dest {
+		None => "stores nothing".to_string(),
+		Some(dest) => {
+			let registers: Vec<String> = dest.chars().map(|r| match r {
+				'M' => "M (RAM[A])".to_string(),
+				r => r.to_string(),
+			}).collect();
+			match dest.contains('A') && dest.contains('M') {
+				true => format!("stores out in {}, with M at the address A held before", registers.join(", ")),
+				false => format!("stores out in {}", registers.join(", ")),
+			}
+		},
+	}
+}
+
+fn condition(jump: Option<&str>) -> &'static str {
+	match jump {
+		None => "never jumps",
+		Some("JGT") => "jumps to ROM[A] if out > 0",
+		Some("JEQ") => "jumps to ROM[A] if out = 0",
+		Some("JGE") => "jumps to ROM[A] if out >= 0",
+		Some("JLT") => "jumps to ROM[A] if out < 0",
+		Some("JNE") => "jumps to ROM[A] if out != 0",
+		Some("JLE") => "jumps to ROM[A] if out <= 0",
+		Some(_) => "always jumps to ROM[A]",
+	}
+}
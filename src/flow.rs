@@ -0,0 +1,231 @@
+use std::collections::{HashMap, HashSet};
+
+use assembler::SymbolTable;
+use diagnostic::Diagnostic;
+use instruction::{Instruction, Statement};
+
+/// Where a C-instruction may transfer control to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+	/// The instruction doesn't jump.
+	None,
+	/// The jump goes to this ROM address.
+	Address(usize),
+	/// A holds a computed address, e.g. a return address read from RAM.
+	Unknown,
+}
+
+/// Control-flow analysis over a program's instructions.
+///
+/// Execution starts at ROM address 0. A jump's target is the value loaded
+/// into A by the `@xxx` right before it, resolved through the symbol table.
+/// When A was computed instead, the jump is assumed to reach any label whose
+/// address is used as data, such as a return address saved with `@RET`, `D=A`.
+pub struct Flow<'a> {
+	/// The instructions, in ROM order.
+	pub code: Vec<&'a Statement>,
+	/// The ROM address of every label.
+	pub labels: HashMap<String, usize>,
+	/// Whether each ROM address can be executed.
+	pub reachable: Vec<bool>,
+	label_statements: Vec<&'a Statement>,
+	label_addresses: HashSet<usize>,
+	address_taken: Vec<usize>,
+	symbol_table: SymbolTable,
+}
+
+impl<'a> Flow<'a> {
+	pub fn new(statements: &'a [Statement]) -> Flow<'a> {
+		let mut code = Vec::new();
+		let mut labels = HashMap::new();
+		let mut label_statements = Vec::new();
+		for statement in statements {
+			match statement.instruction {
+				Instruction::Label(ref label) => {
+					labels.insert(label.clone(), code.len());
+					label_statements.push(statement);
+				},
+				_ => code.push(statement),
+			}
+		}
+
+		let label_addresses = labels.values().cloned().collect();
+		let mut symbol_table = SymbolTable::new();
+		symbol_table.initialise();
+		for (label, address) in &labels {
+			symbol_table.add_entry(label.clone(), *address as u16);
+		}
+
+		let mut flow = Flow {
+			code,
+			labels,
+			label_addresses,
+			reachable: Vec::new(),
+			label_statements,
+			address_taken: Vec::new(),
+			symbol_table,
+		};
+
+		// labels used as data may be jumped to through a computed address
+		let mut address_taken = Vec::new();
+		for (address, statement) in flow.code.iter().enumerate() {
+			if let Instruction::A(ref symbol) = statement.instruction {
+				let jumps = flow.code.get(address + 1).is_some_and(|s| s.instruction.jump().is_some());
+				if let (false, Some(&target)) = (jumps, flow.labels.get(symbol)) {
+					address_taken.push(target);
+				}
+			}
+		}
+		address_taken.sort();
+		address_taken.dedup();
+		flow.address_taken = address_taken;
+
+		flow.reachable = flow.compute_reachable();
+		flow
+	}
+
+	/// Returns the target of the instruction at `address`.
+	pub fn target(&self, address: usize) -> Target {
+		let instruction = &self.code[address].instruction;
+		if instruction.jump().is_none() || never_jumps(instruction) {
+			return Target::None
+		}
+		if address == 0 {
+			return Target::Address(0) // A starts out as 0
+		}
+		match self.code[address - 1].instruction {
+			Instruction::A(ref symbol) if self.follows_directly(address) => {
+				match symbol.parse::<u16>() {
+					Ok(value) => Target::Address(value as usize),
+					Err(_) if self.symbol_table.contains(symbol) => {
+						Target::Address(self.symbol_table.get_address(symbol) as usize)
+					},
+					Err(_) => Target::Unknown, // a variable holding an address
+				}
+			},
+			_ => Target::Unknown,
+		}
+	}
+
+	/// Returns `true` if no label separates the instruction at `address` from the previous one.
+	fn follows_directly(&self, address: usize) -> bool {
+		!self.label_addresses.contains(&address)
+	}
+
+	/// Returns the ROM addresses that may execute right after the one at `address`.
+	pub fn successors(&self, address: usize) -> Vec<usize> {
+		let instruction = &self.code[address].instruction;
+		let mut successors = match self.target(address) {
+			Target::None => Vec::new(),
+			Target::Address(target) => vec![target],
+			Target::Unknown => self.address_taken.clone(),
+		};
+		if !always_jumps(instruction) {
+			successors.push(address + 1);
+		}
+		successors.retain(|&a| a < self.code.len());
+		successors.sort();
+		successors.dedup();
+		successors
+	}
+
+	fn compute_reachable(&self) -> Vec<bool> {
+		let mut reachable = vec![false; self.code.len()];
+		let mut pending = Vec::new();
+		if !self.code.is_empty() {
+			pending.push(0);
+		}
+		while let Some(address) = pending.pop() {
+			if reachable[address] {
+				continue
+			}
+			reachable[address] = true;
+			pending.extend(self.successors(address));
+		}
+		reachable
+	}
+
+	/// Returns the ranges of consecutive ROM addresses that can never execute.
+	pub fn unreachable_ranges(&self) -> Vec<(usize, usize)> {
+		let mut ranges: Vec<(usize, usize)> = Vec::new();
+		for (address, reachable) in self.reachable.iter().enumerate() {
+			if *reachable {
+				continue
+			}
+			match ranges.last_mut() {
+				Some(range) if range.1 + 1 == address => range.1 = address,
+				_ => ranges.push((address, address)),
+			}
+		}
+		ranges
+	}
+
+	/// Returns the label statements that no `@label` refers to.
+	pub fn unused_labels(&self) -> Vec<&'a Statement> {
+		let referenced: HashSet<&str> = self.code.iter().filter_map(|s| match s.instruction {
+			Instruction::A(ref symbol) => Some(symbol.as_str()),
+			_ => None,
+		}).collect();
+		self.label_statements.iter().filter(|s| match s.instruction {
+			Instruction::Label(ref label) => !referenced.contains(label.as_str()),
+			_ => false,
+		}).cloned().collect()
+	}
+
+	/// Warns about unreachable instructions and unused labels.
+	pub fn diagnostics(&self) -> Vec<Diagnostic> {
+		let mut diagnostics = Vec::new();
+		for (start, end) in self.unreachable_ranges() {
+			let (first, last) = (self.code[start].line.line, self.code[end].line.line);
+			let lines = match first == last {
+				true => format!("line {}", first),
+				false => format!("lines {}-{}", first, last),
+			};
+			let message = format!("unreachable code at ROM {}-{} ({})", start, end, lines);
+			diagnostics.push(Diagnostic::warning(first, &message));
+		}
+		for statement in self.unused_labels() {
+			let message = format!("label {} is never used", statement.instruction);
+			diagnostics.push(Diagnostic::warning(statement.line.line, &message));
+		}
+		diagnostics.sort_by_key(|d| d.line);
+		diagnostics
+	}
+}
+
+/// Returns `true` for jumps that are always taken, like `0;JMP` or `0;JEQ`.
+pub fn always_jumps(instruction: &Instruction) -> bool {
+	match *instruction {
+		Instruction::C { ref comp, jump: Some(ref jump), .. } => {
+			jump == "JMP" || (comp == "0" && (jump == "JEQ" || jump == "JGE" || jump == "JLE"))
+		},
+		_ => false,
+	}
+}
+
+/// Returns `true` for jumps that can never be taken, like `0;JGT`.
+pub fn never_jumps(instruction: &Instruction) -> bool {
+	match *instruction {
+		Instruction::C { ref comp, jump: Some(ref jump), .. } => {
+			comp == "0" && (jump == "JGT" || jump == "JLT" || jump == "JNE")
+		},
+		_ => true,
+	}
+}
+
+/// Removes the unreachable instructions from `statements`.
+pub fn strip_unreachable(statements: &[Statement]) -> Vec<Statement> {
+	let flow = Flow::new(statements);
+	let mut address = 0;
+	let mut stripped = Vec::new();
+	for statement in statements {
+		if statement.instruction.is_code() {
+			address += 1;
+			if !flow.reachable[address - 1] {
+				continue
+			}
+		}
+		stripped.push(statement.clone());
+	}
+	stripped
+}
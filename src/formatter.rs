@@ -0,0 +1,162 @@
+/// Indentation of instructions, under the flush-left labels.
+pub const INDENT: &str = "    ";
+
+/// Pseudo-instructions, whose operands are separated by spaces.
+const PSEUDO: [&str; 7] = ["LOAD", "GOTO", "IF", "PUSH", "POP", "INC", "DEC"];
+
+/// Rewrites Hack assembly in the canonical style.
+///
+/// Labels and directives are flush left and instructions indented. Operands
+/// are written the way the book's tables spell them (`D+M` rather than
+/// `M+D`, `AMD` rather than `DMA`). Trailing comments are aligned within
+/// each paragraph, comment lines take the indentation of the line after
+/// them (except for the header comments before any code), and runs of blank
+/// lines are collapsed into one.
+pub fn format(source: &str) -> String {
+	let lines: Vec<Line> = source.lines().map(Line::parse).collect();
+
+	let mut output: Vec<String> = Vec::new();
+	let mut paragraph: Vec<&Line> = Vec::new();
+	let mut header = true;
+	for (i, line) in lines.iter().enumerate() {
+		if line.code.is_empty() && line.comment.is_none() {
+			flush(&mut paragraph, &lines[i..], &mut header, &mut output);
+			if output.last().is_some_and(|l| !l.is_empty()) {
+				output.push(String::new());
+			}
+		} else {
+			paragraph.push(line);
+		}
+	}
+	flush(&mut paragraph, &[], &mut header, &mut output);
+	while output.last().is_some_and(|l| l.is_empty()) {
+		output.pop();
+	}
+
+	let mut formatted = output.join("\n");
+	if !formatted.is_empty() {
+		formatted.push('\n');
+	}
+	formatted
+}
+
+/// A source line split into its code and its comment.
+struct Line {
+	code: String,
+	comment: Option<String>,
+	indented: bool,
+}
+
+impl Line {
+	fn parse(text: &str) -> Line {
+		let (code, comment) = match text.find("//") {
+			Some(i) => (text[..i].trim(), Some(text[i..].trim_end().to_string())),
+			None => (text.trim(), None),
+		};
+		let indented = !(code.starts_with('(') || code.starts_with('.'));
+		Line { code: canonical(code), comment, indented }
+	}
+
+	fn indent(&self) -> &'static str {
+		match self.indented {
+			true => INDENT,
+			false => "",
+		}
+	}
+}
+
+/// Writes a paragraph of consecutive non-blank lines, aligning its trailing comments.
+///
+/// `rest` holds the lines after the paragraph, which trailing comment lines
+/// take their indentation from. `header` is set until the first code is written.
+fn flush(paragraph: &mut Vec<&Line>, rest: &[Line], header: &mut bool, output: &mut Vec<String>) {
+	let column = paragraph.iter()
+		.filter(|l| !l.code.is_empty() && l.comment.is_some())
+		.map(|l| l.indent().len() + l.code.len())
+		.max()
+		.unwrap_or(0) + 1;
+
+	for (i, line) in paragraph.iter().enumerate() {
+		let text = match (line.code.is_empty(), &line.comment) {
+			(true, Some(comment)) if *header => comment.clone(),
+			(true, Some(comment)) => {
+				// indent comment lines like the code they describe
+				let next = paragraph[i + 1..].iter().cloned().chain(rest.iter()).find(|l| !l.code.is_empty());
+				let indent = next.map(|l| l.indent()).unwrap_or("");
+				format!("{}{}", indent, comment)
+			},
+			(_, Some(comment)) => {
+				let code = format!("{}{}", line.indent(), line.code);
+				format!("{:width$}{}", code, comment, width = column)
+			},
+			(_, None) => format!("{}{}", line.indent(), line.code),
+		};
+		if !line.code.is_empty() {
+			*header = false;
+		}
+		output.push(text);
+	}
+	paragraph.clear();
+}
+
+/// Returns `code` with canonical spacing and operand spelling.
+fn canonical(code: &str) -> String {
+	if code.is_empty() || code.starts_with('.') {
+		return code.split_whitespace().collect::<Vec<_>>().join(" ")
+	}
+
+	let mnemonic = code.split_whitespace().next().unwrap_or("");
+	if PSEUDO.contains(&mnemonic) && code.len() > mnemonic.len() {
+		let operands: Vec<String> = code[mnemonic.len()..].split(',')
+			.map(|o| o.split_whitespace().collect::<Vec<_>>().join(" "))
+			.collect();
+		return format!("{} {}", mnemonic, operands.join(", "))
+	}
+
+	let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+	if code.starts_with('@') || code.starts_with('(') {
+		return code
+	}
+
+	let (dest, rest) = match code.find('=') {
+		Some(i) => (Some(&code[..i]), &code[i + 1..]),
+		None => (None, code.as_str()),
+	};
+	let (comp, jump) = match rest.find(';') {
+		Some(i) => (&rest[..i], Some(&rest[i + 1..])),
+		None => (rest, None),
+	};
+
+	let mut canonical = String::new();
+	if let Some(dest) = dest {
+		canonical += &canonical_dest(dest);
+		canonical.push('=');
+	}
+	canonical += &canonical_comp(comp);
+	if let Some(jump) = jump {
+		canonical.push(';');
+		canonical += jump;
+	}
+	canonical
+}
+
+/// Orders the registers of a dest as the book does: `AMD`, `AM`, `AD`, `MD`.
+fn canonical_dest(dest: &str) -> String {
+	match dest.chars().all(|c| "AMD".contains(c)) {
+		true => "AMD".chars().filter(|&c| dest.contains(c)).collect(),
+		false => dest.to_string(),
+	}
+}
+
+/// Swaps the operands of commutative comps into the book's order, e.g. `M+D` into `D+M`.
+fn canonical_comp(comp: &str) -> String {
+	for operator in ['+', '&', '|'] {
+		if let Some((left, right)) = comp.split_once(operator) {
+			let swap = matches!((left, right), ("A", "D") | ("M", "D") | ("1", "D") | ("1", "A") | ("1", "M"));
+			if swap {
+				return format!("{}{}{}", right, operator, left)
+			}
+		}
+	}
+	comp.to_string()
+}
@@ -0,0 +1,309 @@
+use assembler;
+use cpu::{self, Cpu};
+use instruction::COMPS;
+use profiler::End;
+
+/// How many cycles `hack hdl-check` runs before stopping a program that doesn't halt.
+pub const DEFAULT_CYCLES: u64 = 100_000;
+
+/// A 16-bit bus, least significant bit first, so `bus[0]` is `in[0]` in HDL.
+pub type Bus = [bool; 16];
+
+pub fn nand(a: bool, b: bool) -> bool {
+	!(a && b)
+}
+
+pub fn not(a: bool) -> bool {
+	nand(a, a)
+}
+
+pub fn and(a: bool, b: bool) -> bool {
+	not(nand(a, b))
+}
+
+pub fn or(a: bool, b: bool) -> bool {
+	nand(not(a), not(b))
+}
+
+pub fn xor(a: bool, b: bool) -> bool {
+	let n = nand(a, b);
+	nand(nand(a, n), nand(b, n))
+}
+
+/// Selects `a` when `sel` is 0, and `b` when it's 1.
+pub fn mux(a: bool, b: bool, sel: bool) -> bool {
+	or(and(a, not(sel)), and(b, sel))
+}
+
+pub fn not16(a: &Bus) -> Bus {
+	let mut out = [false; 16];
+	for i in 0..16 {
+		out[i] = not(a[i]);
+	}
+	out
+}
+
+pub fn and16(a: &Bus, b: &Bus) -> Bus {
+	let mut out = [false; 16];
+	for i in 0..16 {
+		out[i] = and(a[i], b[i]);
+	}
+	out
+}
+
+pub fn mux16(a: &Bus, b: &Bus, sel: bool) -> Bus {
+	let mut out = [false; 16];
+	for i in 0..16 {
+		out[i] = mux(a[i], b[i], sel);
+	}
+	out
+}
+
+pub fn or16way(a: &Bus) -> bool {
+	a.iter().fold(false, |out, &bit| or(out, bit))
+}
+
+/// Returns the sum and carry bits of `a + b`.
+pub fn half_adder(a: bool, b: bool) -> (bool, bool) {
+	(xor(a, b), and(a, b))
+}
+
+/// Returns the sum and carry bits of `a + b + c`.
+pub fn full_adder(a: bool, b: bool, c: bool) -> (bool, bool) {
+	let (sum, carry1) = half_adder(a, b);
+	let (sum, carry2) = half_adder(sum, c);
+	(sum, or(carry1, carry2))
+}
+
+/// Adds two buses with a ripple of full adders, dropping the last carry.
+pub fn add16(a: &Bus, b: &Bus) -> Bus {
+	let mut out = [false; 16];
+	let mut carry = false;
+	for i in 0..16 {
+		let (sum, next) = full_adder(a[i], b[i], carry);
+		out[i] = sum;
+		carry = next;
+	}
+	out
+}
+
+pub fn inc16(a: &Bus) -> Bus {
+	add16(a, &bus(1))
+}
+
+/// The book's ALU, from the gates above.
+///
+/// `control` holds `zx nx zy ny f no`, and the result is `out`, `zr` and `ng`.
+pub fn alu(x: &Bus, y: &Bus, control: [bool; 6]) -> (Bus, bool, bool) {
+	let [zx, nx, zy, ny, f, no] = control;
+	let zero = [false; 16];
+	let x = mux16(x, &zero, zx);
+	let x = mux16(&x, &not16(&x), nx);
+	let y = mux16(y, &zero, zy);
+	let y = mux16(&y, &not16(&y), ny);
+	let out = mux16(&and16(&x, &y), &add16(&x, &y), f);
+	let out = mux16(&out, &not16(&out), no);
+	(out, not(or16way(&out)), out[15])
+}
+
+pub fn bus(value: u16) -> Bus {
+	let mut bus = [false; 16];
+	for (i, bit) in bus.iter_mut().enumerate() {
+		*bit = value & (1 << i) != 0;
+	}
+	bus
+}
+
+pub fn value(bus: &Bus) -> u16 {
+	bus.iter().enumerate().fold(0, |value, (i, &bit)| value | ((bit as u16) << i))
+}
+
+/// What the CPU chip sends to the data memory in a clock cycle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outputs {
+	pub out_m: u16,
+	pub write_m: bool,
+	pub address_m: u16,
+}
+
+/// The book's CPU chip, wired from the gates above as in `CPU.hdl`, with the
+/// contents of its A, D and PC registers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GateCpu {
+	pub a: Bus,
+	pub d: Bus,
+	pub pc: Bus,
+}
+
+impl GateCpu {
+	pub fn new() -> GateCpu {
+		GateCpu {
+			a: [false; 16],
+			d: [false; 16],
+			pc: [false; 16],
+		}
+	}
+
+	/// Computes the chip's outputs for one clock cycle, then loads the
+	/// registers as the clock ticks.
+	pub fn cycle(&mut self, in_m: u16, instruction: u16, reset: bool) -> Outputs {
+		let i = bus(instruction);
+		let is_c = i[15];
+		let is_a = not(is_c);
+
+		let y = mux16(&self.a, &bus(in_m), and(is_c, i[12]));
+		let (out, zr, ng) = alu(&self.d, &y, control_bits(instruction));
+
+		let outputs = Outputs {
+			out_m: value(&out),
+			write_m: and(is_c, i[3]),
+			address_m: value(&self.a) & 0x7fff,
+		};
+
+		let positive = not(or(zr, ng));
+		let jump = and(is_c, or(or(and(i[2], ng), and(i[1], zr)), and(i[0], positive)));
+		let next = mux16(&inc16(&self.pc), &self.a, jump);
+		self.pc = mux16(&next, &[false; 16], reset);
+
+		if and(is_c, i[4]) {
+			self.d = out;
+		}
+		if or(is_a, and(is_c, i[5])) {
+			self.a = mux16(&i, &out, is_c);
+		}
+		outputs
+	}
+}
+
+impl Default for GateCpu {
+	fn default() -> GateCpu {
+		GateCpu::new()
+	}
+}
+
+/// Checks that every comp mnemonic, encoded by the assembler, makes the
+/// gate-level ALU compute what the mnemonic means, and that the emulator's
+/// ALU agrees with the gates for every setting of the control bits.
+pub fn check_encoding() -> Result<(), String> {
+	let samples: [(u16, u16, u16); 4] = [(0, 0, 0), (3, 7, 10), (0xfffe, 5, 0x8000), (0x7fff, 0x1234, 0xffff)];
+
+	for comp in COMPS.iter() {
+		let instruction = assembler::encode_c(None, comp, None);
+		let control = control_bits(instruction);
+		for &(d, a, m) in &samples {
+			let y = if instruction & 0x1000 != 0 { m } else { a };
+			let (out, _, _) = alu(&bus(d), &bus(y), control);
+			let expected = meaning(comp, d, a, m).unwrap_or_default();
+			if value(&out) != expected {
+				return Err(format!("comp {} is encoded as {:07b}, but the ALU then computes {} instead of {} for D={}, A={}, M={}",
+					comp, (instruction >> 6) & 0x7f, value(&out) as i16, expected as i16, d as i16, a as i16, m as i16))
+			}
+		}
+	}
+
+	for bits in 0..64u16 {
+		let control = control_bits(bits << 6);
+		for &(d, a, _) in &samples {
+			let (out, _, _) = alu(&bus(d), &bus(a), control);
+			let emulated = cpu::alu(d, a, bits as u8);
+			if value(&out) != emulated {
+				return Err(format!("for control bits {:06b}, x={} and y={}, the emulator's ALU computes {} but the gates compute {}",
+					bits, d as i16, a as i16, emulated as i16, value(&out) as i16))
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Runs a program on the emulator and on the gate-level CPU side by side,
+/// comparing A, D, PC and the memory write after every cycle.
+///
+/// Returns the cycles run and why the run stopped, or describes the first
+/// cycle where the two disagree. Programs using the shifts of the extended
+/// instruction set are rejected, since the book's CPU can't run them.
+pub fn cross_check(rom: &[u16], limit: u64) -> Result<(u64, End), String> {
+	let mut emulator = Cpu::new();
+	let mut gates = GateCpu::new();
+	let mut ram = vec![0u16; cpu::RAM_SIZE];
+
+	if let Some(address) = rom.iter().position(|&word| word & 0xe000 == 0xa000) {
+		return Err(format!("ROM {} holds a shift of the extended instruction set, which the book's CPU doesn't have", address))
+	}
+
+	let mut cycles = 0;
+	loop {
+		let pc = emulator.pc as usize;
+		if is_halt(rom, pc) {
+			return Ok((cycles, End::Halted))
+		}
+		let instruction = match rom.get(pc) {
+			Some(&instruction) => instruction,
+			None => return Ok((cycles, End::Ended)),
+		};
+		if cycles == limit {
+			return Ok((cycles, End::Limit))
+		}
+
+		let before = emulator.ram[(emulator.a & 0x7fff) as usize];
+		emulator.execute(instruction);
+		let outputs = gates.cycle(ram[value(&gates.a) as usize & 0x7fff], instruction, false);
+		if outputs.write_m {
+			ram[outputs.address_m as usize] = outputs.out_m;
+		}
+		cycles += 1;
+
+		let address = outputs.address_m as usize;
+		let registers = (emulator.a, emulator.d, emulator.pc) == (value(&gates.a), value(&gates.d), value(&gates.pc));
+		if !registers || emulator.ram[address] != ram[address] || (emulator.ram[address] != before && !outputs.write_m) {
+			return Err(format!("the emulator and the gate-level CPU disagree at cycle {}, PC={}, instruction {:016b}:\n\
+				\x20 emulator: A={} D={} PC={} RAM[{}]={}\n\
+				\x20 gates:    A={} D={} PC={} RAM[{}]={}",
+				cycles, pc, instruction,
+				emulator.a as i16, emulator.d as i16, emulator.pc, address, emulator.ram[address] as i16,
+				value(&gates.a) as i16, value(&gates.d) as i16, value(&gates.pc), address, ram[address] as i16))
+		}
+	}
+}
+
+/// Returns `true` when `address` starts an `@address`, `0;JMP` loop.
+fn is_halt(rom: &[u16], address: usize) -> bool {
+	match (rom.get(address), rom.get(address + 1)) {
+		(Some(&load), Some(&jump)) => load as usize == address && jump & 0xe007 == 0xe007,
+		_ => false,
+	}
+}
+
+/// Returns the `zx nx zy ny f no` bits of an instruction.
+fn control_bits(instruction: u16) -> [bool; 6] {
+	let i = bus(instruction);
+	[i[11], i[10], i[9], i[8], i[7], i[6]]
+}
+
+/// Evaluates a comp mnemonic directly from its text, such as `D-1` or `!M`.
+fn meaning(comp: &str, d: u16, a: u16, m: u16) -> Option<u16> {
+	let operand = |text: &str| match text {
+		"0" => Some(0),
+		"1" => Some(1),
+		"D" => Some(d),
+		"A" => Some(a),
+		"M" => Some(m),
+		_ => None,
+	};
+	if let Some(value) = operand(comp) {
+		return Some(value)
+	}
+	if let Some(text) = comp.strip_prefix('!') {
+		return operand(text).map(|v| !v)
+	}
+	if let Some(text) = comp.strip_prefix('-') {
+		return operand(text).map(|v| v.wrapping_neg())
+	}
+	let i = comp.find(['+', '-', '&', '|'])?;
+	let (x, y) = (operand(&comp[..i])?, operand(&comp[i + 1..])?);
+	Some(match &comp[i..i + 1] {
+		"+" => x.wrapping_add(y),
+		"-" => x.wrapping_sub(y),
+		"&" => x & y,
+		_ => x | y,
+	})
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+
+use preprocessor::SourceLine;
+
+/// The computations a C-instruction can perform.
+pub const COMPS: [&str; 28] = [
+	"0", "1", "-1", "D", "A", "!D", "!A", "-D", "-A", "D+1", "A+1", "D-1", "A-1", "D+A", "D-A", "A-D", "D&A", "D|A",
+	"M", "!M", "-M", "M+1", "M-1", "D+M", "D-M", "M-D", "D&M", "D|M",
+];
+
+/// The commutative comps spelt with their operands swapped, such as `M+D`,
+/// and the comps in `COMPS` they encode as.
+pub const COMP_ALIASES: [(&str, &str); 6] = [
+	("A+D", "D+A"), ("A&D", "D&A"), ("A|D", "D|A"), ("M+D", "D+M"), ("M&D", "D&M"), ("M|D", "D|M"),
+];
+
+/// The shifts of the extended instruction set of the nand2tetris CPU
+/// emulator, encoded with the `101` prefix instead of `111`.
+pub const SHIFTS: [&str; 6] = ["A<<", "D<<", "M<<", "A>>", "D>>", "M>>"];
+
+/// The registers a C-instruction can store its result in.
+pub const DESTS: [&str; 7] = ["M", "D", "MD", "A", "AM", "AD", "AMD"];
+
+/// The jump conditions of a C-instruction.
+pub const JUMPS: [&str; 7] = ["JGT", "JEQ", "JGE", "JLT", "JNE", "JLE", "JMP"];
+
+/// A parsed Hack instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instruction {
+	/// `@xxx` where xxx is either a symbol or a decimal number.
+	A(String),
+	/// `dest=comp;jump`. Either the dest or the jump field may be omitted.
+	C {
+		dest: Option<String>,
+		comp: String,
+		jump: Option<String>,
+	},
+	/// Pseudo-command `(xxx)` where xxx is a symbol.
+	Label(String),
+}
+
+impl Instruction {
+	/// Parses a line of assembly, ignoring comments and surrounding whitespace.
+	///
+	/// Returns `None` for empty lines and comment lines.
+	pub fn parse(text: &str) -> Option<Instruction> {
+		let content = match text.find("//") {
+			Some(i) => text[..i].trim(),
+			None => text.trim(),
+		};
+		if content.is_empty() {
+			return None
+		}
+
+		if let Some(symbol) = content.strip_prefix('@') {
+			return Some(Instruction::A(symbol.to_string()))
+		}
+		if content.starts_with('(') {
+			let pattern: &[_] = &['(', ')'];
+			return Some(Instruction::Label(content.trim_matches(pattern).to_string()))
+		}
+
+		let (dest, rest) = match content.find('=') {
+			Some(i) => (Some(content[..i].to_string()), &content[i + 1..]),
+			None => (None, content),
+		};
+		let (comp, jump) = match rest.find(';') {
+			Some(i) => (rest[..i].to_string(), Some(rest[i + 1..].to_string())),
+			None => (rest.to_string(), None),
+		};
+		Some(Instruction::C { dest, comp, jump })
+	}
+
+	/// Returns `true` for A- and C-instructions, which take up a word of ROM.
+	pub fn is_code(&self) -> bool {
+		!matches!(*self, Instruction::Label(_))
+	}
+
+	/// Returns `true` if the instruction writes `register` (`'A'`, `'D'` or `'M'`).
+	pub fn writes(&self, register: char) -> bool {
+		match *self {
+			Instruction::A(_) => register == 'A',
+			Instruction::C { dest: Some(ref dest), .. } => dest.contains(register),
+			_ => false,
+		}
+	}
+
+	/// Returns `true` if the instruction reads `register` (`'A'`, `'D'` or `'M'`).
+	///
+	/// Writing M and jumping both read A, since A holds the address.
+	pub fn reads(&self, register: char) -> bool {
+		match *self {
+			Instruction::C { ref comp, ref jump, .. } => {
+				comp.contains(register) || (register == 'A' && (self.writes('M') || comp.contains('M') || jump.is_some()))
+			},
+			_ => false,
+		}
+	}
+
+	/// Returns the jump mnemonic of a C-instruction, if it has one.
+	pub fn jump(&self) -> Option<&str> {
+		match *self {
+			Instruction::C { jump: Some(ref jump), .. } => Some(jump),
+			_ => None,
+		}
+	}
+
+	/// Checks that the instruction can be encoded: constants fit in 15 bits
+	/// and the mnemonics of C-instructions are known.
+	pub fn validate(&self) -> Result<(), String> {
+		self.validate_extended(false)
+	}
+
+	/// Checks that the instruction can be encoded, accepting the shifts of
+	/// the extended instruction set when `extended` is set.
+	pub fn validate_extended(&self, extended: bool) -> Result<(), String> {
+		match *self {
+			Instruction::A(ref value) => match value.parse::<u32>() {
+				Ok(n) if n > 32767 => Err(format!("constant {} doesn't fit in 15 bits", n)),
+				_ if value.is_empty() => Err("missing value after @".to_string()),
+				_ => Ok(()),
+			},
+			Instruction::Label(ref label) if label.is_empty() || label.parse::<u32>().is_ok() => {
+				Err(format!("invalid label name '{}'", label))
+			},
+			Instruction::Label(_) => Ok(()),
+			Instruction::C { ref dest, ref comp, ref jump } => {
+				if let Some(ref dest) = *dest {
+					if !DESTS.contains(&dest.as_str()) {
+						return Err(format!("unknown dest {}", dest))
+					}
+				}
+				if SHIFTS.contains(&comp.as_str()) && !extended {
+					return Err(format!("{} is an extended instruction; assemble with --extended", comp))
+				}
+				let alias = COMP_ALIASES.iter().any(|&(alias, _)| alias == comp);
+				if !COMPS.contains(&comp.as_str()) && !alias && !SHIFTS.contains(&comp.as_str()) {
+					return Err(format!("unknown comp {}", comp))
+				}
+				match *jump {
+					Some(ref jump) if !JUMPS.contains(&jump.as_str()) => Err(format!("unknown jump {}", jump)),
+					_ => Ok(()),
+				}
+			},
+		}
+	}
+
+	/// Returns `true` for jumps that are always taken, such as `0;JMP`.
+	pub fn is_unconditional_jump(&self) -> bool {
+		self.jump() == Some("JMP")
+	}
+}
+
+impl fmt::Display for Instruction {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Instruction::A(ref symbol) => write!(f, "@{}", symbol),
+			Instruction::Label(ref symbol) => write!(f, "({})", symbol),
+			Instruction::C { ref dest, ref comp, ref jump } => {
+				if let Some(ref dest) = *dest {
+					write!(f, "{}=", dest)?;
+				}
+				write!(f, "{}", comp)?;
+				if let Some(ref jump) = *jump {
+					write!(f, ";{}", jump)?;
+				}
+				Ok(())
+			},
+		}
+	}
+}
+
+/// An instruction along with the source line it was parsed from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statement {
+	pub line: SourceLine,
+	pub instruction: Instruction,
+}
+
+/// Parses source lines into statements, skipping empty lines and comments.
+pub fn parse(lines: &[SourceLine]) -> Vec<Statement> {
+	lines.iter().filter_map(|line| {
+		Instruction::parse(&line.text).map(|instruction| Statement {
+			line: line.clone(),
+			instruction,
+		})
+	}).collect()
+}
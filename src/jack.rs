@@ -0,0 +1,455 @@
+use std::fmt;
+
+/// Jack keywords, which can't be used as identifiers.
+const KEYWORDS: [&str; 21] = [
+	"class", "constructor", "function", "method", "field", "static", "var", "int", "char", "boolean",
+	"void", "true", "false", "null", "this", "let", "do", "if", "else", "while", "return",
+];
+
+const SYMBOLS: &str = "{}()[].,;+-*/&|<>=~";
+
+/// Binary operators, applied left to right without precedence.
+pub const OPERATORS: &str = "+-*/&|<>=";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenKind {
+	Keyword,
+	Symbol,
+	IntegerConstant,
+	StringConstant,
+	Identifier,
+}
+
+impl TokenKind {
+	/// Returns the XML element name of the kind, as in the nand2tetris compare files.
+	pub fn name(&self) -> &'static str {
+		match *self {
+			TokenKind::Keyword => "keyword",
+			TokenKind::Symbol => "symbol",
+			TokenKind::IntegerConstant => "integerConstant",
+			TokenKind::StringConstant => "stringConstant",
+			TokenKind::Identifier => "identifier",
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+	pub kind: TokenKind,
+	/// The token's text; string constants don't include the quotes.
+	pub text: String,
+	pub line: usize,
+}
+
+impl Token {
+	fn is(&self, text: &str) -> bool {
+		matches!(self.kind, TokenKind::Keyword | TokenKind::Symbol) && self.text == text
+	}
+}
+
+impl fmt::Display for Token {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.kind {
+			TokenKind::StringConstant => write!(f, "\"{}\"", self.text),
+			_ => write!(f, "{}", self.text),
+		}
+	}
+}
+
+/// Splits Jack source into tokens, skipping whitespace and comments.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+	let chars: Vec<char> = source.chars().collect();
+	let mut tokens = Vec::new();
+	let mut line = 1;
+	let mut i = 0;
+	while i < chars.len() {
+		let c = chars[i];
+		let next = chars.get(i + 1).cloned();
+		if c == '\n' {
+			line += 1;
+			i += 1;
+		} else if c.is_whitespace() {
+			i += 1;
+		} else if c == '/' && next == Some('/') {
+			while i < chars.len() && chars[i] != '\n' {
+				i += 1;
+			}
+		} else if c == '/' && next == Some('*') {
+			let start = line;
+			i += 2;
+			loop {
+				match (chars.get(i), chars.get(i + 1)) {
+					(Some('*'), Some('/')) => break,
+					(Some('\n'), _) => line += 1,
+					(None, _) => return Err(format!("line {}: comment is never closed", start)),
+					_ => {},
+				}
+				i += 1;
+			}
+			i += 2;
+		} else if SYMBOLS.contains(c) {
+			tokens.push(Token { kind: TokenKind::Symbol, text: c.to_string(), line });
+			i += 1;
+		} else if c == '"' {
+			let end = match chars[i + 1..].iter().position(|&c| c == '"' || c == '\n') {
+				Some(length) if chars[i + 1 + length] == '"' => i + 1 + length,
+				_ => return Err(format!("line {}: string constant is never closed", line)),
+			};
+			tokens.push(Token { kind: TokenKind::StringConstant, text: chars[i + 1..end].iter().collect(), line });
+			i = end + 1;
+		} else if c.is_ascii_digit() {
+			let start = i;
+			while i < chars.len() && chars[i].is_ascii_digit() {
+				i += 1;
+			}
+			let text: String = chars[start..i].iter().collect();
+			match text.parse::<u16>() {
+				Ok(value) if value <= 32767 => tokens.push(Token { kind: TokenKind::IntegerConstant, text, line }),
+				_ => return Err(format!("line {}: integer constant {} is greater than 32767", line, text)),
+			}
+		} else if c.is_ascii_alphabetic() || c == '_' {
+			let start = i;
+			while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+				i += 1;
+			}
+			let text: String = chars[start..i].iter().collect();
+			let kind = match KEYWORDS.contains(&text.as_str()) {
+				true => TokenKind::Keyword,
+				false => TokenKind::Identifier,
+			};
+			tokens.push(Token { kind, text, line });
+		} else {
+			return Err(format!("line {}: unexpected character '{}'", line, c))
+		}
+	}
+	Ok(tokens)
+}
+
+/// Writes tokens as the `<tokens>` XML of nand2tetris project 10.
+pub fn tokens_xml(tokens: &[Token]) -> String {
+	let mut xml = "<tokens>\n".to_string();
+	for token in tokens {
+		xml += &format!("<{0}> {1} </{0}>\n", token.kind.name(), escape(&token.text));
+	}
+	xml + "</tokens>\n"
+}
+
+/// A node of the parse tree: a token, or a grammar rule and what it matched.
+///
+/// The rules are the ones the nand2tetris compare files show, so rules such
+/// as `type`, `op` and `subroutineCall` appear as their tokens only.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+	Token(Token),
+	Tree(&'static str, Vec<Node>),
+}
+
+impl Node {
+	/// Returns the rule name, or the kind of a token.
+	pub fn kind(&self) -> &str {
+		match *self {
+			Node::Token(ref token) => token.kind.name(),
+			Node::Tree(kind, _) => kind,
+		}
+	}
+
+	/// Returns the nodes a rule matched; a token has none.
+	pub fn children(&self) -> &[Node] {
+		match *self {
+			Node::Token(_) => &[],
+			Node::Tree(_, ref children) => children,
+		}
+	}
+
+	/// Returns the text of a token; a rule has none.
+	pub fn text(&self) -> &str {
+		match *self {
+			Node::Token(ref token) => &token.text,
+			Node::Tree(..) => "",
+		}
+	}
+
+	/// Returns the line of a token, or of the first token a rule matched.
+	pub fn line(&self) -> usize {
+		match *self {
+			Node::Token(ref token) => token.line,
+			Node::Tree(_, ref children) => children.first().map_or(0, |c| c.line()),
+		}
+	}
+
+	/// Writes the tree as the XML of nand2tetris project 10.
+	pub fn to_xml(&self) -> String {
+		let mut xml = String::new();
+		self.write_xml(&mut xml, 0);
+		xml
+	}
+
+	fn write_xml(&self, xml: &mut String, depth: usize) {
+		let indent = "  ".repeat(depth);
+		match *self {
+			Node::Token(ref token) => *xml += &format!("{}<{1}> {2} </{1}>\n", indent, token.kind.name(), escape(&token.text)),
+			Node::Tree(kind, ref children) => {
+				*xml += &format!("{}<{}>\n", indent, kind);
+				for child in children {
+					child.write_xml(xml, depth + 1);
+				}
+				*xml += &format!("{}</{}>\n", indent, kind);
+			},
+		}
+	}
+}
+
+/// Parses a Jack class into its parse tree.
+pub fn parse(source: &str) -> Result<Node, String> {
+	let mut parser = Parser { tokens: tokenize(source)?, position: 0 };
+	let class = parser.class()?;
+	match parser.peek() {
+		Some(token) => Err(format!("line {}: expected the end of the file but found '{}'", token.line, token)),
+		None => Ok(class),
+	}
+}
+
+struct Parser {
+	tokens: Vec<Token>,
+	position: usize,
+}
+
+impl Parser {
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.position)
+	}
+
+	fn peek_is(&self, text: &str) -> bool {
+		self.peek().is_some_and(|t| t.is(text))
+	}
+
+	fn peek_is_any(&self, texts: &[&str]) -> bool {
+		texts.iter().any(|t| self.peek_is(t))
+	}
+
+	fn error(&self, expected: &str) -> String {
+		match self.peek() {
+			Some(token) => format!("line {}: expected {} but found '{}'", token.line, expected, token),
+			None => {
+				let line = self.tokens.last().map_or(1, |t| t.line);
+				format!("line {}: expected {} but reached the end of the file", line, expected)
+			},
+		}
+	}
+
+	fn take(&mut self) -> Node {
+		self.position += 1;
+		Node::Token(self.tokens[self.position - 1].clone())
+	}
+
+	fn expect(&mut self, text: &str) -> Result<Node, String> {
+		match self.peek_is(text) {
+			true => Ok(self.take()),
+			false => Err(self.error(&format!("'{}'", text))),
+		}
+	}
+
+	fn identifier(&mut self, what: &str) -> Result<Node, String> {
+		match self.peek().map(|t| t.kind) {
+			Some(TokenKind::Identifier) => Ok(self.take()),
+			_ => Err(self.error(what)),
+		}
+	}
+
+	/// `int`, `char`, `boolean` or a class name, or also `void` when `void` is true.
+	fn type_name(&mut self, void: bool) -> Result<Node, String> {
+		if self.peek_is_any(&["int", "char", "boolean"]) || (void && self.peek_is("void")) {
+			return Ok(self.take())
+		}
+		self.identifier("a type")
+	}
+
+	fn class(&mut self) -> Result<Node, String> {
+		let mut nodes = vec![self.expect("class")?, self.identifier("a class name")?, self.expect("{")?];
+		while self.peek_is_any(&["static", "field"]) {
+			let mut declaration = vec![self.take(), self.type_name(false)?];
+			self.names(&mut declaration)?;
+			nodes.push(Node::Tree("classVarDec", declaration));
+		}
+		while self.peek_is_any(&["constructor", "function", "method"]) {
+			nodes.push(self.subroutine()?);
+		}
+		nodes.push(self.expect("}")?);
+		Ok(Node::Tree("class", nodes))
+	}
+
+	/// `varName (',' varName)* ';'`
+	fn names(&mut self, nodes: &mut Vec<Node>) -> Result<(), String> {
+		nodes.push(self.identifier("a variable name")?);
+		while self.peek_is(",") {
+			nodes.push(self.take());
+			nodes.push(self.identifier("a variable name")?);
+		}
+		nodes.push(self.expect(";")?);
+		Ok(())
+	}
+
+	fn subroutine(&mut self) -> Result<Node, String> {
+		let mut nodes = vec![self.take(), self.type_name(true)?, self.identifier("a subroutine name")?, self.expect("(")?];
+		let mut parameters = Vec::new();
+		if !self.peek_is(")") {
+			parameters.push(self.type_name(false)?);
+			parameters.push(self.identifier("a parameter name")?);
+			while self.peek_is(",") {
+				parameters.push(self.take());
+				parameters.push(self.type_name(false)?);
+				parameters.push(self.identifier("a parameter name")?);
+			}
+		}
+		nodes.push(Node::Tree("parameterList", parameters));
+		nodes.push(self.expect(")")?);
+
+		let mut body = vec![self.expect("{")?];
+		while self.peek_is("var") {
+			let mut declaration = vec![self.take(), self.type_name(false)?];
+			self.names(&mut declaration)?;
+			body.push(Node::Tree("varDec", declaration));
+		}
+		body.push(self.statements()?);
+		body.push(self.expect("}")?);
+		nodes.push(Node::Tree("subroutineBody", body));
+		Ok(Node::Tree("subroutineDec", nodes))
+	}
+
+	fn statements(&mut self) -> Result<Node, String> {
+		let mut statements = Vec::new();
+		loop {
+			let statement = match self.peek() {
+				Some(token) if token.is("let") => self.let_statement()?,
+				Some(token) if token.is("if") => self.if_statement()?,
+				Some(token) if token.is("while") => {
+					let mut nodes = vec![self.take()];
+					self.condition_and_block(&mut nodes)?;
+					Node::Tree("whileStatement", nodes)
+				},
+				Some(token) if token.is("do") => {
+					let mut nodes = vec![self.take(), self.identifier("a subroutine name")?];
+					self.call(&mut nodes)?;
+					nodes.push(self.expect(";")?);
+					Node::Tree("doStatement", nodes)
+				},
+				Some(token) if token.is("return") => {
+					let mut nodes = vec![self.take()];
+					if !self.peek_is(";") {
+						nodes.push(self.expression()?);
+					}
+					nodes.push(self.expect(";")?);
+					Node::Tree("returnStatement", nodes)
+				},
+				_ => break,
+			};
+			statements.push(statement);
+		}
+		Ok(Node::Tree("statements", statements))
+	}
+
+	fn let_statement(&mut self) -> Result<Node, String> {
+		let mut nodes = vec![self.take(), self.identifier("a variable name")?];
+		if self.peek_is("[") {
+			nodes.push(self.take());
+			nodes.push(self.expression()?);
+			nodes.push(self.expect("]")?);
+		}
+		nodes.push(self.expect("=")?);
+		nodes.push(self.expression()?);
+		nodes.push(self.expect(";")?);
+		Ok(Node::Tree("letStatement", nodes))
+	}
+
+	fn if_statement(&mut self) -> Result<Node, String> {
+		let mut nodes = vec![self.take()];
+		self.condition_and_block(&mut nodes)?;
+		if self.peek_is("else") {
+			nodes.push(self.take());
+			self.block(&mut nodes)?;
+		}
+		Ok(Node::Tree("ifStatement", nodes))
+	}
+
+	/// `'(' expression ')' '{' statements '}'`
+	fn condition_and_block(&mut self, nodes: &mut Vec<Node>) -> Result<(), String> {
+		nodes.push(self.expect("(")?);
+		nodes.push(self.expression()?);
+		nodes.push(self.expect(")")?);
+		self.block(nodes)
+	}
+
+	fn block(&mut self, nodes: &mut Vec<Node>) -> Result<(), String> {
+		nodes.push(self.expect("{")?);
+		nodes.push(self.statements()?);
+		nodes.push(self.expect("}")?);
+		Ok(())
+	}
+
+	/// The rest of a subroutine call after its first name:
+	/// `('.' subroutineName)? '(' expressionList ')'`
+	fn call(&mut self, nodes: &mut Vec<Node>) -> Result<(), String> {
+		if self.peek_is(".") {
+			nodes.push(self.take());
+			nodes.push(self.identifier("a subroutine name")?);
+		}
+		nodes.push(self.expect("(")?);
+		let mut expressions = Vec::new();
+		if !self.peek_is(")") {
+			expressions.push(self.expression()?);
+			while self.peek_is(",") {
+				expressions.push(self.take());
+				expressions.push(self.expression()?);
+			}
+		}
+		nodes.push(Node::Tree("expressionList", expressions));
+		nodes.push(self.expect(")")?);
+		Ok(())
+	}
+
+	fn expression(&mut self) -> Result<Node, String> {
+		let mut nodes = vec![self.term()?];
+		while self.peek().is_some_and(|t| t.kind == TokenKind::Symbol && OPERATORS.contains(t.text.as_str())) {
+			nodes.push(self.take());
+			nodes.push(self.term()?);
+		}
+		Ok(Node::Tree("expression", nodes))
+	}
+
+	fn term(&mut self) -> Result<Node, String> {
+		let token = match self.peek() {
+			Some(token) => token.clone(),
+			None => return Err(self.error("an expression")),
+		};
+		let mut nodes = Vec::new();
+		match token.kind {
+			TokenKind::IntegerConstant | TokenKind::StringConstant => nodes.push(self.take()),
+			TokenKind::Keyword if ["true", "false", "null", "this"].contains(&token.text.as_str()) => nodes.push(self.take()),
+			TokenKind::Identifier => {
+				nodes.push(self.take());
+				if self.peek_is("[") {
+					nodes.push(self.take());
+					nodes.push(self.expression()?);
+					nodes.push(self.expect("]")?);
+				} else if self.peek_is_any(&["(", "."]) {
+					self.call(&mut nodes)?;
+				}
+			},
+			TokenKind::Symbol if token.text == "(" => {
+				nodes.push(self.take());
+				nodes.push(self.expression()?);
+				nodes.push(self.expect(")")?);
+			},
+			TokenKind::Symbol if token.text == "-" || token.text == "~" => {
+				nodes.push(self.take());
+				nodes.push(self.term()?);
+			},
+			_ => return Err(self.error("an expression")),
+		}
+		Ok(Node::Tree("term", nodes))
+	}
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,321 @@
+use std::fmt;
+
+/// A JSON value, as exchanged with editors and debuggers.
+///
+/// Objects keep their keys in insertion order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Json>),
+	Object(Vec<(String, Json)>),
+}
+
+impl Json {
+	/// Parses a JSON document.
+	pub fn parse(text: &str) -> Result<Json, String> {
+		let mut parser = JsonParser { chars: text.chars().collect(), position: 0 };
+		let value = parser.value()?;
+		parser.skip_whitespace();
+		match parser.peek() {
+			None => Ok(value),
+			Some(c) => Err(format!("unexpected '{}' after JSON value", c)),
+		}
+	}
+
+	/// Builds an object from key-value pairs.
+	pub fn object(members: Vec<(&str, Json)>) -> Json {
+		Json::Object(members.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+	}
+
+	pub fn string(text: &str) -> Json {
+		Json::String(text.to_string())
+	}
+
+	/// Returns the member `key` of an object, or `Null`.
+	pub fn get(&self, key: &str) -> &Json {
+		match *self {
+			Json::Object(ref members) => members.iter().find(|m| m.0 == key).map(|m| &m.1).unwrap_or(&Json::Null),
+			_ => &Json::Null,
+		}
+	}
+
+	/// Follows a path of object keys, e.g. `["params", "textDocument", "uri"]`.
+	pub fn path(&self, keys: &[&str]) -> &Json {
+		keys.iter().fold(self, |value, key| value.get(key))
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match *self {
+			Json::String(ref s) => Some(s),
+			_ => None,
+		}
+	}
+
+	pub fn as_i64(&self) -> Option<i64> {
+		match *self {
+			Json::Number(n) if n.fract() == 0.0 => Some(n as i64),
+			_ => None,
+		}
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match *self {
+			Json::Bool(b) => Some(b),
+			_ => None,
+		}
+	}
+
+	pub fn as_array(&self) -> &[Json] {
+		match *self {
+			Json::Array(ref items) => items,
+			_ => &[],
+		}
+	}
+
+	pub fn is_null(&self) -> bool {
+		*self == Json::Null
+	}
+}
+
+impl From<bool> for Json {
+	fn from(value: bool) -> Json {
+		Json::Bool(value)
+	}
+}
+
+impl From<i64> for Json {
+	fn from(value: i64) -> Json {
+		Json::Number(value as f64)
+	}
+}
+
+impl From<usize> for Json {
+	fn from(value: usize) -> Json {
+		Json::Number(value as f64)
+	}
+}
+
+impl From<u16> for Json {
+	fn from(value: u16) -> Json {
+		Json::Number(value as f64)
+	}
+}
+
+impl From<&str> for Json {
+	fn from(value: &str) -> Json {
+		Json::string(value)
+	}
+}
+
+impl From<String> for Json {
+	fn from(value: String) -> Json {
+		Json::String(value)
+	}
+}
+
+impl From<Vec<Json>> for Json {
+	fn from(value: Vec<Json>) -> Json {
+		Json::Array(value)
+	}
+}
+
+impl fmt::Display for Json {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Json::Null => write!(f, "null"),
+			Json::Bool(b) => write!(f, "{}", b),
+			Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", n as i64),
+			Json::Number(n) => write!(f, "{}", n),
+			Json::String(ref s) => write_string(f, s),
+			Json::Array(ref items) => {
+				write!(f, "[")?;
+				for (i, item) in items.iter().enumerate() {
+					if i > 0 {
+						write!(f, ",")?;
+					}
+					write!(f, "{}", item)?;
+				}
+				write!(f, "]")
+			},
+			Json::Object(ref members) => {
+				write!(f, "{{")?;
+				for (i, (key, value)) in members.iter().enumerate() {
+					if i > 0 {
+						write!(f, ",")?;
+					}
+					write_string(f, key)?;
+					write!(f, ":{}", value)?;
+				}
+				write!(f, "}}")
+			},
+		}
+	}
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+	write!(f, "\"")?;
+	for c in s.chars() {
+		match c {
+			'"' => write!(f, "\\\"")?,
+			'\\' => write!(f, "\\\\")?,
+			'\n' => write!(f, "\\n")?,
+			'\r' => write!(f, "\\r")?,
+			'\t' => write!(f, "\\t")?,
+			c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+			c => write!(f, "{}", c)?,
+		}
+	}
+	write!(f, "\"")
+}
+
+struct JsonParser {
+	chars: Vec<char>,
+	position: usize,
+}
+
+impl JsonParser {
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.position).cloned()
+	}
+
+	fn next(&mut self) -> Option<char> {
+		let c = self.peek();
+		self.position += 1;
+		c
+	}
+
+	fn skip_whitespace(&mut self) {
+		while self.peek().is_some_and(|c| c.is_whitespace()) {
+			self.position += 1;
+		}
+	}
+
+	fn expect(&mut self, expected: char) -> Result<(), String> {
+		match self.next() {
+			Some(c) if c == expected => Ok(()),
+			Some(c) => Err(format!("expected '{}' but found '{}'", expected, c)),
+			None => Err(format!("expected '{}' at end of JSON", expected)),
+		}
+	}
+
+	fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+		for expected in keyword.chars() {
+			self.expect(expected)?;
+		}
+		Ok(value)
+	}
+
+	fn value(&mut self) -> Result<Json, String> {
+		self.skip_whitespace();
+		match self.peek() {
+			Some('n') => self.keyword("null", Json::Null),
+			Some('t') => self.keyword("true", Json::Bool(true)),
+			Some('f') => self.keyword("false", Json::Bool(false)),
+			Some('"') => self.string().map(Json::String),
+			Some('[') => self.array(),
+			Some('{') => self.object(),
+			Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+			Some(c) => Err(format!("unexpected '{}' in JSON", c)),
+			None => Err("unexpected end of JSON".to_string()),
+		}
+	}
+
+	fn number(&mut self) -> Result<Json, String> {
+		let start = self.position;
+		while self.peek().is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(c)) {
+			self.position += 1;
+		}
+		let text: String = self.chars[start..self.position].iter().collect();
+		text.parse::<f64>().map(Json::Number).map_err(|_| format!("invalid number {}", text))
+	}
+
+	fn string(&mut self) -> Result<String, String> {
+		self.expect('"')?;
+		let mut s = String::new();
+		loop {
+			match self.next() {
+				Some('"') => return Ok(s),
+				Some('\\') => match self.next() {
+					Some('n') => s.push('\n'),
+					Some('r') => s.push('\r'),
+					Some('t') => s.push('\t'),
+					Some('b') => s.push('\u{8}'),
+					Some('f') => s.push('\u{c}'),
+					Some('u') => {
+						let code = self.hex4()?;
+						// a surrogate pair encodes characters outside the basic plane
+						if (0xd800..0xdc00).contains(&code) && self.peek() == Some('\\') {
+							self.position += 1;
+							self.expect('u')?;
+							let low = self.hex4()?;
+							let c = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+							s.push(char::from_u32(c).unwrap_or('\u{fffd}'));
+						} else {
+							s.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+						}
+					},
+					Some(c) => s.push(c),
+					None => return Err("unterminated string in JSON".to_string()),
+				},
+				Some(c) => s.push(c),
+				None => return Err("unterminated string in JSON".to_string()),
+			}
+		}
+	}
+
+	fn hex4(&mut self) -> Result<u32, String> {
+		let mut code = 0;
+		for _ in 0..4 {
+			match self.next().and_then(|c| c.to_digit(16)) {
+				Some(digit) => code = code * 16 + digit,
+				None => return Err("invalid \\u escape in JSON".to_string()),
+			}
+		}
+		Ok(code)
+	}
+
+	fn array(&mut self) -> Result<Json, String> {
+		self.expect('[')?;
+		let mut items = Vec::new();
+		self.skip_whitespace();
+		if self.peek() == Some(']') {
+			self.position += 1;
+			return Ok(Json::Array(items))
+		}
+		loop {
+			items.push(self.value()?);
+			self.skip_whitespace();
+			match self.next() {
+				Some(',') => continue,
+				Some(']') => return Ok(Json::Array(items)),
+				_ => return Err("expected ',' or ']' in JSON array".to_string()),
+			}
+		}
+	}
+
+	fn object(&mut self) -> Result<Json, String> {
+		self.expect('{')?;
+		let mut members = Vec::new();
+		self.skip_whitespace();
+		if self.peek() == Some('}') {
+			self.position += 1;
+			return Ok(Json::Object(members))
+		}
+		loop {
+			self.skip_whitespace();
+			let key = self.string()?;
+			self.skip_whitespace();
+			self.expect(':')?;
+			members.push((key, self.value()?));
+			self.skip_whitespace();
+			match self.next() {
+				Some(',') => continue,
+				Some('}') => return Ok(Json::Object(members)),
+				_ => return Err("expected ',' or '}' in JSON object".to_string()),
+			}
+		}
+	}
+}
@@ -1 +1,32 @@
-pub mod assembler;
\ No newline at end of file
+pub mod archive;
+pub mod assembler;
+pub mod build;
+pub mod cfg;
+pub mod compiler;
+pub mod cpu;
+pub mod dap;
+pub mod debugger;
+pub mod diagnostic;
+pub mod disassembler;
+pub mod explain;
+pub mod flow;
+pub mod formatter;
+pub mod gates;
+pub mod instruction;
+pub mod jack;
+pub mod json;
+pub mod linker;
+pub mod lint;
+pub mod lsp;
+pub mod map;
+pub mod object;
+pub mod optimizer;
+pub mod preprocessor;
+pub mod profiler;
+pub mod program;
+pub mod pseudo;
+pub mod repl;
+pub mod sourcemap;
+pub mod trace;
+pub mod vm;
+pub mod watch;
\ No newline at end of file
@@ -0,0 +1,174 @@
+use std::collections::{HashMap, HashSet};
+
+use archive::Archive;
+use map::{MemoryMap, VARIABLES_START};
+use object::{ObjectModule, Relocation};
+
+/// Combines separately assembled modules into a single program.
+///
+/// Modules are laid out in ROM in the order they were added. Exported labels
+/// are rebased onto their module's start address, and variables are allocated
+/// across all modules starting at RAM address 16, in order of first use.
+/// Only `.import`ed symbols bind to exported labels; a variable named like an
+/// exported label is an error.
+///
+/// Archive members are only linked when they export a symbol that the
+/// program imports but doesn't define. They are laid out after the modules
+/// added directly.
+pub struct Linker {
+	modules: Vec<(String, ObjectModule)>,
+	archives: Vec<(String, Archive)>,
+}
+
+impl Linker {
+	pub fn new() -> Linker {
+		Linker {
+			modules: Vec::new(),
+			archives: Vec::new(),
+		}
+	}
+
+	/// Adds a module. `name` is used in error messages, usually the file name.
+	pub fn add_module(&mut self, name: &str, module: ObjectModule) {
+		self.modules.push((name.to_string(), module));
+	}
+
+	/// Adds a library archive to search for undefined symbols.
+	pub fn add_archive(&mut self, name: &str, archive: Archive) {
+		self.archives.push((name.to_string(), archive));
+	}
+
+	/// Returns the modules to link: the ones added directly, followed by the
+	/// archive members needed to define their undefined symbols.
+	pub fn selected_modules(&self) -> Vec<(String, &ObjectModule)> {
+		let mut selected: Vec<(String, &ObjectModule)> = self.modules.iter().map(|(name, module)| (name.clone(), module)).collect();
+		let mut included: HashSet<(usize, usize)> = HashSet::new();
+
+		// pulling in a member may introduce new undefined symbols, so repeat until nothing changes
+		loop {
+			let mut defined: HashSet<&str> = HashSet::new();
+			for (_, module) in &selected {
+				for (symbol, _) in &module.exports {
+					defined.insert(symbol);
+				}
+			}
+
+			let mut wanted: Option<(usize, usize)> = None;
+			'search: for (_, module) in &selected {
+				for symbol in &module.imports {
+					if defined.contains(symbol.as_str()) {
+						continue
+					}
+					for (a, (_, archive)) in self.archives.iter().enumerate() {
+						if let Some(m) = archive.find(symbol) {
+							if !included.contains(&(a, m)) {
+								wanted = Some((a, m));
+								break 'search;
+							}
+						}
+					}
+				}
+			}
+
+			match wanted {
+				Some((a, m)) => {
+					included.insert((a, m));
+					let (ref archive_name, ref archive) = self.archives[a];
+					let (ref member_name, ref module) = archive.members[m];
+					selected.push((format!("{}({})", archive_name, member_name), module));
+				},
+				None => return selected,
+			}
+		}
+	}
+
+	/// Links the modules into the final program.
+	///
+	/// Returns every unresolved and duplicate symbol when linking fails.
+	pub fn link(&self) -> Result<Vec<u16>, Vec<String>> {
+		self.link_with_map().map(|(program, _)| program)
+	}
+
+	/// Links the modules and describes the resulting memory layout.
+	///
+	/// The map has a region per module and per exported label. Linking also
+	/// fails when the program doesn't fit in ROM.
+	pub fn link_with_map(&self) -> Result<(Vec<u16>, MemoryMap), Vec<String>> {
+		let modules = self.selected_modules();
+		let mut errors = Vec::new();
+		let mut map = MemoryMap::new();
+
+		// lay out ROM and collect the exported labels
+		let mut bases = Vec::new();
+		let mut exports: HashMap<&str, (u16, &str)> = HashMap::new();
+		let mut rom_address: usize = 0;
+		for (name, module) in &modules {
+			bases.push(rom_address as u16);
+			let mut labels = Vec::new();
+			for (symbol, address) in &module.exports {
+				let absolute = (rom_address + *address as usize) as u16;
+				labels.push((symbol.clone(), absolute));
+				match exports.get(symbol.as_str()) {
+					Some(&(_, other)) => errors.push(format!("duplicate symbol {} exported by {} and {}", symbol, other, name)),
+					None => { exports.insert(symbol, (absolute, name)); },
+				}
+			}
+			map.add_regions(Some(name), &labels, rom_address as u16, rom_address + module.code.len());
+			rom_address += module.code.len();
+		}
+
+		map.rom_size = rom_address;
+		if let Err(why) = map.check_capacity() {
+			errors.push(why);
+		}
+
+		for (name, module) in &modules {
+			for symbol in &module.imports {
+				if !exports.contains_key(symbol.as_str()) {
+					errors.push(format!("unresolved symbol {} imported by {}", symbol, name));
+				}
+			}
+			// only imports bind to exported labels, so a variable of the same name is a forgotten .import
+			let mut shadowing: Vec<&str> = module.code.iter().filter_map(|word| match word.relocation {
+				Relocation::Variable(ref symbol) if exports.contains_key(symbol.as_str()) => Some(symbol.as_str()),
+				_ => None,
+			}).collect();
+			shadowing.sort();
+			shadowing.dedup();
+			for symbol in shadowing {
+				errors.push(format!("{} is a variable in {} but a label exported by {}; import it with .import {}", symbol, name, exports[symbol].1, symbol));
+			}
+		}
+
+		if !errors.is_empty() {
+			return Err(errors);
+		}
+
+		// relocate
+		let mut variables: HashMap<&str, u16> = HashMap::new();
+		let mut user_defined_var_addr: u16 = VARIABLES_START;
+		let mut program = Vec::with_capacity(rom_address);
+		for (i, (_, module)) in modules.iter().enumerate() {
+			for word in &module.code {
+				let code = match word.relocation {
+					Relocation::Absolute => word.code,
+					Relocation::Label => bases[i].wrapping_add(word.code),
+					Relocation::Import(ref symbol) => exports[symbol.as_str()].0,
+					Relocation::Variable(ref symbol) => *variables.entry(symbol).or_insert_with(|| {
+						map.variables.push((symbol.clone(), user_defined_var_addr));
+						user_defined_var_addr += 1;
+						user_defined_var_addr - 1
+					}),
+				};
+				program.push(code);
+			}
+		}
+		Ok((program, map))
+	}
+}
+
+impl Default for Linker {
+	fn default() -> Linker {
+		Linker::new()
+	}
+}
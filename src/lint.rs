@@ -0,0 +1,256 @@
+use std::collections::{HashMap, HashSet};
+
+use assembler::SymbolTable;
+use diagnostic::Diagnostic;
+use instruction::{self, Instruction, Statement};
+use preprocessor::SourceLine;
+
+/// The lint rules, by name.
+///
+/// * `clobbered-jump-target`: A is loaded with a label, then overwritten before the jump
+/// * `am-read-write`: `AM=M...` reads and writes M at the old A, not the address it loads,
+///   except for `@ptr`, `AM=M±1`, which moves a pointer such as SP and follows it
+/// * `jump-to-variable`: a jump through `@var`, which goes to the variable's RAM address
+/// * `case-mismatch`: a variable that differs only in case from a label or predefined symbol
+/// * `possible-typo`: a variable used only once whose name is close to another symbol
+pub const RULES: [&str; 5] = ["clobbered-jump-target", "am-read-write", "jump-to-variable", "case-mismatch", "possible-typo"];
+
+/// The rules the assembler checks on every program, since auto-allocated
+/// variables hide misspelt labels.
+pub const VARIABLE_RULES: [&str; 2] = ["jump-to-variable", "possible-typo"];
+
+const ALLOW: &str = "hack-lint: allow(";
+
+/// Checks a program for suspicious code patterns.
+///
+/// A rule is suppressed for one line with a `// hack-lint: allow(rule, ...)`
+/// comment, either at the end of the line or on its own on the line before.
+/// Symbols imported from other modules count as labels.
+/// Unknown rule names in the allow comments are reported too.
+pub fn lint(lines: &[SourceLine], imports: &[String]) -> Vec<Diagnostic> {
+	let (_, mut diagnostics) = allowances(lines);
+	diagnostics.extend(check(lines, imports, &RULES));
+	diagnostics.sort_by_key(|d| d.line);
+	diagnostics
+}
+
+/// Checks a program with the given rules only, leaving the allow comments
+/// for `lint` to check.
+pub fn check(lines: &[SourceLine], imports: &[String], rules: &[&str]) -> Vec<Diagnostic> {
+	let (allowed, _) = allowances(lines);
+	let mut diagnostics = Vec::new();
+	let statements = instruction::parse(lines);
+
+	let mut labels: HashSet<&str> = statements.iter().filter_map(|s| match s.instruction {
+		Instruction::Label(ref label) => Some(label.as_str()),
+		_ => None,
+	}).collect();
+	labels.extend(imports.iter().map(|i| i.as_str()));
+
+	let mut warnings = Vec::new();
+	clobbered_jump_targets(&statements, &labels, &mut warnings);
+	am_read_writes(&statements, &mut warnings);
+	jumps_to_variables(&statements, &labels, &mut warnings);
+	case_mismatches(&statements, &labels, &mut warnings);
+	possible_typos(&statements, &labels, &mut warnings);
+
+	for (line, rule, message) in warnings {
+		if rules.contains(&rule) && !allowed.get(&line).is_some_and(|rules| rules.contains(rule)) {
+			diagnostics.push(Diagnostic::warning(line, &format!("{} [{}]", message, rule)));
+		}
+	}
+	diagnostics.sort_by_key(|d| d.line);
+	diagnostics
+}
+
+/// Collects the rules allowed on each line, and warns about unknown rule names.
+fn allowances(lines: &[SourceLine]) -> (HashMap<usize, HashSet<String>>, Vec<Diagnostic>) {
+	let mut allowed: HashMap<usize, HashSet<String>> = HashMap::new();
+	let mut diagnostics = Vec::new();
+	let mut pending: Vec<String> = Vec::new();
+	for line in lines {
+		// pseudo-instructions keep their comments in the original line
+		let text = line.origin.as_ref().unwrap_or(&line.text);
+		let (code, comment) = match text.find("//") {
+			Some(i) => (text[..i].trim(), text[i + 2..].trim()),
+			None => (text.trim(), ""),
+		};
+
+		let mut rules = Vec::new();
+		if let Some(list) = comment.strip_prefix(ALLOW) {
+			match list.find(')') {
+				Some(end) => rules = list[..end].split(',').map(|r| r.trim().to_string()).collect(),
+				None => diagnostics.push(Diagnostic::warning(line.line, "expected ')' after hack-lint: allow(")),
+			}
+		}
+		for rule in &rules {
+			if !RULES.contains(&rule.as_str()) {
+				diagnostics.push(Diagnostic::warning(line.line, &format!("unknown lint rule {}", rule)));
+			}
+		}
+
+		pending.extend(rules);
+		if !code.is_empty() {
+			allowed.entry(line.line).or_default().extend(pending.drain(..));
+		}
+	}
+	(allowed, diagnostics)
+}
+
+/// Returns the index of the next instruction after statement `i`, unless a label comes first.
+fn next_in_block(statements: &[Statement], i: usize) -> Option<usize> {
+	match statements.get(i + 1) {
+		Some(s) if s.instruction.is_code() => Some(i + 1),
+		_ => None,
+	}
+}
+
+fn clobbered_jump_targets(statements: &[Statement], labels: &HashSet<&str>, warnings: &mut Vec<(usize, &str, String)>) {
+	for i in 0..statements.len() {
+		let label = match statements[i].instruction {
+			Instruction::A(ref symbol) if labels.contains(symbol.as_str()) => symbol,
+			_ => continue,
+		};
+		let mut j = i;
+		while let Some(next) = next_in_block(statements, j) {
+			let instruction = &statements[next].instruction;
+			if instruction.jump().is_some() {
+				break
+			}
+			if instruction.writes('A') {
+				let jumps = next_in_block(statements, next).is_some_and(|k| statements[k].instruction.jump().is_some());
+				if jumps {
+					let message = format!("{} overwrites A loaded with label {} before the jump", instruction, label);
+					warnings.push((statements[next].line.line, "clobbered-jump-target", message));
+				}
+				break
+			}
+			j = next;
+		}
+	}
+}
+
+fn am_read_writes(statements: &[Statement], warnings: &mut Vec<(usize, &str, String)>) {
+	for (i, statement) in statements.iter().enumerate() {
+		let instruction = &statement.instruction;
+		if !(instruction.writes('A') && instruction.writes('M') && instruction.reads('M')) {
+			continue
+		}
+		// pushing and popping, as PUSH and POP expand to
+		let bump = matches!(*instruction, Instruction::C { dest: Some(ref dest), ref comp, jump: None } if dest == "AM" && (comp == "M+1" || comp == "M-1"));
+		let pointer = i > 0 && matches!(statements[i - 1].instruction, Instruction::A(_));
+		if !(bump && pointer) && statement.line.origin.is_none() {
+			let message = format!("{} reads and writes M at the address A held before this instruction", instruction);
+			warnings.push((statement.line.line, "am-read-write", message));
+		}
+	}
+}
+
+fn jumps_to_variables(statements: &[Statement], labels: &HashSet<&str>, warnings: &mut Vec<(usize, &str, String)>) {
+	for i in 0..statements.len() {
+		let symbol = match statements[i].instruction {
+			Instruction::A(ref symbol) if symbol.parse::<u16>().is_err() && !labels.contains(symbol.as_str()) => symbol,
+			_ => continue,
+		};
+		if let Some(next) = next_in_block(statements, i) {
+			if statements[next].instruction.jump().is_some() {
+				let message = format!("jump to variable {}, which is a RAM address; use A=M to jump to the address it holds", symbol);
+				warnings.push((statements[i].line.line, "jump-to-variable", message));
+			}
+		}
+	}
+}
+
+fn case_mismatches(statements: &[Statement], labels: &HashSet<&str>, warnings: &mut Vec<(usize, &str, String)>) {
+	let mut symbol_table = SymbolTable::new();
+	symbol_table.initialise();
+	let by_case: HashMap<String, &str> = labels.iter().map(|label| (label.to_lowercase(), *label)).collect();
+
+	let mut reported = HashSet::new();
+	for statement in statements {
+		let symbol = match statement.instruction {
+			Instruction::A(ref symbol) => symbol,
+			_ => continue,
+		};
+		if symbol.parse::<u16>().is_ok() || labels.contains(symbol.as_str()) || symbol_table.contains(symbol) {
+			continue
+		}
+		let similar = match by_case.get(&symbol.to_lowercase()) {
+			Some(label) => format!("label {}", label),
+			None if symbol_table.contains(&symbol.to_uppercase()) => format!("predefined symbol {}", symbol.to_uppercase()),
+			None => continue,
+		};
+		if reported.insert(symbol.clone()) {
+			let message = format!("variable {} differs only in case from {}", symbol, similar);
+			warnings.push((statement.line.line, "case-mismatch", message));
+		}
+	}
+}
+
+fn possible_typos(statements: &[Statement], labels: &HashSet<&str>, warnings: &mut Vec<(usize, &str, String)>) {
+	let mut symbol_table = SymbolTable::new();
+	symbol_table.initialise();
+
+	// how often each variable is used, and where it is first used
+	let mut variables: Vec<(&str, usize, usize)> = Vec::new();
+	for statement in statements {
+		if let Instruction::A(ref symbol) = statement.instruction {
+			if symbol.parse::<u16>().is_ok() || labels.contains(symbol.as_str()) || symbol_table.contains(symbol) {
+				continue
+			}
+			match variables.iter_mut().find(|v| v.0 == symbol) {
+				Some(variable) => variable.1 += 1,
+				None => variables.push((symbol, 1, statement.line.line)),
+			}
+		}
+	}
+
+	let mut sorted_labels: Vec<&str> = labels.iter().cloned().collect();
+	sorted_labels.sort();
+	for &(variable, uses, line) in &variables {
+		if uses > 1 {
+			continue
+		}
+		let labels = sorted_labels.iter().map(|label| ("label", *label));
+		let others = variables.iter().filter(|v| v.0 != variable).map(|v| ("variable", v.0));
+		let closest = labels.chain(others)
+			.filter(|(_, name)| !name.eq_ignore_ascii_case(variable)) // left to case-mismatch
+			.map(|(kind, name)| (edit_distance(variable, name), kind, name))
+			.filter(|&(distance, _, name)| distance <= max_typo_distance(name))
+			.min_by_key(|&(distance, _, _)| distance);
+		if let Some((_, kind, name)) = closest {
+			let message = format!("variable {} is used only once; did you mean {} {}?", variable, kind, name);
+			warnings.push((line, "possible-typo", message));
+		}
+	}
+}
+
+/// Returns how many edits a misspelling of `name` may be away from it.
+fn max_typo_distance(name: &str) -> usize {
+	match name.chars().count() {
+		0..=2 => 0,
+		3..=6 => 1,
+		_ => 2,
+	}
+}
+
+/// Returns the number of insertions, deletions, substitutions and swaps of
+/// adjacent characters that turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+	let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+	let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+	for (i, row) in d.iter_mut().enumerate() {
+		row[0] = i;
+	}
+	d[0] = (0..=b.len()).collect();
+	for i in 1..=a.len() {
+		for j in 1..=b.len() {
+			let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+			d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+			if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+				d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+			}
+		}
+	}
+	d[a.len()][b.len()]
+}
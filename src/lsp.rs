@@ -0,0 +1,492 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use assembler::{self, SymbolTable};
+use diagnostic::{Diagnostic, Severity};
+use flow::Flow;
+use instruction::{self, COMPS, DESTS, JUMPS};
+use json::Json;
+use lint;
+use preprocessor::Preprocessor;
+use program::Program;
+use pseudo;
+
+/// Pseudo-instructions offered by completion.
+const PSEUDO: [&str; 7] = ["LOAD", "GOTO", "IF", "PUSH", "POP", "INC", "DEC"];
+
+// JSON-RPC error codes
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Language server for Hack assembly, speaking the Language Server Protocol over stdio.
+///
+/// Documents are kept in memory as the editor sends them, and every request
+/// reparses the document, which is fast enough for programs that fit in ROM.
+pub struct Server {
+	documents: HashMap<String, String>,
+	shutdown: bool,
+}
+
+impl Server {
+	pub fn new() -> Server {
+		Server {
+			documents: HashMap::new(),
+			shutdown: false,
+		}
+	}
+
+	/// Serves requests from `input` until the editor sends `exit` or closes the stream.
+	pub fn run<R: BufRead, W: Write>(&mut self, input: &mut R, output: &mut W) -> Result<(), String> {
+		while let Some(message) = read_message(input)? {
+			if message.get("method").as_str() == Some("exit") {
+				break
+			}
+			for reply in self.handle(&message) {
+				write_message(output, &reply)?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Handles a request or notification, returning the response and any notifications to send.
+	pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+		let method = message.get("method").as_str().unwrap_or("");
+		let params = message.get("params");
+		let id = message.get("id");
+
+		let result = match method {
+			"initialize" => Ok(capabilities()),
+			"initialized" => return Vec::new(),
+			"shutdown" => {
+				self.shutdown = true;
+				Ok(Json::Null)
+			},
+			"textDocument/didOpen" => {
+				let uri = params.path(&["textDocument", "uri"]).as_str().unwrap_or("").to_string();
+				let text = params.path(&["textDocument", "text"]).as_str().unwrap_or("").to_string();
+				self.documents.insert(uri.clone(), text);
+				return vec![self.publish_diagnostics(&uri)]
+			},
+			"textDocument/didChange" => {
+				let uri = params.path(&["textDocument", "uri"]).as_str().unwrap_or("").to_string();
+				// full document sync, so the last change holds the whole text
+				if let Some(text) = params.get("contentChanges").as_array().last().and_then(|c| c.get("text").as_str()) {
+					self.documents.insert(uri.clone(), text.to_string());
+				}
+				return vec![self.publish_diagnostics(&uri)]
+			},
+			"textDocument/didClose" => {
+				let uri = params.path(&["textDocument", "uri"]).as_str().unwrap_or("").to_string();
+				self.documents.remove(&uri);
+				return vec![notification("textDocument/publishDiagnostics", Json::object(vec![
+					("uri", Json::from(uri)),
+					("diagnostics", Json::Array(Vec::new())),
+				]))]
+			},
+			"textDocument/definition" => self.with_document(params, definition),
+			"textDocument/references" => self.with_document(params, references),
+			"textDocument/hover" => self.with_document(params, hover),
+			"textDocument/rename" => self.with_document(params, rename),
+			"textDocument/completion" => self.with_document(params, completion),
+			"textDocument/documentSymbol" => self.with_document(params, document_symbols),
+			_ => Err((METHOD_NOT_FOUND, format!("unknown method {}", method))),
+		};
+
+		if id.is_null() {
+			return Vec::new() // notifications get no response
+		}
+		let reply = match result {
+			Ok(result) => Json::object(vec![("jsonrpc", Json::from("2.0")), ("id", id.clone()), ("result", result)]),
+			Err((code, message)) => Json::object(vec![
+				("jsonrpc", Json::from("2.0")),
+				("id", id.clone()),
+				("error", Json::object(vec![("code", Json::from(code)), ("message", Json::from(message))])),
+			]),
+		};
+		vec![reply]
+	}
+
+	/// Returns `true` once the editor has asked the server to shut down.
+	pub fn is_shut_down(&self) -> bool {
+		self.shutdown
+	}
+
+	fn with_document<F>(&self, params: &Json, f: F) -> Result<Json, (i64, String)>
+		where F: Fn(&Document, &Json) -> Result<Json, (i64, String)> {
+		let uri = params.path(&["textDocument", "uri"]).as_str().unwrap_or("");
+		match self.documents.get(uri) {
+			Some(text) => f(&Document::new(uri, text), params),
+			None => Err((INVALID_PARAMS, format!("unknown document {}", uri))),
+		}
+	}
+
+	fn publish_diagnostics(&self, uri: &str) -> Json {
+		let text = self.documents.get(uri).map(|t| t.as_str()).unwrap_or("");
+		let lines: Vec<&str> = text.lines().collect();
+		let diagnostics: Vec<Json> = diagnostics(text).iter().map(|d| {
+			let length = lines.get(d.line - 1).map(|l| utf16_column(l, l.len())).unwrap_or(0);
+			let severity = match d.severity {
+				Severity::Error => 1,
+				Severity::Warning => 2,
+			};
+			Json::object(vec![
+				("range", range(d.line - 1, 0, length)),
+				("severity", Json::from(severity as i64)),
+				("source", Json::from("hack")),
+				("message", Json::from(d.message.clone())),
+			])
+		}).collect();
+		notification("textDocument/publishDiagnostics", Json::object(vec![
+			("uri", Json::from(uri)),
+			("diagnostics", Json::Array(diagnostics)),
+		]))
+	}
+}
+
+impl Default for Server {
+	fn default() -> Server {
+		Server::new()
+	}
+}
+
+/// Returns the errors of a program, or its warnings if it assembles.
+pub fn diagnostics(text: &str) -> Vec<Diagnostic> {
+	if let Err(errors) = Program::from_source(text, true) {
+		return errors
+	}
+
+	let lines = Preprocessor::new().process(text).and_then(pseudo::expand).unwrap_or_default();
+	let (lines, exports, imports) = assembler::linker_directives(lines);
+	let module = !exports.is_empty() || !imports.is_empty();
+
+	// a module's entry points are only known once it is linked
+	let mut diagnostics = match module {
+		true => Vec::new(),
+		false => Flow::new(&instruction::parse(&lines)).diagnostics(),
+	};
+	diagnostics.extend(lint::lint(&lines, &imports));
+	diagnostics.sort_by_key(|d| d.line);
+	diagnostics
+}
+
+/// Where a symbol appears in a document.
+#[derive(Clone, Debug, PartialEq)]
+struct Occurrence {
+	name: String,
+	/// Zero-based line and column range, as LSP counts them.
+	line: usize,
+	start: usize,
+	end: usize,
+	/// `true` for `(label)`, `false` for uses.
+	definition: bool,
+}
+
+/// A document along with its symbols and, if it assembles, its program.
+struct Document<'a> {
+	uri: &'a str,
+	text: &'a str,
+	occurrences: Vec<Occurrence>,
+	program: Option<Program>,
+}
+
+impl<'a> Document<'a> {
+	fn new(uri: &'a str, text: &'a str) -> Document<'a> {
+		Document {
+			uri,
+			text,
+			occurrences: occurrences(text),
+			program: Program::from_source(text, true).ok(),
+		}
+	}
+
+	fn occurrence_at(&self, params: &Json) -> Option<&Occurrence> {
+		let (line, character) = position(params);
+		self.occurrences.iter().find(|o| o.line == line && o.start <= character && character <= o.end)
+	}
+
+	fn is_label(&self, name: &str) -> bool {
+		self.occurrences.iter().any(|o| o.definition && o.name == name)
+	}
+
+	fn location(&self, occurrence: &Occurrence) -> Json {
+		Json::object(vec![
+			("uri", Json::from(self.uri)),
+			("range", range(occurrence.line, occurrence.start, occurrence.end)),
+		])
+	}
+
+	/// Returns a label's definition, or the first use of a variable.
+	fn definition(&self, name: &str) -> Option<&Occurrence> {
+		self.occurrences.iter().find(|o| o.name == name && o.definition)
+			.or_else(|| self.occurrences.iter().find(|o| o.name == name))
+	}
+}
+
+fn definition(document: &Document, params: &Json) -> Result<Json, (i64, String)> {
+	let definition = document.occurrence_at(params).and_then(|o| document.definition(&o.name));
+	Ok(definition.map(|d| document.location(d)).unwrap_or(Json::Null))
+}
+
+fn references(document: &Document, params: &Json) -> Result<Json, (i64, String)> {
+	let name = match document.occurrence_at(params) {
+		Some(occurrence) => &occurrence.name,
+		None => return Ok(Json::Null),
+	};
+	let include_declaration = params.path(&["context", "includeDeclaration"]).as_bool().unwrap_or(true);
+	let locations = document.occurrences.iter()
+		.filter(|o| o.name == *name && (include_declaration || !o.definition))
+		.map(|o| document.location(o))
+		.collect();
+	Ok(Json::Array(locations))
+}
+
+fn hover(document: &Document, params: &Json) -> Result<Json, (i64, String)> {
+	let (line, _) = position(params);
+	let program = match document.program {
+		Some(ref program) => program,
+		None => return Ok(Json::Null),
+	};
+
+	let (text, hover_range) = match document.occurrence_at(params) {
+		Some(occurrence) => {
+			let address = match program.symbol(&occurrence.name) {
+				Some(address) => address,
+				None => return Ok(Json::Null),
+			};
+			let text = match (document.is_label(&occurrence.name), SymbolTable::is_predefined(&occurrence.name)) {
+				(true, _) => format!("label `{}`: ROM {}", occurrence.name, address),
+				(false, true) => format!("predefined symbol `{}`: {}", occurrence.name, address),
+				(false, false) => format!("variable `{}`: RAM {}", occurrence.name, address),
+			};
+			(text, Some(range(occurrence.line, occurrence.start, occurrence.end)))
+		},
+		None => {
+			let addresses = program.addresses(line + 1);
+			if addresses.is_empty() {
+				return Ok(Json::Null)
+			}
+			let text: Vec<String> = addresses.iter().map(|&a| {
+				format!("ROM {}: `{}` = `{:016b}`", a, program.statements[a].instruction, program.code[a])
+			}).collect();
+			(text.join("\n\n"), None)
+		},
+	};
+
+	let mut members = vec![("contents", Json::object(vec![("kind", Json::from("markdown")), ("value", Json::from(text))]))];
+	if let Some(hover_range) = hover_range {
+		members.push(("range", hover_range));
+	}
+	Ok(Json::object(members))
+}
+
+fn rename(document: &Document, params: &Json) -> Result<Json, (i64, String)> {
+	let name = match document.occurrence_at(params) {
+		Some(occurrence) => occurrence.name.clone(),
+		None => return Err((INVALID_PARAMS, "no symbol to rename here".to_string())),
+	};
+	if SymbolTable::is_predefined(&name) {
+		return Err((INVALID_PARAMS, format!("can't rename predefined symbol {}", name)))
+	}
+	let new_name = params.get("newName").as_str().unwrap_or("");
+	if !is_symbol(new_name) {
+		return Err((INVALID_PARAMS, format!("'{}' isn't a valid symbol", new_name)))
+	}
+
+	let edits = document.occurrences.iter().filter(|o| o.name == name).map(|o| Json::object(vec![
+		("range", range(o.line, o.start, o.end)),
+		("newText", Json::from(new_name)),
+	])).collect();
+	Ok(Json::object(vec![("changes", Json::Object(vec![(document.uri.to_string(), Json::Array(edits))]))]))
+}
+
+fn completion(document: &Document, params: &Json) -> Result<Json, (i64, String)> {
+	let (line, character) = position(params);
+	let text = document.text.lines().nth(line).unwrap_or("");
+	let before = &text[..byte_offset(text, character)];
+	let code = before.trim_start();
+
+	// CompletionItemKind: Function = 3, Variable = 6, Keyword = 14, Constant = 21
+	let mut items = Vec::new();
+	let wants_symbol = code.starts_with('@') || code.split_whitespace().any(|w| w == "GOTO" || w == "LOAD");
+	if wants_symbol {
+		let mut seen = Vec::new();
+		for occurrence in &document.occurrences {
+			if !seen.contains(&occurrence.name) && !SymbolTable::is_predefined(&occurrence.name) {
+				let kind = match document.is_label(&occurrence.name) {
+					true => 3,
+					false => 6,
+				};
+				items.push(completion_item(&occurrence.name, kind));
+				seen.push(occurrence.name.clone());
+			}
+		}
+		let mut predefined = SymbolTable::new();
+		predefined.initialise();
+		for (symbol, _) in predefined.entries() {
+			items.push(completion_item(&symbol, 21));
+		}
+	} else if code.contains(';') {
+		items.extend(JUMPS.iter().map(|j| completion_item(j, 14)));
+	} else if code.contains('=') {
+		items.extend(COMPS.iter().map(|c| completion_item(c, 14)));
+	} else {
+		items.extend(PSEUDO.iter().map(|p| completion_item(p, 14)));
+		items.extend(DESTS.iter().map(|d| completion_item(&format!("{}=", d), 14)));
+		items.extend(COMPS.iter().map(|c| completion_item(c, 14)));
+	}
+	Ok(Json::Array(items))
+}
+
+fn document_symbols(document: &Document, _: &Json) -> Result<Json, (i64, String)> {
+	// SymbolKind: Function = 12, Variable = 13
+	let mut symbols = Vec::new();
+	let mut seen: Vec<&str> = Vec::new();
+	for occurrence in &document.occurrences {
+		let label = document.is_label(&occurrence.name);
+		if (label && !occurrence.definition) || seen.contains(&occurrence.name.as_str()) || SymbolTable::is_predefined(&occurrence.name) {
+			continue
+		}
+		seen.push(&occurrence.name);
+		let detail = document.program.as_ref().and_then(|p| p.symbol(&occurrence.name)).map(|address| match label {
+			true => format!("ROM {}", address),
+			false => format!("RAM {}", address),
+		});
+		let occurrence_range = range(occurrence.line, occurrence.start, occurrence.end);
+		let mut members = vec![
+			("name", Json::from(occurrence.name.clone())),
+			("kind", Json::from(if label { 12i64 } else { 13 })),
+			("range", occurrence_range.clone()),
+			("selectionRange", occurrence_range),
+		];
+		if let Some(detail) = detail {
+			members.insert(1, ("detail", Json::from(detail)));
+		}
+		symbols.push(Json::object(members));
+	}
+	Ok(Json::Array(symbols))
+}
+
+fn capabilities() -> Json {
+	Json::object(vec![
+		("capabilities", Json::object(vec![
+			("textDocumentSync", Json::from(1i64)), // full document on every change
+			("definitionProvider", Json::from(true)),
+			("referencesProvider", Json::from(true)),
+			("hoverProvider", Json::from(true)),
+			("renameProvider", Json::from(true)),
+			("completionProvider", Json::object(vec![("triggerCharacters", Json::Array(vec![Json::from("@")]))])),
+			("documentSymbolProvider", Json::from(true)),
+		])),
+		("serverInfo", Json::object(vec![("name", Json::from("hack"))])),
+	])
+}
+
+/// Finds the labels defined with `(label)` and the symbols used by
+/// A-instructions and pseudo-instructions.
+fn occurrences(text: &str) -> Vec<Occurrence> {
+	let mut occurrences = Vec::new();
+	for (line, raw) in text.lines().enumerate() {
+		let code = match raw.find("//") {
+			Some(i) => &raw[..i],
+			None => raw,
+		};
+		let content = code.trim();
+		let (name, definition) = if let Some(label) = content.strip_prefix('(') {
+			(label.trim_end_matches(')').trim(), true)
+		} else if let Some(symbol) = content.strip_prefix('@') {
+			(symbol.trim(), false)
+		} else {
+			match content.split_whitespace().next() {
+				Some("GOTO") | Some("IF") => (content.split_whitespace().last().unwrap_or(""), false),
+				Some("LOAD") => (content.rsplit(',').next().unwrap_or("").trim(), false),
+				_ => continue,
+			}
+		};
+		if !is_symbol(name) {
+			continue
+		}
+		if let Some(offset) = code.rfind(name) {
+			let start = utf16_column(code, offset);
+			let end = start + name.encode_utf16().count();
+			occurrences.push(Occurrence { name: name.to_string(), line, start, end, definition });
+		}
+	}
+	occurrences
+}
+
+/// Returns `true` for names that can be used as labels and variables.
+fn is_symbol(name: &str) -> bool {
+	let valid = |c: char| c.is_ascii_alphanumeric() || "_.$:".contains(c);
+	!name.is_empty() && !name.starts_with(|c: char| c.is_ascii_digit()) && name.chars().all(valid)
+}
+
+fn position(params: &Json) -> (usize, usize) {
+	let line = params.path(&["position", "line"]).as_i64().unwrap_or(0);
+	let character = params.path(&["position", "character"]).as_i64().unwrap_or(0);
+	(line.max(0) as usize, character.max(0) as usize)
+}
+
+/// Converts a column, which LSP counts in UTF-16 code units, into a byte offset in `text`.
+fn byte_offset(text: &str, character: usize) -> usize {
+	let mut units = 0;
+	for (offset, c) in text.char_indices() {
+		if units >= character {
+			return offset
+		}
+		units += c.len_utf16();
+	}
+	text.len()
+}
+
+/// Converts a byte offset in `text` into a column in UTF-16 code units.
+fn utf16_column(text: &str, offset: usize) -> usize {
+	text[..offset].encode_utf16().count()
+}
+
+fn range(line: usize, start: usize, end: usize) -> Json {
+	let point = |character: usize| Json::object(vec![("line", Json::from(line)), ("character", Json::from(character))]);
+	Json::object(vec![("start", point(start)), ("end", point(end))])
+}
+
+fn completion_item(label: &str, kind: i64) -> Json {
+	Json::object(vec![("label", Json::from(label)), ("kind", Json::from(kind))])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+	Json::object(vec![("jsonrpc", Json::from("2.0")), ("method", Json::from(method)), ("params", params)])
+}
+
+/// Reads a message framed with a `Content-Length` header, or `None` at the end of the input.
+pub fn read_message<R: BufRead>(input: &mut R) -> Result<Option<Json>, String> {
+	let mut length = None;
+	loop {
+		let mut header = String::new();
+		match input.read_line(&mut header) {
+			Ok(0) => return Ok(None),
+			Ok(_) => {},
+			Err(why) => return Err(format!("couldn't read message: {}", why)),
+		}
+		let header = header.trim();
+		if header.is_empty() {
+			break
+		}
+		if let Some(value) = header.strip_prefix("Content-Length:") {
+			length = value.trim().parse::<usize>().ok();
+		}
+	}
+
+	let length = length.ok_or("missing Content-Length header")?;
+	let mut body = vec![0; length];
+	input.read_exact(&mut body).map_err(|why| format!("couldn't read message: {}", why))?;
+	let body = String::from_utf8(body).map_err(|_| "message isn't UTF-8".to_string())?;
+	Json::parse(&body).map(Some)
+}
+
+/// Writes a message with its `Content-Length` header.
+pub fn write_message<W: Write>(output: &mut W, message: &Json) -> Result<(), String> {
+	let body = message.to_string();
+	write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+		.and_then(|_| output.flush())
+		.map_err(|why| format!("couldn't write message: {}", why))
+}
+
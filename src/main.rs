@@ -1,17 +1,650 @@
 extern crate hack;
 
 use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::Duration;
+use hack::archive::Archive;
 use hack::assembler::Assembler;
+use hack::build;
+use hack::cfg::Cfg;
+use hack::cpu::{self, Cpu};
+use hack::dap;
+use hack::debugger::Debugger;
+use hack::disassembler;
+use hack::explain;
+use hack::formatter;
+use hack::gates;
+use hack::instruction;
+use hack::linker::Linker;
+use hack::lint;
+use hack::lsp::Server;
+use hack::object::ObjectModule;
+use hack::preprocessor::{self, SourceLine};
+use hack::profiler::{self, Profile};
+use hack::program::Program;
+use hack::repl::Repl;
+use hack::sourcemap::SourceMap;
+use hack::trace::{self, Trace};
+use hack::vm;
+use hack::watch::Watcher;
+
+const USAGE: &str = "HACK Assembler. Translates assembly (mnemonics) into binary machine code.\n\
+	\nUsage:\n\thack [OPTIONS] [PATH_TO_ASM_FILE]\
+	\n\thack link [--map] [PATH_TO_OBJECT_OR_ARCHIVE]... -o [PATH_TO_HACK_FILE]\
+	\n\thack ar [PATH_TO_ARCHIVE] [PATH_TO_OBJECT_FILE]...\
+	\n\thack cfg [OPTIONS] [PATH_TO_ASM_FILE] > [PATH_TO_DOT_FILE]\
+	\n\thack lint [OPTIONS] [PATH_TO_ASM_FILE]\
+	\n\thack fmt [--check] [PATH_TO_ASM_FILE]...\
+	\n\thack lsp\
+	\n\thack watch [OPTIONS] [PATH_TO_DIRECTORY]\
+	\n\thack repl [--run]\
+	\n\thack explain [OPTIONS] [PATH_TO_ASM_FILE]\
+	\n\thack debug [OPTIONS] [PATH_TO_ASM_FILE]\
+	\n\thack dap\
+	\n\thack profile [OPTIONS] [--cycles N] [--collapsed PATH_TO_FILE] [PATH_TO_ASM_FILE]\
+	\n\thack trace [OPTIONS] [--cycles N] [-o PATH_TO_TRACE_FILE] [PATH_TO_ASM_FILE]\
+	\n\thack trace-diff [PATH_TO_TRACE_FILE] [PATH_TO_TRACE_FILE]\
+	\n\thack vm [PATH_TO_VM_FILE_OR_DIRECTORY]\
+	\n\thack jack [--xml] [PATH_TO_JACK_FILE_OR_DIRECTORY]\
+	\n\thack build [--intermediates] [--force] [PATH_TO_DIRECTORY]\
+	\n\thack hdl-check [--cycles N] [PATH_TO_HACK_FILE]\
+	\n\thack disasm [PATH_TO_HACK_FILE]\n\
+	\nOptions:\
+	\n\t-D NAME[=VALUE]\tdefine a constant for conditional assembly\
+	\n\t--strict\tdisable pseudo-instructions (book compatible)\
+	\n\t--listing\twrite a <name_of_prog>.lst listing\
+	\n\t--map\t\twrite a <name_of_prog>.map of ROM and RAM usage\
+	\n\t--source-map\twrite a <name_of_prog>.sourcemap.json mapping ROM addresses to source lines\
+	\n\t-O\t\tenable the peephole optimiser\
+	\n\t--strip-unreachable\tremove code that can never execute\
+	\n\t--extended\tenable the shift instructions of the extended instruction set, such as D=D<<\
+	\n\t-c\t\twrite a relocatable <name_of_prog>.o module for the linker";
 
-#[cfg(not(test))]
 fn main() {
-	let args: Vec<String> = env::args().collect();
+	let args: Vec<String> = env::args().skip(1).collect();
+	match args.first().map(|a| a.as_str()) {
+		Some("link") => link(&args[1..]),
+		Some("ar") => archive(&args[1..]),
+		Some("cfg") => cfg(&args[1..]),
+		Some("lint") => lint(&args[1..]),
+		Some("fmt") => format(&args[1..]),
+		Some("lsp") => language_server(),
+		Some("watch") => watch_directory(&args[1..]),
+		Some("repl") => repl(&args[1..]),
+		Some("explain") => explain(&args[1..]),
+		Some("debug") => debug(&args[1..]),
+		Some("dap") => debug_adapter(),
+		Some("profile") => profile(&args[1..]),
+		Some("trace") => record_trace(&args[1..]),
+		Some("trace-diff") => diff_traces(&args[1..]),
+		Some("vm") => translate_vm(&args[1..]),
+		Some("jack") => compile_jack(&args[1..]),
+		Some("build") => build_directory(&args[1..]),
+		Some("hdl-check") => hdl_check(&args[1..]),
+		Some("disasm") => disassemble(&args[1..]),
+		_ => assemble(&args),
+	}
+}
+
+/// Assembles a single `.asm` file.
+fn assemble(args: &[String]) {
+	if let Err(why) = configure(args).try_translate() {
+		fail(&[why]);
+	}
+}
+
+/// Prints the control-flow graph of a `.asm` file in Graphviz DOT.
+fn cfg(args: &[String]) {
+	let mut assembler = configure(args);
+	let source = read_source(&mut assembler);
+	let statements = instruction::parse(&source);
+	print!("{}", Cfg::new(&statements).to_dot(assembler.input_filename()));
+}
+
+/// Describes how each instruction of a `.asm` file is encoded.
+fn explain(args: &[String]) {
+	let mut assembler = configure(args);
+	let program = assemble_in_memory(&mut assembler);
+	print!("{}", explain::explain(&program));
+}
+
+/// Debugs a `.asm` file on the emulated CPU.
+fn debug(args: &[String]) {
+	let mut assembler = configure(args);
+	let program = assemble_in_memory(&mut assembler);
+	let source = fs::read_to_string(assembler.input_filename()).unwrap_or_default();
+	let mut debugger = Debugger::new(program, &source);
+
+	println!("Debugging {}. Type help for the commands.", assembler.input_filename());
+	println!("{}", debugger.location());
+	let stdin = io::stdin();
+	let mut lines = stdin.lock().lines();
+	let mut previous = String::new();
+	loop {
+		print!("(hdb) ");
+		let _ = io::stdout().flush();
+		let mut command = match lines.next() {
+			Some(Ok(line)) => line.trim().to_string(),
+			_ => break,
+		};
+		// an empty line repeats the last command, handy for stepping
+		if command.is_empty() {
+			command = previous.clone();
+		}
+		if command == "quit" || command == "q" {
+			break
+		}
+		let output = debugger.execute(&command);
+		if !output.is_empty() {
+			println!("{}", output);
+		}
+		previous = command;
+	}
+}
+
+/// Runs a `.asm` file on the emulated CPU and reports where the cycles went.
+fn profile(args: &[String]) {
+	let mut cycles = profiler::DEFAULT_CYCLES;
+	let mut collapsed = None;
+	let mut options = Vec::new();
+	let mut i = 0;
+	while i < args.len() {
+		match args[i].as_str() {
+			"--cycles" => {
+				i += 1;
+				cycles = match args.get(i).map(|c| c.parse::<u64>()) {
+					Some(Ok(cycles)) => cycles,
+					_ => usage(),
+				};
+			},
+			"--collapsed" => {
+				i += 1;
+				collapsed = Some(args.get(i).unwrap_or_else(|| usage()).clone());
+			},
+			_ => options.push(args[i].clone()),
+		}
+		i += 1;
+	}
+
+	let mut assembler = configure(&options);
+	let program = assemble_in_memory(&mut assembler);
+	let name = Path::new(assembler.input_filename()).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+	let source_map = SourceMap::from_program(&program, assembler.input_filename());
+	let profile = Profile::run(&program, &mut Cpu::new(), cycles);
+	print!("{}", profile.report(&program, &source_map, &name));
+	if let Some(path) = collapsed {
+		if let Err(why) = fs::write(&path, profile.collapsed(&program, &source_map, &name)) {
+			fail(&[format!("couldn't write {}: {}", path, why)]);
+		}
+	}
+}
+
+/// Records every cycle of a `.asm` file running on the emulated CPU.
+fn record_trace(args: &[String]) {
+	let mut cycles = trace::DEFAULT_CYCLES;
+	let mut output = None;
+	let mut options = Vec::new();
+	let mut i = 0;
+	while i < args.len() {
+		match args[i].as_str() {
+			"--cycles" => {
+				i += 1;
+				cycles = match args.get(i).map(|c| c.parse::<u64>()) {
+					Some(Ok(cycles)) => cycles,
+					_ => usage(),
+				};
+			},
+			"-o" => {
+				i += 1;
+				output = Some(args.get(i).unwrap_or_else(|| usage()).clone());
+			},
+			_ => options.push(args[i].clone()),
+		}
+		i += 1;
+	}
+
+	let mut assembler = configure(&options);
+	let program = assemble_in_memory(&mut assembler);
+	let output = output.unwrap_or_else(|| assembler.input_filename().trim_end_matches(".asm").to_string() + ".trace");
+	let trace = Trace::record(&program, &mut Cpu::new(), cycles);
+	if let Err(why) = fs::write(&output, trace.to_string()) {
+		fail(&[format!("couldn't write {}: {}", output, why)]);
+	}
+	println!("recorded {} cycle{} to {}", trace.steps.len(), plural(trace.steps.len()), output);
+}
+
+/// Compares two traces, failing at the first cycle where they differ.
+fn diff_traces(args: &[String]) {
 	if args.len() != 2 {
-		println!("HACK Assembler. Translates assembly (mnemonics) into binary machine code.\n
-			\nUsage:\n\tassembler [PATH_TO_ASM_FILE]");
-		return;
-	}
-	let path_to_asm_file = &args[1];
-	let mut assembler = Assembler::new(path_to_asm_file);
-	assembler.translate();
-}
\ No newline at end of file
+		usage();
+	}
+	let traces: Vec<Trace> = args.iter().map(|path| {
+		let text = fs::read_to_string(path).unwrap_or_else(|why| fail(&[format!("couldn't read {}: {}", path, why)]));
+		Trace::parse(&text).unwrap_or_else(|why| fail(&[format!("{}: {}", path, why)]))
+	}).collect();
+	match trace::diff(&traces[0], &traces[1]) {
+		Some(difference) => {
+			println!("{}", difference);
+			process::exit(1);
+		},
+		None => println!("the traces are identical for {} cycle{}", traces[0].steps.len(), plural(traces[0].steps.len())),
+	}
+}
+
+/// Translates VM code into a `.asm` file for the assembler.
+fn translate_vm(args: &[String]) {
+	if args.len() != 1 {
+		usage();
+	}
+	let (output, assembly) = vm::translate_path(&args[0]).unwrap_or_else(|why| fail(&[why]));
+	if let Err(why) = fs::write(&output, assembly) {
+		fail(&[format!("couldn't write {}: {}", output.display(), why)]);
+	}
+	println!("translated {} to {}", args[0], output.display());
+}
+
+/// Compiles Jack classes into VM code, or with `--xml` into the XML of project 10.
+fn compile_jack(args: &[String]) {
+	let xml = args.iter().any(|a| a == "--xml");
+	let paths: Vec<&String> = args.iter().filter(|a| *a != "--xml").collect();
+	if paths.len() != 1 {
+		usage();
+	}
+	let files = build::jack_files(Path::new(paths[0])).unwrap_or_else(|why| fail(&[why]));
+	for file in files {
+		let written = match xml {
+			true => build::write_xml(&file).map(|(tokens, tree)| format!("{} and {}", tokens.display(), tree.display())),
+			false => build::compile_jack(&file).map(|vm| vm.display().to_string()),
+		};
+		match written {
+			Ok(written) => println!("compiled {} to {}", file.display(), written),
+			Err(why) => fail(&[why]),
+		}
+	}
+}
+
+/// Builds the Jack, VM and assembly files of a directory into `.hack`
+/// programs, skipping the stages whose inputs haven't changed.
+fn build_directory(args: &[String]) {
+	let flags = ["--intermediates", "--force"];
+	let paths: Vec<&String> = args.iter().filter(|a| !flags.contains(&a.as_str())).collect();
+	if paths.len() != 1 {
+		usage();
+	}
+	let mut builder = build::Builder::new(Path::new(paths[0]));
+	builder.set_intermediates(args.iter().any(|a| a == "--intermediates"));
+	builder.set_force(args.iter().any(|a| a == "--force"));
+	let steps = builder.build().unwrap_or_else(|why| fail(&[why]));
+	for step in &steps {
+		match step.ran {
+			true => println!("{} {} to {}", step.stage.verb(), step.input, step.output.display()),
+			false => println!("{} is up to date", step.output.display()),
+		}
+	}
+}
+
+/// Runs a `.hack` program on the emulator and on a gate-level model of the
+/// CPU, failing at the first cycle where they disagree.
+fn hdl_check(args: &[String]) {
+	let mut cycles = gates::DEFAULT_CYCLES;
+	let mut paths = Vec::new();
+	let mut i = 0;
+	while i < args.len() {
+		match args[i].as_str() {
+			"--cycles" => {
+				i += 1;
+				cycles = match args.get(i).map(|c| c.parse::<u64>()) {
+					Some(Ok(cycles)) => cycles,
+					_ => usage(),
+				};
+			},
+			_ => paths.push(args[i].clone()),
+		}
+		i += 1;
+	}
+	if paths.len() != 1 {
+		usage();
+	}
+
+	if let Err(why) = gates::check_encoding() {
+		fail(&[why]);
+	}
+	println!("the assembler's encoding of all {} comp mnemonics matches the ALU", instruction::COMPS.len());
+
+	let text = fs::read_to_string(&paths[0]).unwrap_or_else(|why| fail(&[format!("couldn't read {}: {}", paths[0], why)]));
+	let rom = cpu::parse_hack(&text).unwrap_or_else(|why| fail(&[format!("{}: {}", paths[0], why)]));
+	match gates::cross_check(&rom, cycles) {
+		Ok((cycles, end)) => println!("the emulator and the gate-level CPU agree for {} cycle{}{}", cycles, plural(cycles as usize), match end {
+			profiler::End::Halted => ", until the program halted",
+			profiler::End::Ended => ", until PC went past the last instruction",
+			profiler::End::Limit => ", the cycle limit",
+		}),
+		Err(why) => fail(&[why]),
+	}
+}
+
+/// Prints the assembly of a `.hack` program.
+fn disassemble(args: &[String]) {
+	if args.len() != 1 {
+		usage();
+	}
+	let text = fs::read_to_string(&args[0]).unwrap_or_else(|why| fail(&[format!("couldn't read {}: {}", args[0], why)]));
+	let rom = cpu::parse_hack(&text).unwrap_or_else(|why| fail(&[format!("{}: {}", args[0], why)]));
+	match disassembler::disassemble_program(&rom) {
+		Ok(assembly) => print!("{}", assembly),
+		Err(why) => fail(&[format!("{}: {}", args[0], why)]),
+	}
+}
+
+/// Serves the Debug Adapter Protocol on stdio, for debugging from an editor.
+fn debug_adapter() {
+	let stdin = io::stdin();
+	if let Err(why) = dap::Server::new().run(&mut stdin.lock(), &mut io::stdout()) {
+		fail(&[why]);
+	}
+}
+
+/// Assembles a `.asm` file without writing any output, failing on errors.
+fn assemble_in_memory(assembler: &mut Assembler) -> Program {
+	match Program::assemble_extended(&read_source(assembler), assembler.extended()) {
+		Ok(program) => program,
+		Err(errors) => {
+			for error in &errors {
+				eprintln!("{}: {}", assembler.input_filename(), error);
+			}
+			process::exit(1);
+		},
+	}
+}
+
+/// Returns the source lines of a `.asm` file as the assembler sees them, failing on errors.
+fn read_source(assembler: &mut Assembler) -> Vec<SourceLine> {
+	match assembler.read_source() {
+		Ok(source) => source,
+		Err(why) => fail(&[why]),
+	}
+}
+
+/// Checks a `.asm` file for suspicious code, failing if any is found.
+fn lint(args: &[String]) {
+	let mut assembler = configure(args);
+	let source = read_source(&mut assembler);
+	let diagnostics = lint::lint(&source, assembler.imports());
+	for diagnostic in &diagnostics {
+		println!("{}: {}", assembler.input_filename(), diagnostic);
+	}
+	if !diagnostics.is_empty() {
+		process::exit(1);
+	}
+}
+
+/// Rewrites `.asm` files in the canonical style, or with `--check` lists
+/// the files that would change and fails if there are any.
+fn format(args: &[String]) {
+	let check = args.iter().any(|a| a == "--check");
+	let files: Vec<&String> = args.iter().filter(|a| *a != "--check").collect();
+	if files.is_empty() {
+		usage();
+	}
+
+	let mut unformatted = false;
+	for filename in files {
+		let source = match fs::read_to_string(filename) {
+			Ok(source) => source,
+			Err(why) => fail(&[format!("couldn't read {}: {}", filename, why)]),
+		};
+		let formatted = formatter::format(&source);
+		if formatted == source {
+			continue
+		}
+		if check {
+			println!("{}", filename);
+			unformatted = true;
+		} else if let Err(why) = fs::write(filename, formatted) {
+			fail(&[format!("couldn't write {}: {}", filename, why)]);
+		}
+	}
+	if unformatted {
+		process::exit(1);
+	}
+}
+
+/// Runs the language server on stdin and stdout.
+fn language_server() {
+	let stdin = io::stdin();
+	let mut server = Server::new();
+	if let Err(why) = server.run(&mut stdin.lock(), &mut io::stdout()) {
+		fail(&[why]);
+	}
+	// the protocol asks for an error status when exiting without a shutdown request
+	if !server.is_shut_down() {
+		process::exit(1);
+	}
+}
+
+/// Reassembles the `.asm` files of a directory whenever they change.
+fn watch_directory(args: &[String]) {
+	let (directory, options) = match args.split_last() {
+		Some(split) => split,
+		None => usage(),
+	};
+	let mut watcher = Watcher::new(directory);
+	loop {
+		let changed = match watcher.changed() {
+			Ok(changed) => changed,
+			Err(why) => fail(&[why]),
+		};
+		if !changed.is_empty() {
+			let (mut errors, mut warnings) = (0, 0);
+			for path in &changed {
+				let mut args = options.to_vec();
+				args.push(path.display().to_string());
+				let mut assembler = configure(&args);
+				assembler.set_quiet(true);
+				match assembler.try_translate() {
+					Ok(()) => warnings += assembler.warnings().len(),
+					Err(why) => {
+						eprintln!("error: {}", why);
+						errors += 1;
+					},
+				}
+			}
+			println!("assembled {} file{}: {} error{}, {} warning{}",
+				changed.len(), plural(changed.len()), errors, plural(errors), warnings, plural(warnings));
+		}
+		thread::sleep(Duration::from_millis(500));
+	}
+}
+
+fn plural(count: usize) -> &'static str {
+	match count {
+		1 => "",
+		_ => "s",
+	}
+}
+
+/// Shows the encoding of instructions typed at the prompt, and with
+/// `--run` executes them.
+fn repl(args: &[String]) {
+	let mut repl = Repl::new();
+	for arg in args {
+		match arg.as_str() {
+			"--run" => repl.set_run(true),
+			_ => usage(),
+		}
+	}
+
+	println!("Hack REPL. Type :help for help.");
+	let stdin = io::stdin();
+	let mut lines = stdin.lock().lines();
+	loop {
+		print!("hack> ");
+		let _ = io::stdout().flush();
+		let line = match lines.next() {
+			Some(Ok(line)) => line,
+			_ => break,
+		};
+		if line.trim() == ":quit" {
+			break
+		}
+		let output = repl.eval(&line);
+		if !output.is_empty() {
+			println!("{}", output);
+		}
+	}
+}
+
+/// Creates an assembler for the `.asm` file and options given on the command line.
+fn configure(args: &[String]) -> Assembler {
+	let mut defines: Vec<(String, i32)> = Vec::new();
+	let mut files: Vec<&String> = Vec::new();
+	let mut strict = false;
+	let mut listing = false;
+	let mut object = false;
+	let mut map = false;
+	let mut source_map = false;
+	let mut optimize = false;
+	let mut strip_unreachable = false;
+	let mut extended = false;
+
+	let mut i = 0;
+	while i < args.len() {
+		let arg = &args[i];
+		if arg == "--strict" {
+			strict = true;
+		} else if arg == "--listing" {
+			listing = true;
+		} else if arg == "-c" {
+			object = true;
+		} else if arg == "--map" {
+			map = true;
+		} else if arg == "--source-map" {
+			source_map = true;
+		} else if arg == "-O" {
+			optimize = true;
+		} else if arg == "--strip-unreachable" {
+			strip_unreachable = true;
+		} else if arg == "--extended" {
+			extended = true;
+		} else if arg == "-D" {
+			i += 1;
+			match args.get(i) {
+				Some(define) => defines.push(parse_define(define)),
+				None => usage(),
+			}
+		} else if let Some(define) = arg.strip_prefix("-D") {
+			defines.push(parse_define(define));
+		} else {
+			files.push(arg);
+		}
+		i += 1;
+	}
+
+	if files.len() != 1 {
+		usage();
+	}
+
+	let mut assembler = Assembler::new(files[0]);
+	assembler.set_pseudo_instructions(!strict);
+	assembler.set_listing(listing);
+	assembler.set_object(object);
+	assembler.set_map(map);
+	assembler.set_source_map(source_map);
+	assembler.set_optimize(optimize);
+	assembler.set_strip_unreachable(strip_unreachable);
+	assembler.set_extended(extended);
+	for (name, value) in &defines {
+		assembler.define(name, *value);
+	}
+	assembler
+}
+
+/// Links `.o` modules into a `.hack` program.
+fn link(args: &[String]) {
+	let mut output: Option<&String> = None;
+	let mut map = false;
+	let mut linker = Linker::new();
+
+	let mut i = 0;
+	while i < args.len() {
+		if args[i] == "--map" {
+			map = true;
+		} else if args[i] == "-o" {
+			i += 1;
+			match args.get(i) {
+				Some(filename) => output = Some(filename),
+				None => usage(),
+			}
+		} else if args[i].ends_with(".a") {
+			match Archive::load(&args[i]) {
+				Ok(archive) => linker.add_archive(&args[i], archive),
+				Err(why) => fail(&[why]),
+			}
+		} else {
+			match ObjectModule::load(&args[i]) {
+				Ok(module) => linker.add_module(&args[i], module),
+				Err(why) => fail(&[why]),
+			}
+		}
+		i += 1;
+	}
+
+	let output = match output {
+		Some(output) => output,
+		None => usage(),
+	};
+	match linker.link_with_map() {
+		Ok((program, memory_map)) => {
+			let code: String = program.iter().map(|word| format!("{:016b}\n", word)).collect();
+			if let Err(why) = fs::write(output, code) {
+				fail(&[format!("couldn't write {}: {}", output, why)]);
+			}
+			if map {
+				let filename = output.trim_end_matches(".hack").to_string() + ".map";
+				if let Err(why) = fs::write(&filename, memory_map.to_string()) {
+					fail(&[format!("couldn't write {}: {}", filename, why)]);
+				}
+			}
+		},
+		Err(errors) => fail(&errors),
+	}
+}
+
+/// Bundles `.o` modules into a `.a` library archive.
+fn archive(args: &[String]) {
+	if args.len() < 2 {
+		usage();
+	}
+
+	let mut archive = Archive::new();
+	for filename in &args[1..] {
+		match ObjectModule::load(filename) {
+			Ok(module) => archive.add_member(filename, module),
+			Err(why) => fail(&[why]),
+		}
+	}
+	if let Err(why) = fs::write(&args[0], archive.to_string()) {
+		fail(&[format!("couldn't write {}: {}", args[0], why)]);
+	}
+}
+
+fn fail(errors: &[String]) -> ! {
+	for error in errors {
+		eprintln!("error: {}", error);
+	}
+	process::exit(1);
+}
+
+/// Parses the argument of `-D`, failing with a usage error if it's invalid.
+fn parse_define(define: &str) -> (String, i32) {
+	preprocessor::parse_define(define).unwrap_or_else(|why| fail(&[format!("-D {}: {}", define, why)]))
+}
+
+fn usage() -> ! {
+	println!("{}", USAGE);
+	process::exit(1);
+}
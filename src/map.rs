@@ -0,0 +1,97 @@
+use std::fmt;
+
+/// Number of words in the Hack instruction memory.
+pub const ROM_SIZE: usize = 32768;
+
+/// First RAM address given to variables.
+pub const VARIABLES_START: u16 = 16;
+
+/// RAM available to variables: everything between R15 and the screen.
+pub const VARIABLES_SIZE: usize = 16384 - VARIABLES_START as usize;
+
+/// A contiguous range of ROM starting at a label, or at the start of a module.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+	pub name: String,
+	pub module: Option<String>,
+	pub start: u16,
+	pub size: usize,
+}
+
+/// Describes where everything in a program ended up in ROM and RAM.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryMap {
+	pub regions: Vec<Region>,
+	pub variables: Vec<(String, u16)>,
+	pub rom_size: usize,
+}
+
+impl MemoryMap {
+	pub fn new() -> MemoryMap {
+		MemoryMap::default()
+	}
+
+	/// Splits the ROM range `start..end` into one region per label.
+	///
+	/// Each region runs until the next label. Code before the first label
+	/// is recorded as a `(start)` region.
+	pub fn add_regions(&mut self, module: Option<&str>, labels: &[(String, u16)], start: u16, end: usize) {
+		let mut labels: Vec<&(String, u16)> = labels.iter().collect();
+		labels.sort_by_key(|&(_, address)| *address);
+
+		let first = labels.first().map_or(end, |&(_, address)| *address as usize);
+		if first > start as usize || labels.is_empty() {
+			self.push_region("(start)", module, start, first - start as usize);
+		}
+		for (i, &(name, address)) in labels.iter().enumerate() {
+			let next = labels.get(i + 1).map_or(end, |&(_, address)| *address as usize);
+			self.push_region(name, module, *address, next - *address as usize);
+		}
+	}
+
+	fn push_region(&mut self, name: &str, module: Option<&str>, start: u16, size: usize) {
+		self.regions.push(Region {
+			name: name.to_string(),
+			module: module.map(|m| m.to_string()),
+			start,
+			size,
+		});
+	}
+
+	/// Returns an error when the program doesn't fit in ROM.
+	pub fn check_capacity(&self) -> Result<(), String> {
+		match self.rom_size > ROM_SIZE {
+			true => Err(format!("program is {} words, exceeding the ROM capacity of {} words by {}",
+				self.rom_size, ROM_SIZE, self.rom_size - ROM_SIZE)),
+			false => Ok(()),
+		}
+	}
+}
+
+fn percentage(used: usize, total: usize) -> f64 {
+	used as f64 * 100.0 / total as f64
+}
+
+impl fmt::Display for MemoryMap {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "ROM")?;
+		writeln!(f, "  {:>5}  {:>5}  region", "start", "size")?;
+		for region in &self.regions {
+			match region.module {
+				Some(ref module) => writeln!(f, "  {:05}  {:5}  {} ({})", region.start, region.size, region.name, module)?,
+				None => writeln!(f, "  {:05}  {:5}  {}", region.start, region.size, region.name)?,
+			}
+		}
+
+		writeln!(f, "\nRAM")?;
+		writeln!(f, "  {:>5}  variable", "addr")?;
+		for (name, address) in &self.variables {
+			writeln!(f, "  {:05}  {}", address, name)?;
+		}
+
+		writeln!(f, "\nSummary")?;
+		writeln!(f, "  ROM  {:5} / {} words ({:.1}%)", self.rom_size, ROM_SIZE, percentage(self.rom_size, ROM_SIZE))?;
+		writeln!(f, "  RAM  {:5} / {} variable words ({:.1}%)",
+			self.variables.len(), VARIABLES_SIZE, percentage(self.variables.len(), VARIABLES_SIZE))
+	}
+}
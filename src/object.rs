@@ -0,0 +1,122 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// How the linker must fix up a word of code once modules are laid out in ROM.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Relocation {
+	/// The word is final, e.g. a C-instruction or `@5`.
+	Absolute,
+	/// The word is the address of a label relative to the start of the module.
+	Label,
+	/// The word is the address of a label exported by another module.
+	Import(String),
+	/// The word is the RAM address of a variable, allocated by the linker.
+	Variable(String),
+}
+
+/// A word of code along with its relocation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectWord {
+	pub code: u16,
+	pub relocation: Relocation,
+}
+
+/// A separately assembled module, stored as a `.o` file.
+///
+/// The format is line oriented text, so it can be inspected like a `.hack` file:
+///
+/// ```text
+/// hack-object 1
+/// export MAIN 0
+/// import MULT
+/// code 0000000000000000 label
+/// code 0000000000000000 import MULT
+/// code 0000000000000000 variable i
+/// code 1110101010000111
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjectModule {
+	/// Labels visible to other modules, with their module-relative address.
+	pub exports: Vec<(String, u16)>,
+	/// Labels this module expects another module to export.
+	pub imports: Vec<String>,
+	pub code: Vec<ObjectWord>,
+}
+
+const HEADER: &str = "hack-object 1";
+
+impl ObjectModule {
+	pub fn new() -> ObjectModule {
+		ObjectModule::default()
+	}
+
+	/// Appends a word of code.
+	pub fn push(&mut self, code: u16, relocation: Relocation) {
+		self.code.push(ObjectWord { code, relocation });
+	}
+
+	/// Parses the textual object format.
+	pub fn parse(content: &str) -> Result<ObjectModule, String> {
+		let mut lines = content.lines().enumerate();
+		match lines.next() {
+			Some((_, header)) if header.trim() == HEADER => {},
+			_ => return Err("not a hack object file".to_string()),
+		}
+
+		let mut module = ObjectModule::new();
+		for (index, line) in lines {
+			let fields: Vec<&str> = line.split_whitespace().collect();
+			let error = || format!("line {}: malformed '{}'", index + 1, line);
+			match fields.as_slice() {
+				[] => {},
+				["export", name, address] => {
+					let address = address.parse::<u16>().map_err(|_| error())?;
+					module.exports.push((name.to_string(), address));
+				},
+				["import", name] => module.imports.push(name.to_string()),
+				["code", bits, rest @ ..] => {
+					let code = u16::from_str_radix(bits, 2).map_err(|_| error())?;
+					let relocation = match rest {
+						[] => Relocation::Absolute,
+						["label"] => Relocation::Label,
+						["import", name] => Relocation::Import(name.to_string()),
+						["variable", name] => Relocation::Variable(name.to_string()),
+						_ => return Err(error()),
+					};
+					module.push(code, relocation);
+				},
+				_ => return Err(error()),
+			}
+		}
+		Ok(module)
+	}
+
+	/// Reads an object file.
+	pub fn load(filename: &str) -> Result<ObjectModule, String> {
+		let path = Path::new(filename);
+		let content = fs::read_to_string(path).map_err(|why| format!("couldn't read {}: {}", path.display(), why))?;
+		ObjectModule::parse(&content).map_err(|why| format!("{}: {}", path.display(), why))
+	}
+}
+
+impl fmt::Display for ObjectModule {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{}", HEADER)?;
+		for (name, address) in &self.exports {
+			writeln!(f, "export {} {}", name, address)?;
+		}
+		for name in &self.imports {
+			writeln!(f, "import {}", name)?;
+		}
+		for word in &self.code {
+			match word.relocation {
+				Relocation::Absolute => writeln!(f, "code {:016b}", word.code)?,
+				Relocation::Label => writeln!(f, "code {:016b} label", word.code)?,
+				Relocation::Import(ref name) => writeln!(f, "code {:016b} import {}", word.code, name)?,
+				Relocation::Variable(ref name) => writeln!(f, "code {:016b} variable {}", word.code, name)?,
+			}
+		}
+		Ok(())
+	}
+}
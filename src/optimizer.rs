@@ -0,0 +1,245 @@
+use std::collections::{HashMap, HashSet};
+
+use instruction::{self, Instruction, Statement};
+use preprocessor::SourceLine;
+
+/// Peephole optimiser run on the instruction stream before the first pass.
+///
+/// Applies the following rewrites until none of them changes the program:
+///
+/// * redundant A-loads: `@X` when A is already known to hold `X`
+/// * dead A-loads: `@X` immediately followed by another A-load
+/// * dead D writes: `D=...` overwritten before D is read
+/// * redundant stores: `M=D` right after `D=M`, and `D=M` right after `M=D`
+/// * jumps to the next instruction: `@L`, `0;JMP` followed by `(L)`
+/// * jump chains: `@L1`, `0;JMP` where `L1` is itself `@L2`, `0;JMP`
+///
+/// Since the optimiser runs before the first pass, label addresses are
+/// computed on the optimised program. Returns the optimised lines and the
+/// number of words saved.
+pub fn optimize(lines: Vec<SourceLine>) -> (Vec<SourceLine>, usize) {
+	let mut statements = instruction::parse(&lines);
+	let before = code_size(&statements);
+
+	loop {
+		let changed = redundant_a_loads(&mut statements)
+			| dead_a_loads(&mut statements)
+			| dead_d_writes(&mut statements)
+			| redundant_stores(&mut statements)
+			| jumps_to_next(&mut statements)
+			| jump_chains(&mut statements);
+		if !changed {
+			break
+		}
+	}
+
+	let saved = before - code_size(&statements);
+	let lines = statements.into_iter().map(|statement| {
+		let mut line = statement.line;
+		line.text = statement.instruction.to_string();
+		line
+	}).collect();
+	(lines, saved)
+}
+
+fn code_size(statements: &[Statement]) -> usize {
+	statements.iter().filter(|s| s.instruction.is_code()).count()
+}
+
+/// Returns the index of the statement after `i`, unless it is a label.
+fn next_in_block(statements: &[Statement], i: usize) -> Option<usize> {
+	match statements.get(i + 1) {
+		Some(s) if s.instruction.is_code() => Some(i + 1),
+		_ => None,
+	}
+}
+
+fn is_d_assignment_of(instruction: &Instruction, dest: &str, comp: &str) -> bool {
+	match *instruction {
+		Instruction::C { dest: Some(ref d), comp: ref c, jump: None } => d == dest && c == comp,
+		_ => false,
+	}
+}
+
+fn redundant_a_loads(statements: &mut Vec<Statement>) -> bool {
+	let mut known: Option<String> = None;
+	let mut redundant = Vec::new();
+	for (i, statement) in statements.iter().enumerate() {
+		match statement.instruction {
+			Instruction::Label(_) => known = None,
+			Instruction::A(ref value) => {
+				if known.as_ref() == Some(value) {
+					redundant.push(i);
+				}
+				known = Some(value.clone());
+			},
+			ref c => {
+				if c.writes('A') {
+					known = None;
+				}
+			},
+		}
+	}
+	remove(statements, &redundant)
+}
+
+fn dead_a_loads(statements: &mut Vec<Statement>) -> bool {
+	let mut dead = Vec::new();
+	for i in 0..statements.len() {
+		if let Instruction::A(_) = statements[i].instruction {
+			if let Some(next) = next_in_block(statements, i) {
+				let next = &statements[next].instruction;
+				if next.writes('A') && !next.reads('A') && !next.reads('M') {
+					dead.push(i);
+				}
+			}
+		}
+	}
+	remove(statements, &dead)
+}
+
+fn dead_d_writes(statements: &mut Vec<Statement>) -> bool {
+	let mut dead = Vec::new();
+	for i in 0..statements.len() {
+		let writes_only_d = match statements[i].instruction {
+			Instruction::C { dest: Some(ref dest), jump: None, .. } => dest == "D",
+			_ => false,
+		};
+		if !writes_only_d {
+			continue
+		}
+
+		// A-loads don't touch D, so look past them for the next use of D
+		let mut j = i;
+		while let Some(next) = next_in_block(statements, j) {
+			let instruction = &statements[next].instruction;
+			if instruction.reads('D') {
+				break
+			}
+			if let Instruction::C { .. } = *instruction {
+				if instruction.writes('D') {
+					dead.push(i);
+				}
+				break
+			}
+			j = next;
+		}
+	}
+	remove(statements, &dead)
+}
+
+fn redundant_stores(statements: &mut Vec<Statement>) -> bool {
+	let mut redundant = Vec::new();
+	for i in 0..statements.len() {
+		if let Some(next) = next_in_block(statements, i) {
+			let (current, following) = (&statements[i].instruction, &statements[next].instruction);
+			let load_then_store = current.writes('D') && !current.writes('A') && current.jump().is_none()
+				&& match *current { Instruction::C { ref comp, .. } => comp == "M", _ => false }
+				&& is_d_assignment_of(following, "M", "D");
+			let store_then_load = is_d_assignment_of(current, "M", "D") && is_d_assignment_of(following, "D", "M");
+			if load_then_store || store_then_load {
+				redundant.push(next);
+			}
+		}
+	}
+	redundant.dedup();
+	remove(statements, &redundant)
+}
+
+/// Returns the labels that refer to the instruction after statement `i`.
+fn labels_after(statements: &[Statement], i: usize) -> Vec<&str> {
+	statements[i + 1..].iter().take_while(|s| !s.instruction.is_code()).filter_map(|s| match s.instruction {
+		Instruction::Label(ref label) => Some(label.as_str()),
+		_ => None,
+	}).collect()
+}
+
+/// Returns the target of a `@L`, `jump` pair starting at `i`.
+fn jump_target(statements: &[Statement], i: usize) -> Option<String> {
+	match (&statements[i].instruction, statements.get(i + 1).map(|s| &s.instruction)) {
+		(Instruction::A(ref target), Some(&Instruction::C { dest: None, jump: Some(_), .. })) => Some(target.clone()),
+		_ => None,
+	}
+}
+
+/// Returns `true` if the code after statement `i` doesn't depend on A before
+/// overwriting it.
+///
+/// A jump always arrives with A holding the target address, which the code
+/// at the target could rely on. Reading A or M, writing M and jumping all
+/// use A, so the scan stops at the first of those or at the first write to A.
+fn ignores_a_after(statements: &[Statement], i: usize) -> bool {
+	for s in statements[i + 1..].iter().filter(|s| s.instruction.is_code()) {
+		if s.instruction.reads('A') || s.instruction.reads('M') {
+			return false
+		}
+		if s.instruction.writes('A') {
+			return true
+		}
+	}
+	true
+}
+
+fn jumps_to_next(statements: &mut Vec<Statement>) -> bool {
+	let mut useless = Vec::new();
+	let mut i = 0;
+	while i + 1 < statements.len() {
+		if let Some(target) = jump_target(statements, i) {
+			if labels_after(statements, i + 1).contains(&target.as_str()) && ignores_a_after(statements, i + 1) {
+				useless.push(i);
+				useless.push(i + 1);
+				i += 2;
+				continue
+			}
+		}
+		i += 1;
+	}
+	remove(statements, &useless)
+}
+
+fn jump_chains(statements: &mut [Statement]) -> bool {
+	// where each label leads when it is the start of an unconditional jump
+	let mut forwards: HashMap<String, String> = HashMap::new();
+	for i in 0..statements.len() {
+		if let Instruction::Label(ref label) = statements[i].instruction {
+			let start = match statements[i..].iter().position(|s| s.instruction.is_code()) {
+				Some(offset) => i + offset,
+				None => continue,
+			};
+			if let Some(target) = jump_target(statements, start) {
+				if statements[start + 1].instruction.is_unconditional_jump() && target != *label {
+					forwards.insert(label.clone(), target);
+				}
+			}
+		}
+	}
+
+	let mut changed = false;
+	for i in 0..statements.len() {
+		let target = match jump_target(statements, i) {
+			Some(target) => target,
+			None => continue,
+		};
+		let mut visited = HashSet::new();
+		let mut last = target.clone();
+		while let Some(next) = forwards.get(&last) {
+			if !visited.insert(last.clone()) {
+				break // a loop of jumps never settles, leave it alone
+			}
+			last = next.clone();
+		}
+		if last != target && !visited.contains(&last) {
+			statements[i].instruction = Instruction::A(last);
+			changed = true;
+		}
+	}
+	changed
+}
+
+/// Removes the statements at the given (sorted) indexes.
+fn remove(statements: &mut Vec<Statement>, indexes: &[usize]) -> bool {
+	for &i in indexes.iter().rev() {
+		statements.remove(i);
+	}
+	!indexes.is_empty()
+}
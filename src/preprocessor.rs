@@ -0,0 +1,416 @@
+use std::collections::{HashMap, HashSet};
+
+/// A line of assembly source along with the line number it came from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceLine {
+	pub line: usize,
+	pub text: String,
+	/// Source text of the pseudo-instruction this line was expanded from.
+	pub origin: Option<String>,
+}
+
+impl SourceLine {
+	pub fn new(line: usize, text: &str) -> SourceLine {
+		SourceLine {
+			line,
+			text: text.to_string(),
+			origin: None,
+		}
+	}
+
+	/// Creates a line generated by expanding the pseudo-instruction `origin`.
+	pub fn expanded(line: usize, text: &str, origin: &str) -> SourceLine {
+		SourceLine {
+			line,
+			text: text.to_string(),
+			origin: Some(origin.to_string()),
+		}
+	}
+}
+
+/// Evaluates conditional assembly directives before the first pass.
+///
+/// Supported directives:
+///
+/// * `.define NAME value`
+/// * `.if expr`, `.elif expr`, `.else`, `.endif`
+/// * `.ifdef NAME`, `.ifndef NAME`
+///
+/// The linker directives `.export NAME` and `.import NAME` are passed through
+/// to the assembler like ordinary lines.
+///
+/// Lines excluded by a directive never reach the parser, so they neither
+/// take up ROM addresses nor define labels.
+///
+/// Constants defined with `define`, as on the command line, take precedence
+/// over a `.define` of the same name in the source.
+pub struct Preprocessor {
+	defines: HashMap<String, i32>,
+	/// Constants defined with `define`, which `.define` leaves alone.
+	overrides: HashSet<String>,
+}
+
+/// State of one `.if` ... `.endif` block.
+struct Conditional {
+	/// Whether the enclosing block is emitting lines.
+	parent_active: bool,
+	/// Whether the current branch is emitting lines.
+	active: bool,
+	/// Whether one of the branches has already been taken.
+	taken: bool,
+	seen_else: bool,
+	line: usize,
+}
+
+impl Preprocessor {
+	pub fn new() -> Preprocessor {
+		Preprocessor {
+			defines: HashMap::new(),
+			overrides: HashSet::new(),
+		}
+	}
+
+	/// Defines the constant `name`, e.g. from `-D NAME=value` on the command line.
+	pub fn define(&mut self, name: &str, value: i32) {
+		self.defines.insert(name.to_string(), value);
+		self.overrides.insert(name.to_string());
+	}
+
+	/// Returns the value of the constant `name`, if defined.
+	pub fn value(&self, name: &str) -> Option<i32> {
+		self.defines.get(name).cloned()
+	}
+
+	/// Runs the directives in `source` and returns the lines that remain.
+	pub fn process(&mut self, source: &str) -> Result<Vec<SourceLine>, String> {
+		let mut output = Vec::new();
+		let mut stack: Vec<Conditional> = Vec::new();
+
+		for (index, raw) in source.lines().enumerate() {
+			let line = index + 1;
+			let content = strip_comment(raw);
+			let active = stack.last().is_none_or(|c| c.active);
+
+			if !content.starts_with('.') {
+				if active {
+					output.push(SourceLine::new(line, raw));
+				}
+				continue
+			}
+
+			let (directive, argument) = match content.find(char::is_whitespace) {
+				Some(i) => (&content[..i], content[i..].trim()),
+				None => (content, ""),
+			};
+
+			match directive {
+				".export" | ".import" => {
+					if active {
+						output.push(SourceLine::new(line, raw));
+					}
+				},
+				".define" => {
+					if active {
+						self.process_define(argument, line)?;
+					}
+				},
+				".if" | ".ifdef" | ".ifndef" => {
+					let condition = match active {
+						true => self.condition(directive, argument, line)?,
+						false => false,
+					};
+					stack.push(Conditional {
+						parent_active: active,
+						active: condition,
+						taken: condition,
+						seen_else: false,
+						line,
+					});
+				},
+				".elif" => {
+					let (parent_active, taken) = match stack.last() {
+						Some(c) if c.seen_else => return Err(format!("line {}: .elif after .else", line)),
+						Some(c) => (c.parent_active, c.taken),
+						None => return Err(format!("line {}: .elif without .if", line)),
+					};
+					let condition = match parent_active && !taken {
+						true => self.condition(".if", argument, line)?,
+						false => false,
+					};
+					let current = stack.last_mut().unwrap();
+					current.active = condition;
+					current.taken |= condition;
+				},
+				".else" => {
+					match stack.last_mut() {
+						Some(c) if c.seen_else => return Err(format!("line {}: duplicate .else", line)),
+						Some(c) => {
+							c.active = c.parent_active && !c.taken;
+							c.taken = true;
+							c.seen_else = true;
+						},
+						None => return Err(format!("line {}: .else without .if", line)),
+					}
+				},
+				".endif" => {
+					if stack.pop().is_none() {
+						return Err(format!("line {}: .endif without .if", line));
+					}
+				},
+				_ => return Err(format!("line {}: unknown directive {}", line, directive)),
+			}
+		}
+
+		match stack.last() {
+			Some(c) => Err(format!("line {}: .if without .endif", c.line)),
+			None => Ok(output),
+		}
+	}
+
+	/// Handles `.define NAME value`. The value defaults to 1 when omitted.
+	fn process_define(&mut self, argument: &str, line: usize) -> Result<(), String> {
+		let mut parts = argument.splitn(2, char::is_whitespace);
+		let name = parts.next().unwrap_or("");
+		if !is_identifier(name) {
+			return Err(format!("line {}: invalid constant name '{}'", line, name));
+		}
+		let value = match parts.next().map(|s| s.trim()) {
+			Some(expr) if !expr.is_empty() => self.evaluate(expr).map_err(|e| format!("line {}: {}", line, e))?,
+			_ => 1,
+		};
+		if !self.overrides.contains(name) {
+			self.defines.insert(name.to_string(), value);
+		}
+		Ok(())
+	}
+
+	/// Evaluates the condition of an `.if`, `.ifdef` or `.ifndef` directive.
+	fn condition(&self, directive: &str, argument: &str, line: usize) -> Result<bool, String> {
+		match directive {
+			".ifdef" | ".ifndef" => {
+				if !is_identifier(argument) {
+					return Err(format!("line {}: invalid constant name '{}'", line, argument));
+				}
+				Ok(self.defines.contains_key(argument) == (directive == ".ifdef"))
+			},
+			_ => {
+				let value = self.evaluate(argument).map_err(|e| format!("line {}: {}", line, e))?;
+				Ok(value != 0)
+			},
+		}
+	}
+
+	/// Evaluates a constant expression.
+	///
+	/// Supports decimal numbers, constant names, `defined(NAME)`, parentheses,
+	/// `!`, unary `-`, `+`, `-`, comparisons, `&&` and `||`.
+	pub fn evaluate(&self, expr: &str) -> Result<i32, String> {
+		let tokens = tokenize(expr)?;
+		let mut parser = ExprParser {
+			tokens: &tokens,
+			position: 0,
+			defines: &self.defines,
+		};
+		let value = parser.or()?;
+		match parser.peek() {
+			None => Ok(value),
+			Some(token) => Err(format!("unexpected '{}' in expression", token)),
+		}
+	}
+}
+
+impl Default for Preprocessor {
+	fn default() -> Preprocessor {
+		Preprocessor::new()
+	}
+}
+
+/// Parses a define given outside the source, `NAME=value` or just `NAME`,
+/// which defines it as 1.
+pub fn parse_define(define: &str) -> Result<(String, i32), String> {
+	let (name, value) = match define.split_once('=') {
+		Some((name, value)) => match value.trim().parse::<i32>() {
+			Ok(value) => (name.trim(), value),
+			Err(_) => return Err(format!("invalid value for {}: {}", name.trim(), value)),
+		},
+		None => (define.trim(), 1),
+	};
+	match is_identifier(name) {
+		true => Ok((name.to_string(), value)),
+		false => Err(format!("invalid constant name '{}'", name)),
+	}
+}
+
+fn strip_comment(line: &str) -> &str {
+	match line.find("//") {
+		Some(i) => line[..i].trim(),
+		None => line.trim(),
+	}
+}
+
+fn is_identifier(name: &str) -> bool {
+	let mut chars = name.chars();
+	match chars.next() {
+		Some(c) if c.is_alphabetic() || c == '_' => chars.all(|c| c.is_alphanumeric() || c == '_'),
+		_ => false,
+	}
+}
+
+fn tokenize(expr: &str) -> Result<Vec<String>, String> {
+	let chars: Vec<char> = expr.chars().collect();
+	let mut tokens = Vec::new();
+	let mut i = 0;
+	while i < chars.len() {
+		let c = chars[i];
+		if c.is_whitespace() {
+			i += 1;
+		} else if c.is_alphanumeric() || c == '_' {
+			let start = i;
+			while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+				i += 1;
+			}
+			tokens.push(chars[start..i].iter().collect());
+		} else {
+			let pair: String = chars[i..chars.len().min(i + 2)].iter().collect();
+			match pair.as_str() {
+				"==" | "!=" | "<=" | ">=" | "&&" | "||" => {
+					tokens.push(pair);
+					i += 2;
+				},
+				_ => match c {
+					'(' | ')' | '!' | '<' | '>' | '+' | '-' => {
+						tokens.push(c.to_string());
+						i += 1;
+					},
+					_ => return Err(format!("unexpected '{}' in expression", c)),
+				},
+			}
+		}
+	}
+	Ok(tokens)
+}
+
+/// Recursive descent parser over the tokens of a constant expression.
+struct ExprParser<'a> {
+	tokens: &'a [String],
+	position: usize,
+	defines: &'a HashMap<String, i32>,
+}
+
+impl<'a> ExprParser<'a> {
+	fn peek(&self) -> Option<&'a str> {
+		self.tokens.get(self.position).map(|t| t.as_str())
+	}
+
+	fn next(&mut self) -> Option<&'a str> {
+		let token = self.peek();
+		self.position += 1;
+		token
+	}
+
+	fn expect(&mut self, expected: &str) -> Result<(), String> {
+		match self.next() {
+			Some(token) if token == expected => Ok(()),
+			Some(token) => Err(format!("expected '{}' but found '{}'", expected, token)),
+			None => Err(format!("expected '{}' at end of expression", expected)),
+		}
+	}
+
+	fn or(&mut self) -> Result<i32, String> {
+		let mut value = self.and()?;
+		while self.peek() == Some("||") {
+			self.next();
+			let rhs = self.and()?;
+			value = (value != 0 || rhs != 0) as i32;
+		}
+		Ok(value)
+	}
+
+	fn and(&mut self) -> Result<i32, String> {
+		let mut value = self.comparison()?;
+		while self.peek() == Some("&&") {
+			self.next();
+			let rhs = self.comparison()?;
+			value = (value != 0 && rhs != 0) as i32;
+		}
+		Ok(value)
+	}
+
+	fn comparison(&mut self) -> Result<i32, String> {
+		let lhs = self.sum()?;
+		let op = match self.peek() {
+			Some(op @ "==") | Some(op @ "!=") | Some(op @ "<") |
+			Some(op @ "<=") | Some(op @ ">") | Some(op @ ">=") => op,
+			_ => return Ok(lhs),
+		};
+		self.next();
+		let rhs = self.sum()?;
+		let result = match op {
+			"==" => lhs == rhs,
+			"!=" => lhs != rhs,
+			"<" => lhs < rhs,
+			"<=" => lhs <= rhs,
+			">" => lhs > rhs,
+			_ => lhs >= rhs,
+		};
+		Ok(result as i32)
+	}
+
+	fn sum(&mut self) -> Result<i32, String> {
+		let mut value = self.unary()?;
+		loop {
+			match self.peek() {
+				Some("+") => {
+					self.next();
+					value = value.wrapping_add(self.unary()?);
+				},
+				Some("-") => {
+					self.next();
+					value = value.wrapping_sub(self.unary()?);
+				},
+				_ => return Ok(value),
+			}
+		}
+	}
+
+	fn unary(&mut self) -> Result<i32, String> {
+		match self.peek() {
+			Some("!") => {
+				self.next();
+				Ok((self.unary()? == 0) as i32)
+			},
+			Some("-") => {
+				self.next();
+				Ok(self.unary()?.wrapping_neg())
+			},
+			_ => self.primary(),
+		}
+	}
+
+	fn primary(&mut self) -> Result<i32, String> {
+		match self.next() {
+			Some("(") => {
+				let value = self.or()?;
+				self.expect(")")?;
+				Ok(value)
+			},
+			Some("defined") => {
+				self.expect("(")?;
+				let name = self.next().unwrap_or("");
+				self.expect(")")?;
+				Ok(self.defines.contains_key(name) as i32)
+			},
+			Some(token) if token.chars().all(|c| c.is_ascii_digit()) => {
+				token.parse::<i32>().map_err(|_| format!("number out of range: {}", token))
+			},
+			Some(token) if is_identifier(token) => {
+				match self.defines.get(token) {
+					Some(value) => Ok(*value),
+					None => Err(format!("undefined constant '{}'", token)),
+				}
+			},
+			Some(token) => Err(format!("unexpected '{}' in expression", token)),
+			None => Err("unexpected end of expression".to_string()),
+		}
+	}
+}
@@ -0,0 +1,176 @@
+use std::fmt::Write;
+
+use cpu::Cpu;
+use map::MemoryMap;
+use program::Program;
+use sourcemap::SourceMap;
+
+/// How many cycles `hack profile` runs before stopping a program that doesn't halt.
+pub const DEFAULT_CYCLES: u64 = 10_000_000;
+
+/// How many of the busiest instructions the report lists.
+const HOT_SPOTS: usize = 10;
+
+/// Why a profiled run stopped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum End {
+	/// PC reached the loop the program ends with.
+	Halted,
+	/// PC went past the last instruction.
+	Ended,
+	/// The cycle limit was reached.
+	Limit,
+}
+
+/// Execution counts of every ROM address from a run on the emulated CPU.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+	pub counts: Vec<u64>,
+	pub cycles: u64,
+	pub end: End,
+}
+
+impl Profile {
+	/// Runs `program` on `cpu` until it halts, runs off the end of ROM or
+	/// `limit` cycles have passed, counting how often each instruction executes.
+	///
+	/// The `@X`, `0;JMP` loop a program halts in runs once, so it isn't
+	/// reported as never executed.
+	pub fn run(program: &Program, cpu: &mut Cpu, limit: u64) -> Profile {
+		let mut counts = vec![0; program.code.len()];
+		let mut cycles = 0;
+		let end = loop {
+			let pc = cpu.pc as usize;
+			if pc >= program.code.len() {
+				break End::Ended
+			}
+			if cycles >= limit {
+				break End::Limit
+			}
+			if program.is_halt(pc) {
+				counts[pc] += 1;
+				counts[pc + 1] += 1;
+				cycles += 2;
+				break End::Halted
+			}
+			counts[pc] += 1;
+			cycles += 1;
+			cpu.execute(program.code[pc]);
+		};
+		Profile { counts, cycles, end }
+	}
+
+	/// Returns the number of instructions that executed at least once.
+	pub fn covered(&self) -> usize {
+		self.counts.iter().filter(|&&c| c > 0).count()
+	}
+
+	/// Returns the cycles spent in each label region, in ROM order.
+	pub fn regions(&self, program: &Program) -> Vec<(String, u16, usize, u64)> {
+		let mut map = MemoryMap::new();
+		map.add_regions(None, &program.labels, 0, program.code.len());
+		map.regions.iter().map(|r| {
+			let start = r.start as usize;
+			let cycles = self.counts[start..start + r.size].iter().sum();
+			(r.name.clone(), r.start, r.size, cycles)
+		}).collect()
+	}
+
+	/// Describes where the cycles went: per region, the busiest instructions,
+	/// and the instructions that never executed, with the source lines
+	/// `source_map` gives them.
+	pub fn report(&self, program: &Program, source_map: &SourceMap, name: &str) -> String {
+		let mut text = String::new();
+		let end = match self.end {
+			End::Halted => "halted".to_string(),
+			End::Ended => "ran past the end of the program".to_string(),
+			End::Limit => format!("stopped after {} cycles without halting", self.cycles),
+		};
+		let _ = writeln!(text, "Profile of {}: {} cycles, {}", name, self.cycles, end);
+		let _ = writeln!(text, "Coverage: {} / {} instructions executed ({:.1}%)",
+			self.covered(), self.counts.len(), percentage(self.covered() as u64, self.counts.len() as u64));
+
+		let mut regions = self.regions(program);
+		regions.sort_by(|a, b| b.3.cmp(&a.3).then(a.1.cmp(&b.1)));
+		let _ = writeln!(text, "\nRegions");
+		let _ = writeln!(text, "  {:>5}  {:>5}  {:>10}  {:>6}  region", "start", "size", "cycles", "share");
+		for (region, start, size, cycles) in regions {
+			let _ = writeln!(text, "  {:05}  {:5}  {:10}  {:5.1}%  {}", start, size, cycles, percentage(cycles, self.cycles), region);
+		}
+
+		let mut hot: Vec<usize> = (0..self.counts.len()).filter(|&a| self.counts[a] > 0).collect();
+		hot.sort_by(|&a, &b| self.counts[b].cmp(&self.counts[a]).then(a.cmp(&b)));
+		let _ = writeln!(text, "\nHot spots");
+		let _ = writeln!(text, "  {:>5}  {:>5}  {:>10}  {:>6}  instruction", "ROM", "line", "cycles", "share");
+		for &address in hot.iter().take(HOT_SPOTS) {
+			let _ = writeln!(text, "  {:05}  {:5}  {:10}  {:5.1}%  {}", address, line(source_map, address), self.counts[address],
+				percentage(self.counts[address], self.cycles), program.statements[address].instruction);
+		}
+
+		let _ = writeln!(text, "\nNever executed");
+		let never = self.never_executed();
+		if never.is_empty() {
+			let _ = writeln!(text, "  nothing, every instruction ran");
+		}
+		for (first, last) in never {
+			let (first_line, last_line) = (line(source_map, first), line(source_map, last));
+			let lines = match first_line == last_line {
+				true => format!("line {}", first_line),
+				false => format!("lines {}-{}", first_line, last_line),
+			};
+			let _ = match first == last {
+				true => writeln!(text, "  ROM {:05}        {}", first, lines),
+				false => writeln!(text, "  ROM {:05}-{:05}  {}", first, last, lines),
+			};
+		}
+		text
+	}
+
+	/// Returns the cycles per region and source line in the collapsed stack
+	/// format flamegraph tools read, e.g. `Pong;LOOP;line 7 1234`.
+	///
+	/// The Hack CPU has no call stack, so each stack is the program, the
+	/// label region and the source line.
+	pub fn collapsed(&self, program: &Program, source_map: &SourceMap, name: &str) -> String {
+		let mut text = String::new();
+		for (region, start, size, _) in self.regions(program) {
+			let start = start as usize;
+			let mut lines: Vec<(usize, u64)> = Vec::new();
+			for address in start..start + size {
+				let line = line(source_map, address);
+				match lines.last_mut() {
+					Some(last) if last.0 == line => last.1 += self.counts[address],
+					_ => lines.push((line, self.counts[address])),
+				}
+			}
+			for (line, cycles) in lines.into_iter().filter(|l| l.1 > 0) {
+				let _ = writeln!(text, "{};{};line {} {}", name, region, line, cycles);
+			}
+		}
+		text
+	}
+
+	/// Returns the runs of consecutive ROM addresses that never executed.
+	fn never_executed(&self) -> Vec<(usize, usize)> {
+		let mut runs: Vec<(usize, usize)> = Vec::new();
+		for address in (0..self.counts.len()).filter(|&a| self.counts[a] == 0) {
+			match runs.last_mut() {
+				Some(run) if run.1 + 1 == address => run.1 = address,
+				_ => runs.push((address, address)),
+			}
+		}
+		runs
+	}
+}
+
+/// Returns the source line of the instruction at `address`, or 0 if it isn't mapped.
+fn line(source_map: &SourceMap, address: usize) -> usize {
+	source_map.lookup(address as u16).map_or(0, |m| m.line)
+}
+
+fn percentage(part: u64, total: u64) -> f64 {
+	match total {
+		0 => 0.0,
+		_ => part as f64 * 100.0 / total as f64,
+	}
+}
@@ -0,0 +1,81 @@
+use assembler::{self, SymbolTable};
+use diagnostic::Diagnostic;
+use flow;
+use instruction::{self, Instruction, Statement};
+use preprocessor::{Preprocessor, SourceLine};
+use pseudo;
+
+/// A program assembled in memory, keeping the source line of every instruction.
+///
+/// Unlike the `Assembler`, which writes files and stops at the first error,
+/// this collects every error, so editors and debuggers can work on programs
+/// that are still being written.
+pub struct Program {
+	/// The machine code, one word per ROM address.
+	pub code: Vec<u16>,
+	/// The instruction at each ROM address.
+	pub statements: Vec<Statement>,
+	/// Each label and its ROM address, in source order.
+	pub labels: Vec<(String, u16)>,
+	/// Each variable and its RAM address, in order of allocation.
+	pub variables: Vec<(String, u16)>,
+}
+
+impl Program {
+	/// Preprocesses, expands and assembles the source of a program.
+	///
+	/// Linker directives are ignored, so the symbols of a module are
+	/// resolved as if it were a whole program.
+	pub fn from_source(source: &str, pseudo_instructions: bool) -> Result<Program, Vec<Diagnostic>> {
+		let mut lines = Preprocessor::new().process(source).map_err(|e| vec![Diagnostic::from_error(&e)])?;
+		if pseudo_instructions {
+			lines = pseudo::expand(lines).map_err(|e| vec![Diagnostic::from_error(&e)])?;
+		}
+		let (lines, _, _) = assembler::linker_directives(lines);
+		Program::assemble(&lines)
+	}
+
+	/// Assembles preprocessed source lines.
+	pub fn assemble(lines: &[SourceLine]) -> Result<Program, Vec<Diagnostic>> {
+		Program::assemble_extended(lines, false)
+	}
+
+	/// Assembles preprocessed source lines, accepting the shift instructions
+	/// of the extended instruction set when `extended` is set.
+	pub fn assemble_extended(lines: &[SourceLine], extended: bool) -> Result<Program, Vec<Diagnostic>> {
+		let encoding = assembler::encode(instruction::parse(lines), extended, None)?;
+		Ok(Program {
+			code: encoding.code.into_iter().map(|(word, _)| word).collect(),
+			statements: encoding.statements,
+			labels: encoding.labels,
+			variables: encoding.variables,
+		})
+	}
+
+	/// Returns the ROM addresses of the instructions on source line `line`.
+	pub fn addresses(&self, line: usize) -> Vec<usize> {
+		(0..self.statements.len()).filter(|&a| self.statements[a].line.line == line).collect()
+	}
+
+	/// Returns `true` when `address` starts an `@X`, `0;JMP` loop jumping to
+	/// itself, which is how programs halt.
+	pub fn is_halt(&self, address: usize) -> bool {
+		match (self.statements.get(address), self.statements.get(address + 1)) {
+			(Some(load), Some(jump)) => {
+				matches!(load.instruction, Instruction::A(_)) && self.code[address] as usize == address && flow::always_jumps(&jump.instruction)
+			},
+			_ => false,
+		}
+	}
+
+	/// Returns the address of a label, variable or predefined symbol.
+	pub fn symbol(&self, name: &str) -> Option<u16> {
+		let mut predefined = SymbolTable::new();
+		predefined.initialise();
+		self.labels.iter().chain(self.variables.iter()).find(|s| s.0 == name).map(|s| s.1)
+			.or_else(|| match predefined.contains(name) {
+				true => Some(predefined.get_address(name)),
+				false => None,
+			})
+	}
+}
@@ -0,0 +1,107 @@
+use preprocessor::SourceLine;
+
+/// Expands pseudo-instructions into canonical Hack instructions.
+///
+/// | Pseudo-instruction     | Expansion                        |
+/// |------------------------|----------------------------------|
+/// | `LOAD D, x`            | `@x`, `D=A`                      |
+/// | `LOAD A, x`            | `@x`                             |
+/// | `GOTO label`           | `@label`, `0;JMP`                |
+/// | `IF D>0 GOTO label`    | `@label`, `D;JGT`                |
+/// | `PUSH D`               | `@SP`, `AM=M+1`, `A=A-1`, `M=D`  |
+/// | `POP D`                | `@SP`, `AM=M-1`, `D=M`           |
+/// | `INC r`, `DEC r`       | `r=r+1`, `r=r-1` (r is A, D or M)|
+///
+/// `IF` accepts the conditions `D>0`, `D=0`, `D>=0`, `D<0`, `D!=0` and `D<=0`.
+///
+/// Ordinary instructions are passed through unchanged. The expanded lines keep
+/// the line number of the pseudo-instruction and remember its text.
+pub fn expand(lines: Vec<SourceLine>) -> Result<Vec<SourceLine>, String> {
+	let mut output = Vec::new();
+	for line in lines {
+		match expand_line(&line.text).map_err(|e| format!("line {}: {}", line.line, e))? {
+			Some(instructions) => {
+				for instruction in instructions {
+					output.push(SourceLine::expanded(line.line, &instruction, &line.text));
+				}
+			},
+			None => output.push(line),
+		}
+	}
+	Ok(output)
+}
+
+/// Returns the expansion of `text`, or `None` if it isn't a pseudo-instruction.
+fn expand_line(text: &str) -> Result<Option<Vec<String>>, String> {
+	let content = match text.find("//") {
+		Some(i) => text[..i].trim(),
+		None => text.trim(),
+	};
+	let (mnemonic, operands) = match content.find(char::is_whitespace) {
+		Some(i) => (&content[..i], content[i..].trim()),
+		None => return Ok(None),
+	};
+
+	let expansion = match mnemonic {
+		"LOAD" => {
+			let v: Vec<&str> = operands.splitn(2, ',').map(|s| s.trim()).collect();
+			if v.len() != 2 || v[1].is_empty() {
+				return Err(format!("expected LOAD register, value but found '{}'", content));
+			}
+			let value = format!("@{}", v[1]);
+			match v[0] {
+				"D" => vec![value, "D=A".to_string()],
+				"A" => vec![value],
+				register => return Err(format!("LOAD can't load into {}", register)),
+			}
+		},
+		"GOTO" => vec![format!("@{}", target(operands)?), "0;JMP".to_string()],
+		"IF" => {
+			let v: Vec<&str> = operands.split_whitespace().collect();
+			if v.len() != 3 || v[1] != "GOTO" {
+				return Err(format!("expected IF condition GOTO label but found '{}'", content));
+			}
+			let jump = match v[0] {
+				"D>0" => "JGT",
+				"D=0" | "D==0" => "JEQ",
+				"D>=0" => "JGE",
+				"D<0" => "JLT",
+				"D!=0" | "D<>0" => "JNE",
+				"D<=0" => "JLE",
+				condition => return Err(format!("unknown condition {}", condition)),
+			};
+			vec![format!("@{}", target(v[2])?), format!("D;{}", jump)]
+		},
+		"PUSH" => {
+			register_d(mnemonic, operands)?;
+			vec!["@SP".to_string(), "AM=M+1".to_string(), "A=A-1".to_string(), "M=D".to_string()]
+		},
+		"POP" => {
+			register_d(mnemonic, operands)?;
+			vec!["@SP".to_string(), "AM=M-1".to_string(), "D=M".to_string()]
+		},
+		"INC" | "DEC" => {
+			let op = if mnemonic == "INC" { '+' } else { '-' };
+			match operands {
+				"A" | "D" | "M" => vec![format!("{0}={0}{1}1", operands, op)],
+				register => return Err(format!("{} can't update {}", mnemonic, register)),
+			}
+		},
+		_ => return Ok(None),
+	};
+	Ok(Some(expansion))
+}
+
+fn target(label: &str) -> Result<&str, String> {
+	match label.is_empty() || label.contains(char::is_whitespace) {
+		true => Err(format!("invalid jump target '{}'", label)),
+		false => Ok(label),
+	}
+}
+
+fn register_d(mnemonic: &str, operands: &str) -> Result<(), String> {
+	match operands {
+		"D" => Ok(()),
+		register => Err(format!("{} only supports the D register, not {}", mnemonic, register)),
+	}
+}
@@ -0,0 +1,142 @@
+use assembler::{self, SymbolTable};
+use cpu::Cpu;
+use instruction::Instruction;
+use map::VARIABLES_START;
+use preprocessor::SourceLine;
+use pseudo;
+
+const HELP: &str = "Type an instruction to see its encoding, e.g. D=D+M or @i.\n\
+	:run         toggle executing instructions on the emulated CPU\n\
+	:set R VALUE set A, D or M (the RAM word A points at)\n\
+	:reset       clear the registers, RAM and symbols\n\
+	:quit        leave";
+
+/// Read-eval-print loop showing how instructions are encoded, and optionally
+/// executing them.
+///
+/// Unknown symbols become variables from RAM address 16 on, and labels
+/// stand for the PC at the point they are typed.
+pub struct Repl {
+	pub cpu: Cpu,
+	run: bool,
+	symbol_table: SymbolTable,
+	next_variable: u16,
+}
+
+impl Repl {
+	pub fn new() -> Repl {
+		let mut symbol_table = SymbolTable::new();
+		symbol_table.initialise();
+		Repl {
+			cpu: Cpu::new(),
+			run: false,
+			symbol_table,
+			next_variable: VARIABLES_START,
+		}
+	}
+
+	/// Enables executing each instruction after showing its encoding.
+	pub fn set_run(&mut self, enabled: bool) {
+		self.run = enabled;
+	}
+
+	/// Evaluates a line of input, returning the text to show.
+	pub fn eval(&mut self, line: &str) -> String {
+		let line = line.trim();
+		match line.split_whitespace().next() {
+			Some(":help") => HELP.to_string(),
+			Some(":run") => {
+				self.run = !self.run;
+				format!("execution {}\n{}", if self.run { "on" } else { "off" }, self.state())
+			},
+			Some(":reset") => {
+				*self = Repl { run: self.run, ..Repl::new() };
+				self.state()
+			},
+			Some(":set") => self.set(line),
+			Some(command) if command.starts_with(':') => format!("error: unknown command {}, try :help", command),
+			_ => match self.instructions(line) {
+				Ok(output) => output,
+				Err(why) => format!("error: {}", why),
+			},
+		}
+	}
+
+	/// Returns the registers, along with M and PC.
+	pub fn state(&self) -> String {
+		format!("A={} D={} M={} PC={}", self.cpu.a as i16, self.cpu.d as i16, self.cpu.m() as i16, self.cpu.pc)
+	}
+
+	fn set(&mut self, line: &str) -> String {
+		let operands: Vec<&str> = line.split_whitespace().skip(1).collect();
+		let value = match operands.get(1).map(|v| v.parse::<i32>()) {
+			Some(Ok(value)) if (-32768..=65535).contains(&value) => value as u16,
+			_ => return "error: expected :set A|D|M VALUE".to_string(),
+		};
+		match operands[0] {
+			"A" => self.cpu.a = value,
+			"D" => self.cpu.d = value,
+			"M" => {
+				let address = (self.cpu.a & 0x7fff) as usize;
+				self.cpu.ram[address] = value;
+			},
+			register => return format!("error: can't set {}", register),
+		}
+		self.state()
+	}
+
+	fn instructions(&mut self, line: &str) -> Result<String, String> {
+		let expanded = pseudo::expand(vec![SourceLine::new(1, line)]).map_err(|e| e.trim_start_matches("line 1: ").to_string())?;
+
+		let mut output = Vec::new();
+		for line in expanded {
+			let instruction = match Instruction::parse(&line.text) {
+				Some(instruction) => instruction,
+				None => continue,
+			};
+			instruction.validate()?;
+			let (word, fields) = match instruction {
+				Instruction::Label(ref label) => {
+					self.symbol_table.add_entry(label.clone(), self.cpu.pc);
+					output.push(format!("{:<10} = ROM {}", instruction, self.cpu.pc));
+					continue
+				},
+				Instruction::A(ref value) => {
+					let word = self.resolve(value);
+					(word, format!("0 {:015b}", word))
+				},
+				Instruction::C { ref dest, ref comp, ref jump } => {
+					let (comp_bits, dest_bits, jump_bits) = assembler::c_fields(dest.as_deref(), comp, jump.as_deref());
+					let word = assembler::encode_c(dest.as_deref(), comp, jump.as_deref());
+					(word, format!("111 {} {} {} {}", &comp_bits[..1], &comp_bits[1..], dest_bits, jump_bits))
+				},
+			};
+			output.push(format!("{:<10} {:<22} 0x{:04x}", instruction.to_string(), fields, word));
+			if self.run {
+				self.cpu.execute(word);
+			}
+		}
+		if self.run && !output.is_empty() {
+			output.push(self.state());
+		}
+		Ok(output.join("\n"))
+	}
+
+	/// Returns the value of `@value`, allocating a variable for unknown symbols.
+	fn resolve(&mut self, value: &str) -> u16 {
+		if let Ok(number) = value.parse::<u16>() {
+			return number
+		}
+		if !self.symbol_table.contains(value) {
+			self.symbol_table.add_entry(value.to_string(), self.next_variable);
+			self.next_variable += 1;
+		}
+		self.symbol_table.get_address(value)
+	}
+}
+
+impl Default for Repl {
+	fn default() -> Repl {
+		Repl::new()
+	}
+}
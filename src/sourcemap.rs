@@ -0,0 +1,135 @@
+use std::fmt;
+
+use json::Json;
+use preprocessor::SourceLine;
+use program::Program;
+
+/// Version of the source map format, written to every map.
+pub const VERSION: i64 = 1;
+
+/// Where the instruction at a ROM address came from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mapping {
+	pub address: u16,
+	pub file: String,
+	pub line: usize,
+	/// Column of the first character of the instruction, counting from 1.
+	pub column: usize,
+	/// The pseudo-instruction the instruction was expanded from, if any.
+	pub expanded_from: Option<String>,
+}
+
+/// Maps ROM addresses back to the source locations that produced them.
+///
+/// Written as JSON so emulators, debuggers and profilers can read it:
+///
+/// ```text
+/// {"version":1,"file":"Prog.asm","mappings":[
+///   {"address":0,"file":"Prog.asm","line":2,"column":5},
+///   {"address":1,"file":"Prog.asm","line":4,"column":5,"expandedFrom":"GOTO LOOP"}, ...]}
+/// ```
+///
+/// Every instruction expanded from a pseudo-instruction maps to the line and
+/// column of the pseudo-instruction. Pseudo-instructions are the only
+/// expansions mapped: the assembler has no macros or include directive, so
+/// every mapping is in `file` and no mapping records a chain of expansions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceMap {
+	pub file: String,
+	/// Sorted by address, so `lookup` can binary search them.
+	pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+	pub fn new(file: &str) -> SourceMap {
+		SourceMap {
+			file: file.to_string(),
+			mappings: Vec::new(),
+		}
+	}
+
+	/// Builds the source map of a program assembled from `file`.
+	pub fn from_program(program: &Program, file: &str) -> SourceMap {
+		let mut map = SourceMap::new(file);
+		for (address, statement) in program.statements.iter().enumerate() {
+			map.push(address as u16, &statement.line);
+		}
+		map
+	}
+
+	/// Records that the instruction at `address` was assembled from `line`.
+	pub fn push(&mut self, address: u16, line: &SourceLine) {
+		let text = line.origin.as_ref().unwrap_or(&line.text);
+		let indent = text.len() - text.trim_start().len();
+		let index = self.mappings.partition_point(|m| m.address <= address);
+		self.mappings.insert(index, Mapping {
+			address,
+			file: self.file.clone(),
+			line: line.line,
+			column: text[..indent].chars().count() + 1,
+			expanded_from: line.origin.as_ref().map(|o| o.trim().to_string()),
+		});
+	}
+
+	/// Returns where the instruction at `address` came from.
+	pub fn lookup(&self, address: u16) -> Option<&Mapping> {
+		let index = self.mappings.partition_point(|m| m.address < address);
+		self.mappings.get(index).filter(|m| m.address == address)
+	}
+
+	/// Returns the ROM addresses of the instructions assembled from a source line.
+	pub fn addresses(&self, file: &str, line: usize) -> Vec<u16> {
+		self.mappings.iter().filter(|m| m.file == file && m.line == line).map(|m| m.address).collect()
+	}
+
+	/// Reads a source map written by `to_string`.
+	pub fn parse(text: &str) -> Result<SourceMap, String> {
+		let json = Json::parse(text)?;
+		match json.get("version").as_i64() {
+			Some(VERSION) => {},
+			Some(version) => return Err(format!("unsupported source map version {}", version)),
+			None => return Err("not a source map: missing version".to_string()),
+		}
+
+		let mut map = SourceMap::new(json.get("file").as_str().unwrap_or(""));
+		for (i, mapping) in json.get("mappings").as_array().iter().enumerate() {
+			let number = |key: &str| mapping.get(key).as_i64().filter(|&n| n >= 0).ok_or(format!("mapping {}: missing {}", i, key));
+			let address = number("address")?;
+			if address > u16::MAX as i64 {
+				return Err(format!("mapping {}: address {} is outside ROM", i, address));
+			}
+			map.mappings.push(Mapping {
+				address: address as u16,
+				file: mapping.get("file").as_str().unwrap_or(&map.file).to_string(),
+				line: number("line")? as usize,
+				column: number("column")? as usize,
+				expanded_from: mapping.get("expandedFrom").as_str().map(|o| o.to_string()),
+			});
+		}
+		map.mappings.sort_by_key(|m| m.address);
+		Ok(map)
+	}
+}
+
+impl fmt::Display for SourceMap {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let mappings: Vec<Json> = self.mappings.iter().map(|m| {
+			let mut members = vec![
+				("address", Json::from(m.address)),
+				("file", Json::from(m.file.as_str())),
+				("line", Json::from(m.line)),
+				("column", Json::from(m.column)),
+			];
+			if let Some(ref origin) = m.expanded_from {
+				members.push(("expandedFrom", Json::from(origin.as_str())));
+			}
+			Json::object(members)
+		}).collect();
+		let json = Json::object(vec![
+			("version", Json::from(VERSION)),
+			("file", Json::from(self.file.as_str())),
+			("mappings", Json::Array(mappings)),
+		]);
+		writeln!(f, "{}", json)
+	}
+}
@@ -0,0 +1,184 @@
+use std::fmt;
+
+use cpu::Cpu;
+use program::Program;
+
+/// How many cycles `hack trace` records unless told otherwise.
+pub const DEFAULT_CYCLES: u64 = 100_000;
+
+/// First line of every trace, naming the format and its version.
+const HEADER: &str = "hack-trace 1";
+
+/// One cycle of execution: the instruction at PC and what it left behind.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+	pub pc: u16,
+	pub instruction: u16,
+	/// A after the instruction.
+	pub a: u16,
+	/// D after the instruction.
+	pub d: u16,
+	/// The RAM address written and the value written to it.
+	pub write: Option<(u16, u16)>,
+}
+
+/// A record of every cycle of a run on the emulated CPU, along with the
+/// program's labels and variables so addresses can be named.
+///
+/// Written as text, one line per cycle with PC, the instruction, A and D,
+/// and the RAM word written if any, all in hex:
+///
+/// ```text
+/// hack-trace 1
+/// label LOOP 4
+/// variable i 16
+/// 0000 0010 0010 0000
+/// 0001 ea88 0010 0000 0010=0000
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+	pub labels: Vec<(String, u16)>,
+	pub variables: Vec<(String, u16)>,
+	pub steps: Vec<Step>,
+}
+
+impl Trace {
+	/// Runs `program` on `cpu` for up to `cycles` cycles, stopping early when
+	/// it halts or runs off the end of ROM.
+	pub fn record(program: &Program, cpu: &mut Cpu, cycles: u64) -> Trace {
+		let mut trace = Trace {
+			labels: program.labels.clone(),
+			variables: program.variables.clone(),
+			steps: Vec::new(),
+		};
+		while (trace.steps.len() as u64) < cycles {
+			let pc = cpu.pc as usize;
+			if pc >= program.code.len() || program.is_halt(pc) {
+				break
+			}
+			let instruction = program.code[pc];
+			let address = cpu.a & 0x7fff;
+			cpu.execute(instruction);
+			let write = match instruction & 0x8008 == 0x8008 {
+				true => Some((address, cpu.ram[address as usize])),
+				false => None,
+			};
+			trace.steps.push(Step { pc: pc as u16, instruction, a: cpu.a, d: cpu.d, write });
+		}
+		trace
+	}
+
+	/// Reads a trace written by `to_string`.
+	pub fn parse(text: &str) -> Result<Trace, String> {
+		let mut lines = text.lines().enumerate();
+		match lines.next() {
+			Some((_, HEADER)) => {},
+			_ => return Err(format!("not a trace: expected '{}' on the first line", HEADER)),
+		}
+
+		let mut trace = Trace::default();
+		for (i, line) in lines {
+			let fields: Vec<&str> = line.split_whitespace().collect();
+			let error = || format!("line {}: can't read '{}'", i + 1, line);
+			match fields.as_slice() {
+				["label", name, address] => trace.labels.push((name.to_string(), address.parse().map_err(|_| error())?)),
+				["variable", name, address] => trace.variables.push((name.to_string(), address.parse().map_err(|_| error())?)),
+				[pc, instruction, a, d, rest @ ..] if rest.len() <= 1 => {
+					let hex = |field: &str| u16::from_str_radix(field, 16).map_err(|_| error());
+					let write = match rest.first() {
+						Some(write) => {
+							let (address, value) = write.split_once('=').ok_or_else(error)?;
+							Some((hex(address)?, hex(value)?))
+						},
+						None => None,
+					};
+					trace.steps.push(Step { pc: hex(pc)?, instruction: hex(instruction)?, a: hex(a)?, d: hex(d)?, write });
+				},
+				[] => {},
+				_ => return Err(error()),
+			}
+		}
+		Ok(trace)
+	}
+
+	/// Names a ROM address after the label it follows, e.g. `6 (LOOP+2)`.
+	pub fn name_rom(&self, address: u16) -> String {
+		match self.labels.iter().filter(|l| l.1 <= address).max_by_key(|l| l.1) {
+			Some(&(ref label, start)) if start == address => format!("{} ({})", address, label),
+			Some(&(ref label, start)) => format!("{} ({}+{})", address, label, address - start),
+			None => address.to_string(),
+		}
+	}
+
+	/// Names a RAM address after its variable or predefined symbol, e.g. `RAM[16] (i)`.
+	pub fn name_ram(&self, address: u16) -> String {
+		let name = match self.variables.iter().find(|v| v.1 == address) {
+			Some(variable) => variable.0.clone(),
+			None => match address {
+				0..=15 => format!("R{}", address),
+				16384 => "SCREEN".to_string(),
+				16385..=24575 => format!("SCREEN+{}", address - 16384),
+				24576 => "KBD".to_string(),
+				_ => return format!("RAM[{}]", address),
+			},
+		};
+		format!("RAM[{}] ({})", address, name)
+	}
+
+	/// Describes a step with the names this trace gives addresses.
+	pub fn describe(&self, step: &Step) -> String {
+		let write = step.write.map(|(address, value)| format!(", {} = {}", self.name_ram(address), value as i16)).unwrap_or_default();
+		format!("PC={} instruction=0x{:04x} A={} D={}{}", self.name_rom(step.pc), step.instruction, step.a as i16, step.d as i16, write)
+	}
+}
+
+impl fmt::Display for Trace {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{}", HEADER)?;
+		for (name, address) in &self.labels {
+			writeln!(f, "label {} {}", name, address)?;
+		}
+		for (name, address) in &self.variables {
+			writeln!(f, "variable {} {}", name, address)?;
+		}
+		for step in &self.steps {
+			write!(f, "{:04x} {:04x} {:04x} {:04x}", step.pc, step.instruction, step.a, step.d)?;
+			match step.write {
+				Some((address, value)) => writeln!(f, " {:04x}={:04x}", address, value)?,
+				None => writeln!(f)?,
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Finds the first cycle where two traces differ, describing it, or
+/// returns `None` when they are the same.
+pub fn diff(a: &Trace, b: &Trace) -> Option<String> {
+	let cycle = match a.steps.iter().zip(&b.steps).position(|(x, y)| x != y) {
+		Some(cycle) => cycle,
+		None if a.steps.len() == b.steps.len() => return None,
+		None => {
+			let (longer, name) = if a.steps.len() > b.steps.len() { (a, "a") } else { (b, "b") };
+			let cycle = a.steps.len().min(b.steps.len());
+			return Some(format!("the traces match for {} cycles, then only {} continues:\n  {}: {}",
+				cycle, name, name, longer.describe(&longer.steps[cycle])))
+		},
+	};
+
+	let (x, y) = (&a.steps[cycle], &b.steps[cycle]);
+	let mut differences = Vec::new();
+	for (field, differs) in [("PC", x.pc != y.pc), ("instruction", x.instruction != y.instruction), ("A", x.a != y.a), ("D", x.d != y.d), ("RAM write", x.write != y.write)] {
+		if differs {
+			differences.push(field);
+		}
+	}
+
+	let mut text = format!("first difference at cycle {}, in {}\n", cycle, differences.join(", "));
+	text += &format!("  a: {}\n", a.describe(x));
+	text += &format!("  b: {}", b.describe(y));
+	if cycle > 0 {
+		text += &format!("\nlast matching cycle {}: {}", cycle - 1, a.describe(&a.steps[cycle - 1]));
+	}
+	Some(text)
+}
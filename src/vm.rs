@@ -0,0 +1,326 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use formatter::INDENT;
+
+/// Base address of the temp segment, R5 to R12.
+const TEMP: u16 = 5;
+
+/// Translates the stack-based VM language of nand2tetris projects 7 and 8
+/// into Hack assembly.
+///
+/// The output only uses the instructions of the book, so it assembles with
+/// `--strict` too. Following the book's conventions, `static i` in `Foo.vm`
+/// becomes the variable `Foo.i`, `label X` in function `f` becomes `f$X`,
+/// and the return address of the n-th call becomes `f$ret.n`. R13 and R14
+/// are scratch registers.
+pub struct Translator {
+	lines: Vec<String>,
+	file: String,
+	function: String,
+	labels: usize,
+	/// Functions defined so far.
+	functions: Vec<String>,
+	/// Functions called so far, with where they were called from.
+	calls: Vec<(String, String)>,
+}
+
+impl Translator {
+	pub fn new() -> Translator {
+		Translator {
+			lines: Vec::new(),
+			file: String::new(),
+			function: String::new(),
+			labels: 0,
+			functions: Vec::new(),
+			calls: Vec::new(),
+		}
+	}
+
+	/// Emits the bootstrap code, which sets SP to 256 and calls `Sys.init`.
+	pub fn bootstrap(&mut self) {
+		self.comment("bootstrap: SP = 256, call Sys.init");
+		self.code(&["@256", "D=A", "@SP", "M=D"]);
+		self.function = "Sys".to_string();
+		self.calls.push(("Sys.init".to_string(), "bootstrap".to_string()));
+		self.call("Sys.init", 0);
+	}
+
+	/// Translates the VM code of the file `file`, e.g. `Main.vm`.
+	///
+	/// Errors name the file and line.
+	pub fn translate(&mut self, file: &str, source: &str) -> Result<(), String> {
+		self.file = Path::new(file).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+		self.function = self.file.clone();
+		self.comment(file);
+		for (i, line) in source.lines().enumerate() {
+			let command = line.split("//").next().unwrap_or("").trim();
+			if command.is_empty() {
+				continue
+			}
+			self.comment(command);
+			self.command(command).map_err(|why| format!("{}: line {}: {}", file, i + 1, why))?;
+			if let Some(function) = command.strip_prefix("call ").and_then(|c| c.split_whitespace().next()) {
+				self.calls.push((function.to_string(), format!("{}: line {}", file, i + 1)));
+			}
+		}
+		Ok(())
+	}
+
+	/// Checks that every function called has been defined, as a call to a
+	/// missing function would silently assemble to a jump through a variable.
+	pub fn check_calls(&self) -> Result<(), String> {
+		match self.calls.iter().find(|c| !self.functions.contains(&c.0)) {
+			Some((function, location)) => Err(format!("{}: call to undefined function {}", location, function)),
+			None => Ok(()),
+		}
+	}
+
+	/// Returns the assembly translated so far.
+	pub fn output(&self) -> String {
+		self.lines.join("\n") + "\n"
+	}
+
+	fn command(&mut self, command: &str) -> Result<(), String> {
+		let words: Vec<&str> = command.split_whitespace().collect();
+		let operands = match words[0] {
+			"push" | "pop" | "function" | "call" => 2,
+			"label" | "goto" | "if-goto" => 1,
+			"add" | "sub" | "neg" | "eq" | "gt" | "lt" | "and" | "or" | "not" | "return" => 0,
+			other => return Err(format!("unknown command {}", other)),
+		};
+		if words.len() != operands + 1 {
+			return Err(format!("{} expects {} operand{} but found {}", words[0], operands, if operands == 1 { "" } else { "s" }, words.len() - 1))
+		}
+		let index = |n: usize| -> Result<u16, String> {
+			match words[n].parse::<u16>() {
+				Ok(index) if index <= 32767 => Ok(index),
+				_ => Err(format!("expected a number from 0 to 32767 but found '{}'", words[n])),
+			}
+		};
+		let name = |n: usize| -> Result<&str, String> {
+			match is_name(words[n]) {
+				true => Ok(words[n]),
+				false => Err(format!("invalid name '{}'", words[n])),
+			}
+		};
+
+		match words[0] {
+			"push" => self.push(name(1)?, index(2)?)?,
+			"pop" => self.pop(name(1)?, index(2)?)?,
+			"add" => self.binary("M=D+M"),
+			"sub" => self.binary("M=M-D"),
+			"and" => self.binary("M=D&M"),
+			"or" => self.binary("M=D|M"),
+			"neg" => self.code(&["@SP", "A=M-1", "M=-M"]),
+			"not" => self.code(&["@SP", "A=M-1", "M=!M"]),
+			"eq" => self.compare("JEQ"),
+			"gt" => self.compare("JGT"),
+			"lt" => self.compare("JLT"),
+			"label" => {
+				let label = format!("{}${}", self.function, name(1)?);
+				self.label(&label);
+			},
+			"goto" => {
+				let label = format!("@{}${}", self.function, name(1)?);
+				self.code(&[&label, "0;JMP"]);
+			},
+			"if-goto" => {
+				let label = format!("@{}${}", self.function, name(1)?);
+				self.code(&["@SP", "AM=M-1", "D=M", &label, "D;JNE"]);
+			},
+			"function" => {
+				let (function, locals) = (name(1)?.to_string(), index(2)?);
+				self.label(&function);
+				self.functions.push(function.clone());
+				for _ in 0..locals {
+					self.code(&["@SP", "A=M", "M=0", "@SP", "M=M+1"]);
+				}
+				self.function = function;
+			},
+			"call" => {
+				let (function, arguments) = (name(1)?.to_string(), index(2)?);
+				self.call(&function, arguments);
+			},
+			_ => self.ret(),
+		}
+		Ok(())
+	}
+
+	fn push(&mut self, segment: &str, index: u16) -> Result<(), String> {
+		match segment {
+			"constant" => self.code(&[&format!("@{}", index), "D=A"]),
+			"local" | "argument" | "this" | "that" => {
+				self.code(&[&format!("@{}", index), "D=A", base(segment), "A=D+M", "D=M"]);
+			},
+			_ => {
+				let address = self.fixed_address(segment, index)?;
+				self.code(&[&address, "D=M"]);
+			},
+		}
+		self.code(&["@SP", "A=M", "M=D", "@SP", "M=M+1"]);
+		Ok(())
+	}
+
+	fn pop(&mut self, segment: &str, index: u16) -> Result<(), String> {
+		match segment {
+			"constant" => return Err("can't pop to the constant segment".to_string()),
+			"local" | "argument" | "this" | "that" => {
+				self.code(&[&format!("@{}", index), "D=A", base(segment), "D=D+M", "@R13", "M=D"]);
+				self.code(&["@SP", "AM=M-1", "D=M", "@R13", "A=M", "M=D"]);
+			},
+			_ => {
+				let address = self.fixed_address(segment, index)?;
+				self.code(&["@SP", "AM=M-1", "D=M", &address, "M=D"]);
+			},
+		}
+		Ok(())
+	}
+
+	/// Returns the `@` instruction of a word in the temp, pointer or static segment.
+	fn fixed_address(&self, segment: &str, index: u16) -> Result<String, String> {
+		match segment {
+			"temp" if index < 8 => Ok(format!("@R{}", TEMP + index)),
+			"pointer" if index < 2 => Ok(["@THIS", "@THAT"][index as usize].to_string()),
+			"static" => Ok(format!("@{}.{}", self.file, index)),
+			"temp" | "pointer" => Err(format!("{} {} is out of range", segment, index)),
+			other => Err(format!("unknown segment {}", other)),
+		}
+	}
+
+	/// Pops y into D and applies `operation` to the x left on top of the stack.
+	fn binary(&mut self, operation: &str) {
+		self.code(&["@SP", "AM=M-1", "D=M", "A=A-1", operation]);
+	}
+
+	/// Replaces x and y with -1 (true) if x - y meets `jump`, 0 (false) otherwise.
+	///
+	/// x - y overflows when x and y have opposite signs, so then D is set to
+	/// 1 or -1 from the sign of x instead, with y kept in R13.
+	fn compare(&mut self, jump: &str) {
+		let done = self.unique_label("cmp");
+		let (negative, subtract, test) = (format!("{}.neg", done), format!("{}.sub", done), format!("{}.test", done));
+		self.code(&["@SP", "AM=M-1", "D=M", "@R13", "M=D", "@SP", "A=M-1", "D=M", &format!("@{}", negative), "D;JLT"]);
+		// x >= 0
+		self.code(&["@R13", "D=M", &format!("@{}", subtract), "D;JGE", "D=1", &format!("@{}", test), "0;JMP"]);
+		self.label(&negative);
+		self.code(&["@R13", "D=M", &format!("@{}", subtract), "D;JLT", "D=-1", &format!("@{}", test), "0;JMP"]);
+		self.label(&subtract);
+		self.code(&["@R13", "D=M", "@SP", "A=M-1", "D=M-D"]);
+		self.label(&test);
+		self.code(&["@SP", "A=M-1", "M=-1", &format!("@{}", done), &format!("D;{}", jump)]);
+		self.code(&["@SP", "A=M-1", "M=0"]);
+		self.label(&done);
+	}
+
+	fn call(&mut self, function: &str, arguments: u16) {
+		let ret = self.unique_label("ret");
+		self.code(&[&format!("@{}", ret), "D=A", "@SP", "A=M", "M=D", "@SP", "M=M+1"]);
+		for saved in ["@LCL", "@ARG", "@THIS", "@THAT"] {
+			self.code(&[saved, "D=M", "@SP", "A=M", "M=D", "@SP", "M=M+1"]);
+		}
+		// ARG = SP - 5 - arguments, LCL = SP
+		self.code(&["@SP", "D=M", &format!("@{}", arguments + 5), "D=D-A", "@ARG", "M=D"]);
+		self.code(&["@SP", "D=M", "@LCL", "M=D"]);
+		self.code(&[&format!("@{}", function), "0;JMP"]);
+		self.label(&ret);
+	}
+
+	fn ret(&mut self) {
+		// R13 = frame, R14 = return address, read before the return value overwrites it
+		self.code(&["@LCL", "D=M", "@R13", "M=D", "@5", "A=D-A", "D=M", "@R14", "M=D"]);
+		self.code(&["@SP", "AM=M-1", "D=M", "@ARG", "A=M", "M=D"]);
+		self.code(&["@ARG", "D=M+1", "@SP", "M=D"]);
+		for restored in ["@THAT", "@THIS", "@ARG", "@LCL"] {
+			self.code(&["@R13", "AM=M-1", "D=M", restored, "M=D"]);
+		}
+		self.code(&["@R14", "A=M", "0;JMP"]);
+	}
+
+	fn unique_label(&mut self, kind: &str) -> String {
+		self.labels += 1;
+		format!("{}${}.{}", self.function, kind, self.labels)
+	}
+
+	fn code(&mut self, instructions: &[&str]) {
+		self.lines.extend(instructions.iter().map(|i| format!("{}{}", INDENT, i)));
+	}
+
+	fn label(&mut self, label: &str) {
+		self.lines.push(format!("({})", label));
+	}
+
+	fn comment(&mut self, text: &str) {
+		self.lines.push(format!("{}// {}", INDENT, text));
+	}
+}
+
+impl Default for Translator {
+	fn default() -> Translator {
+		Translator::new()
+	}
+}
+
+/// Translates a `.vm` file, or every `.vm` file of a directory into one
+/// program, returning the path of the `.asm` file to write and its contents.
+///
+/// `Foo.vm` becomes `Foo.asm`, and directory `Prog` becomes `Prog/Prog.asm`.
+/// The program starts with the bootstrap code when it defines `Sys.init`.
+pub fn translate_path(path: &str) -> Result<(PathBuf, String), String> {
+	let path = Path::new(path);
+	let (files, output) = match path.is_dir() {
+		true => {
+			let entries = fs::read_dir(path).map_err(|why| format!("couldn't read {}: {}", path.display(), why))?;
+			let mut files: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == "vm")).collect();
+			files.sort();
+			let name = path.canonicalize().ok().and_then(|p| p.file_name().map(|n| n.to_os_string())).unwrap_or_default();
+			(files, path.join(name).with_extension("asm"))
+		},
+		false => (vec![path.to_path_buf()], path.with_extension("asm")),
+	};
+	if files.is_empty() {
+		return Err(format!("no .vm files in {}", path.display()))
+	}
+
+	let mut sources = Vec::new();
+	for file in &files {
+		let source = fs::read_to_string(file).map_err(|why| format!("couldn't read {}: {}", file.display(), why))?;
+		let name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+		sources.push((name, source));
+	}
+	Ok((output, translate_sources(&sources)?.output()))
+}
+
+/// Translates the named sources of `.vm` files into one program, with the
+/// bootstrap code when one of them defines `Sys.init`.
+///
+/// Calls to other files are left unchecked, so that a single file of a
+/// program translates on its own; use `check_calls` for a whole program.
+pub fn translate_sources(sources: &[(String, String)]) -> Result<Translator, String> {
+	let mut translator = Translator::new();
+	let defines_init = sources.iter().any(|(_, source)| source.lines().any(|l| {
+		l.split("//").next().unwrap_or("").split_whitespace().take(2).eq(["function", "Sys.init"])
+	}));
+	if defines_init {
+		translator.bootstrap();
+	}
+	for (name, source) in sources {
+		translator.translate(name, source)?;
+	}
+	Ok(translator)
+}
+
+fn base(segment: &str) -> &'static str {
+	match segment {
+		"local" => "@LCL",
+		"argument" => "@ARG",
+		"this" => "@THIS",
+		_ => "@THAT",
+	}
+}
+
+/// Returns `true` for VM names: letters, digits, `_`, `.`, `:` and `$`, not starting with a digit.
+fn is_name(name: &str) -> bool {
+	!name.starts_with(|c: char| c.is_ascii_digit())
+		&& name.chars().all(|c| c.is_ascii_alphanumeric() || "_.:$".contains(c))
+}
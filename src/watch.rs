@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Polls a directory tree for `.asm` files that were added or changed.
+///
+/// A file counts as changed when its modification time or size differs from
+/// the last poll, so edits within the file system's timestamp resolution are
+/// still noticed. There is no include directive, so a file never depends on
+/// another and only the changed files need reassembling.
+pub struct Watcher {
+	directory: PathBuf,
+	seen: HashMap<PathBuf, (SystemTime, u64)>,
+}
+
+impl Watcher {
+	pub fn new(directory: &str) -> Watcher {
+		Watcher {
+			directory: PathBuf::from(directory),
+			seen: HashMap::new(),
+		}
+	}
+
+	/// Returns the `.asm` files added or changed since the last call, sorted by path.
+	///
+	/// The first call returns every file.
+	pub fn changed(&mut self) -> Result<Vec<PathBuf>, String> {
+		let mut files = Vec::new();
+		find_sources(&self.directory, &mut files)?;
+
+		let mut changed = Vec::new();
+		let mut seen = HashMap::new();
+		for file in files {
+			let metadata = match fs::metadata(&file) {
+				Ok(metadata) => metadata,
+				Err(_) => continue, // deleted while we looked
+			};
+			let stamp = (metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH), metadata.len());
+			if self.seen.get(&file) != Some(&stamp) {
+				changed.push(file.clone());
+			}
+			seen.insert(file, stamp);
+		}
+		self.seen = seen;
+		changed.sort();
+		Ok(changed)
+	}
+}
+
+fn find_sources(directory: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+	let entries = fs::read_dir(directory).map_err(|why| format!("couldn't read {}: {}", directory.display(), why))?;
+	for entry in entries.flatten() {
+		let path = entry.path();
+		if path.is_dir() {
+			find_sources(&path, files)?;
+		} else if path.extension().is_some_and(|e| e == "asm") {
+			files.push(path);
+		}
+	}
+	Ok(())
+}
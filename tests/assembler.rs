@@ -1,6 +1,8 @@
 extern crate hack;
 use hack::assembler::Assembler;
 
+use std::env;
+use std::fs;
 use std::path::Path;
 use std::error::Error;
 use std::fs::OpenOptions;
@@ -24,6 +26,41 @@ fn translate_asm_file_to_hack_symbols() {
 	generic_asm_to_hack(filenames_with_symbol);
 }
 
+/// FEATURE
+/// As a user,
+/// I want the binary code of my programs to stay the same as the assembler changes.
+#[test]
+fn translate_asm_file_to_hack_as_before() {
+	// GIVEN a program using every comp, dest and jump, assembled by the first version of the assembler
+	let f_in = env::temp_dir().join("hack-encoding.asm");
+	let mut source = fs::read_to_string("tests/fixtures/Encoding.asm").unwrap();
+	fs::write(&f_in, &source).unwrap();
+
+	// WHEN I run the assembler on it
+	let mut assembler = Assembler::new(&f_in.to_string_lossy());
+	assembler.set_quiet(true);
+	assert_eq!(assembler.try_translate(), Ok(()));
+
+	// THEN I will get the same binary code
+	let f_out = f_in.with_extension("hack");
+	let expected = fs::read_to_string("tests/fixtures/Encoding.hack").unwrap();
+	assert_eq!(fs::read_to_string(&f_out).unwrap(), expected);
+
+	// AND the commutative comps may be spelt with their operands swapped
+	for (comp, alias) in [("D+A", "A+D"), ("D&A", "A&D"), ("D|A", "A|D"), ("D+M", "M+D"), ("D&M", "M&D"), ("D|M", "M|D")] {
+		source = source.replace(&format!("{};", comp), &format!("{};", alias));
+		assert!(source.contains(alias));
+	}
+	fs::write(&f_in, &source).unwrap();
+	let mut assembler = Assembler::new(&f_in.to_string_lossy());
+	assembler.set_quiet(true);
+	assert_eq!(assembler.try_translate(), Ok(()));
+	assert_eq!(fs::read_to_string(&f_out).unwrap(), expected);
+
+	fs::remove_file(&f_in).unwrap();
+	fs::remove_file(&f_out).unwrap();
+}
+
 fn generic_asm_to_hack(filenames_no_symbol: Vec<&str>) {
 	let dir = "06/";
 	let in_ext = ".asm";
@@ -51,6 +88,7 @@ fn generic_asm_to_hack(filenames_no_symbol: Vec<&str>) {
 	}
 }
 
+#[allow(deprecated)]
 fn load_file_content(filename: &str) -> String {
 	let path = Path::new(filename);
 	let display = path.display();
@@ -68,6 +106,7 @@ fn load_file_content(filename: &str) -> String {
 	s
 }
 
+#[allow(clippy::no_effect)]
 fn clean_up(filename: &str) {
 	match std::fs::remove_file(filename) {
 		Ok(_) => {"Clean up successful";},
@@ -0,0 +1,138 @@
+extern crate hack;
+use hack::build::{Builder, Stage};
+use hack::cpu::Cpu;
+use hack::profiler::{End, Profile};
+use hack::program::Program;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Copies the JackBuild fixture into a fresh directory, so builds don't touch it.
+fn project(name: &str) -> PathBuf {
+	let directory = env::temp_dir().join(name).join("JackBuild");
+	let _ = fs::remove_dir_all(&directory);
+	fs::create_dir_all(&directory).unwrap();
+	for entry in fs::read_dir("tests/fixtures/JackBuild").unwrap() {
+		let path = entry.unwrap().path();
+		fs::copy(&path, directory.join(path.file_name().unwrap())).unwrap();
+	}
+	directory
+}
+
+/// Returns which steps ran, as (stage, output file name, ran).
+fn build(builder: &Builder) -> Vec<(Stage, String, bool)> {
+	builder.build().unwrap().into_iter().map(|step| {
+		(step.stage, step.output.file_name().unwrap().to_string_lossy().to_string(), step.ran)
+	}).collect()
+}
+
+/// FEATURE
+/// As a student,
+/// I want to build a directory of Jack classes into a Hack program in one step.
+#[test]
+fn build_jack_to_hack() {
+	let directory = project("hack-build-jack");
+	assert_eq!(build(&Builder::new(&directory)), vec![
+		(Stage::Compile, "Counter.vm".to_string(), true),
+		(Stage::Compile, "Main.vm".to_string(), true),
+		(Stage::Translate, "JackBuild.asm".to_string(), true),
+		(Stage::Assemble, "JackBuild.hack".to_string(), true),
+	]);
+
+	// the intermediate files are kept out of the way
+	assert!(!directory.join("Main.vm").exists());
+	let words = fs::read_to_string(directory.join("JackBuild.hack")).unwrap().lines().count();
+	let assembly = fs::read_to_string(directory.join(".hack-build/JackBuild.asm")).unwrap();
+	let program = Program::from_source(&assembly, false).unwrap();
+	assert_eq!(program.code.len(), words);
+
+	let mut cpu = Cpu::new();
+	assert_eq!(Profile::run(&program, &mut cpu, 100_000).end, End::Halted);
+	assert_eq!(cpu.ram[program.symbol("Main.0").unwrap() as usize], 18);
+	fs::remove_dir_all(directory.parent().unwrap()).unwrap();
+}
+
+/// FEATURE
+/// As a student,
+/// I want a rebuild to only rerun the stages whose inputs changed.
+#[test]
+fn rebuild_what_changed() {
+	let directory = project("hack-build-incremental");
+	let mut builder = Builder::new(&directory);
+	build(&builder);
+	assert!(build(&builder).iter().all(|&(_, _, ran)| !ran));
+
+	// a comment changes the class, but not the VM code compiled from it
+	let main = directory.join("Main.jack");
+	let source = fs::read_to_string(&main).unwrap();
+	fs::write(&main, format!("// the entry point\n{}", source)).unwrap();
+	assert_eq!(build(&builder).iter().filter(|step| step.2).map(|step| step.1.as_str()).collect::<Vec<_>>(), ["Main.vm"]);
+
+	fs::write(&main, source.replace("5", "6")).unwrap();
+	assert_eq!(build(&builder).iter().filter(|step| step.2).map(|step| step.1.as_str()).collect::<Vec<_>>(), ["Main.vm", "JackBuild.asm", "JackBuild.hack"]);
+
+	// a missing or edited output is rebuilt too
+	fs::remove_file(directory.join("JackBuild.hack")).unwrap();
+	assert_eq!(build(&builder).iter().filter(|step| step.2).map(|step| step.1.as_str()).collect::<Vec<_>>(), ["JackBuild.hack"]);
+
+	builder.set_force(true);
+	builder.set_intermediates(true);
+	assert!(build(&builder).iter().all(|&(_, _, ran)| ran));
+	assert!(fs::read_to_string(directory.join("Counter.vm")).unwrap().starts_with("function Counter.new 0\n"));
+	assert!(directory.join("JackBuild.asm").exists());
+
+	// the intermediate files beside the sources aren't taken for sources
+	builder.set_force(false);
+	assert!(build(&builder).iter().all(|&(_, _, ran)| !ran));
+	fs::remove_dir_all(directory.parent().unwrap()).unwrap();
+}
+
+/// FEATURE
+/// As a student,
+/// I want a directory of assembly files each assembled into its own program.
+#[test]
+fn build_assembly_files() {
+	let directory = env::temp_dir().join("hack-build-assembly");
+	let _ = fs::remove_dir_all(&directory);
+	fs::create_dir_all(&directory).unwrap();
+	fs::write(directory.join("Add.asm"), "@2\nD=A\n@3\nD=D+A\n@0\nM=D\n").unwrap();
+	fs::write(directory.join("Max.asm"), "@R0\nD=M\n@R1\nD=D-M\n").unwrap();
+
+	let builder = Builder::new(&directory);
+	assert_eq!(build(&builder), vec![
+		(Stage::Assemble, "Add.hack".to_string(), true),
+		(Stage::Assemble, "Max.hack".to_string(), true),
+	]);
+	assert_eq!(fs::read_to_string(directory.join("Max.hack")).unwrap().lines().count(), 4);
+
+	fs::write(directory.join("Max.asm"), "@R0\nD=M\n@R1\nD=D-M\n@R2\nM=D\n").unwrap();
+	assert_eq!(build(&builder), vec![
+		(Stage::Assemble, "Add.hack".to_string(), false),
+		(Stage::Assemble, "Max.hack".to_string(), true),
+	]);
+	fs::remove_dir_all(&directory).unwrap();
+}
+
+/// FEATURE
+/// As a student,
+/// I want to know when my program calls a function nothing defines, such as the Jack OS.
+#[test]
+fn report_build_errors() {
+	let directory = env::temp_dir().join("hack-build-missing");
+	let _ = fs::remove_dir_all(&directory);
+	fs::create_dir_all(&directory).unwrap();
+	assert_eq!(Builder::new(&directory).build(), Err(format!("no .jack, .vm or .asm files in {}", directory.display())));
+
+	fs::write(directory.join("Main.jack"), "class Main {\n  function void main() {\n    do Output.printInt(1);\n    return;\n  }\n}\n").unwrap();
+	fs::write(directory.join("Sys.vm"), "function Sys.init 0\ncall Main.main 0\n").unwrap();
+	assert_eq!(Builder::new(&directory).build(), Err("Main.vm: line 3: call to undefined function Output.printInt".to_string()));
+
+	// the class compiled, so only the translation is retried
+	fs::write(directory.join("Sys.vm"), "function Sys.init 0\ncall Main.main 0\nfunction Output.printInt 0\npush constant 0\nreturn\n").unwrap();
+	assert_eq!(Builder::new(&directory).build().unwrap().iter().map(|step| step.ran).collect::<Vec<_>>(), [false, true, true]);
+
+	let file = Path::new("tests/fixtures/JackBuild/Main.jack");
+	assert_eq!(Builder::new(file).build(), Err("tests/fixtures/JackBuild/Main.jack is not a directory".to_string()));
+	fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,49 @@
+extern crate hack;
+use hack::cfg::{Cfg, Edge, EdgeKind};
+use hack::instruction;
+use hack::preprocessor::SourceLine;
+
+fn cfg(source: &str) -> Cfg {
+	let lines: Vec<SourceLine> = source.lines().enumerate().map(|(i, text)| SourceLine::new(i + 1, text)).collect();
+	Cfg::new(&instruction::parse(&lines))
+}
+
+/// FEATURE
+/// As a teacher,
+/// I want programs split into basic blocks at labels and jumps.
+#[test]
+fn split_into_basic_blocks() {
+	// GIVEN a loop with a conditional exit
+	let graph = cfg("@i\nM=0\n(LOOP)\n@i\nD=M\n@END\nD;JGT\n@i\nM=M+1\n@LOOP\n0;JMP\n(END)\n@END\n0;JMP\n");
+
+	// THEN blocks start at 0, at each label and after each jump
+	let ranges: Vec<(usize, usize)> = graph.blocks.iter().map(|b| (b.start, b.end)).collect();
+	assert_eq!(ranges, vec![(0, 2), (2, 6), (6, 10), (10, 12)]);
+
+	// AND the conditional jump has both a jump and a fall through edge
+	assert_eq!(graph.blocks[0].edges, vec![Edge { to: 1, kind: EdgeKind::FallThrough, condition: None }]);
+	assert_eq!(graph.blocks[1].edges, vec![
+		Edge { to: 2, kind: EdgeKind::FallThrough, condition: None },
+		Edge { to: 3, kind: EdgeKind::Jump, condition: Some("JGT".to_string()) },
+	]);
+	assert_eq!(graph.blocks[2].edges, vec![Edge { to: 1, kind: EdgeKind::Jump, condition: Some("JMP".to_string()) }]);
+	assert_eq!(graph.blocks[1].statements[0].line.line, 3);
+}
+
+/// FEATURE
+/// As a teacher,
+/// I want to export the graph as DOT with the source lines in each node.
+#[test]
+fn export_dot() {
+	let graph = cfg("@END\n0;JMP\nD=A\n(END)\n@END\n0;JMP\n");
+
+	assert_eq!(graph.to_dot("prog.asm"), "digraph \"prog.asm\" {\n\
+		\tnode [shape=box, fontname=\"monospace\"];\n\
+		\tb0 [label=\"ROM 0-1\\l   1:     @END\\l   2:     0;JMP\\l\"];\n\
+		\tb1 [label=\"ROM 2-2\\l   3:     D=A\\l\", style=filled, fillcolor=lightgrey];\n\
+		\tb2 [label=\"ROM 3-4\\l   4: (END)\\l   5:     @END\\l   6:     0;JMP\\l\"];\n\
+		\tb0 -> b2 [label=\"JMP\"];\n\
+		\tb1 -> b2;\n\
+		\tb2 -> b2 [label=\"JMP\"];\n\
+		}\n");
+}
@@ -0,0 +1,61 @@
+extern crate hack;
+use hack::assembler;
+use hack::cpu::Cpu;
+use hack::instruction::COMPS;
+use hack::program::Program;
+
+/// FEATURE
+/// As a teacher,
+/// I want the emulated ALU to compute every comp of the instruction set.
+#[test]
+fn compute_every_comp() {
+	let (a, d, m) = (7u16, 3u16, 10u16);
+	let expected: [i16; 28] = [
+		0, 1, -1, 3, 7, !3, !7, -3, -7, 4, 8, 2, 6, 10, -4, 4, 3, 7,
+		10, !10, -10, 11, 9, 13, -7, 7, 2, 11,
+	];
+
+	for (comp, expected) in COMPS.iter().zip(expected.iter()) {
+		let mut cpu = Cpu::new();
+		cpu.a = a;
+		cpu.d = d;
+		cpu.ram[a as usize] = m;
+		cpu.execute(assembler::encode_c(Some("D"), comp, None));
+		assert_eq!(cpu.d as i16, *expected, "{}", comp);
+	}
+}
+
+/// FEATURE
+/// As a teacher,
+/// I want M written at the address A held before the instruction.
+#[test]
+fn write_m_at_old_address() {
+	let mut cpu = Cpu::new();
+	cpu.a = 20;
+	cpu.ram[20] = 5;
+
+	cpu.execute(assembler::encode_c(Some("AM"), "M+1", None));
+
+	assert_eq!((cpu.a, cpu.ram[20], cpu.ram[6], cpu.pc), (6, 6, 0, 1));
+}
+
+/// FEATURE
+/// As a user,
+/// I want to run assembled programs.
+#[test]
+fn run_program() {
+	// GIVEN a program multiplying R0 by R1 into R2
+	let source = "@R2\nM=0\n(LOOP)\n@R1\nD=M\n@END\nD;JEQ\n@R0\nD=M\n@R2\nM=D+M\n@R1\nM=M-1\n@LOOP\n0;JMP\n(END)\n@END\n0;JMP\n";
+	let program = Program::from_source(source, true).unwrap();
+	let mut cpu = Cpu::new();
+	cpu.ram[0] = 6;
+	cpu.ram[1] = 7;
+
+	// WHEN it runs until it reaches the final loop
+	while cpu.pc != 14 {
+		assert!(cpu.step(&program.code));
+	}
+
+	// THEN the product is in R2
+	assert_eq!(cpu.ram[2], 42);
+}
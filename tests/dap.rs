@@ -0,0 +1,214 @@
+extern crate hack;
+use hack::dap::Server;
+use hack::json::Json;
+use hack::lsp;
+
+use std::env;
+use std::fs;
+use std::io::Cursor;
+
+const PROGRAM: &str = "tests/fixtures/Mult.asm";
+const CONDITIONAL: &str = "tests/fixtures/Conditional.asm";
+
+fn request(server: &mut Server, command: &str, arguments: Vec<(&str, Json)>) -> Vec<Json> {
+	server.handle(&Json::object(vec![
+		("seq", Json::from(1i64)),
+		("type", Json::from("request")),
+		("command", Json::from(command)),
+		("arguments", Json::object(arguments)),
+	]))
+}
+
+fn events(replies: &[Json]) -> Vec<String> {
+	replies[1..].iter().map(|e| {
+		let reason = e.path(&["body", "reason"]).as_str().map(|r| format!(" {}", r)).unwrap_or_default();
+		format!("{}{}", e.get("event").as_str().unwrap(), reason)
+	}).collect()
+}
+
+fn line(server: &mut Server) -> i64 {
+	let trace = request(server, "stackTrace", vec![("threadId", Json::from(1i64))]);
+	trace[0].path(&["body", "stackFrames"]).as_array()[0].get("line").as_i64().unwrap()
+}
+
+fn variables(server: &mut Server, reference: i64) -> Vec<String> {
+	let replies = request(server, "variables", vec![("variablesReference", Json::from(reference))]);
+	replies[0].path(&["body", "variables"]).as_array().iter()
+		.map(|v| format!("{}={}", v.get("name").as_str().unwrap(), v.get("value").as_str().unwrap()))
+		.collect()
+}
+
+/// Launches Mult.asm stopped on entry, with R0 = 6 and R1 = 3.
+fn launch(server: &mut Server, breakpoints: &[usize]) -> Vec<Json> {
+	request(server, "initialize", vec![("adapterID", Json::from("hack"))]);
+	request(server, "launch", vec![("program", Json::from(PROGRAM)), ("stopOnEntry", Json::from(true))]);
+	let lines = breakpoints.iter().map(|&l| Json::object(vec![("line", Json::from(l))])).collect();
+	request(server, "setBreakpoints", vec![
+		("source", Json::object(vec![("path", Json::from(PROGRAM))])),
+		("breakpoints", Json::Array(lines)),
+	]);
+	let replies = request(server, "configurationDone", Vec::new());
+	for (name, value) in [("R0", "6"), ("R1", "3")] {
+		request(server, "setVariable", vec![
+			("variablesReference", Json::from(2i64)),
+			("name", Json::from(name)),
+			("value", Json::from(value)),
+		]);
+	}
+	replies
+}
+
+/// FEATURE
+/// As a student,
+/// I want to start debugging from my editor.
+#[test]
+fn initialize_and_launch() {
+	let mut server = Server::new();
+
+	let replies = request(&mut server, "initialize", vec![("adapterID", Json::from("hack"))]);
+	assert_eq!(replies[0].get("success").as_bool(), Some(true));
+	assert_eq!(replies[0].path(&["body", "supportsStepBack"]).as_bool(), Some(true));
+	assert_eq!(events(&replies), vec!["initialized"]);
+
+	let replies = request(&mut server, "launch", vec![("program", Json::from(PROGRAM)), ("stopOnEntry", Json::from(true))]);
+	assert_eq!(replies[0].get("success").as_bool(), Some(true));
+	assert!(events(&replies).is_empty(), "nothing runs before configurationDone");
+
+	let replies = request(&mut server, "setBreakpoints", vec![
+		("source", Json::object(vec![("path", Json::from(PROGRAM))])),
+		("breakpoints", Json::Array(vec![Json::object(vec![("line", Json::from(7i64))]), Json::object(vec![("line", Json::from(1i64))])])),
+	]);
+	let breakpoints = replies[0].path(&["body", "breakpoints"]).as_array();
+	assert_eq!(breakpoints[0].get("verified").as_bool(), Some(true));
+	assert_eq!(breakpoints[1].get("verified").as_bool(), Some(false));
+	assert_eq!(breakpoints[1].get("message").as_str(), Some("no instruction on line 1"));
+
+	assert_eq!(events(&request(&mut server, "configurationDone", Vec::new())), vec!["stopped entry"]);
+	assert_eq!(line(&mut server), 2);
+
+	let mut server = Server::new();
+	let replies = request(&mut server, "launch", vec![("program", Json::from("tests/fixtures/Missing.asm"))]);
+	assert_eq!(replies[0].get("success").as_bool(), Some(false));
+	assert!(replies[0].get("message").as_str().unwrap().starts_with("couldn't read tests/fixtures/Missing.asm"));
+}
+
+/// FEATURE
+/// As a student,
+/// I want to stop at breakpoints and see the registers and variables.
+#[test]
+fn breakpoints_and_variables() {
+	let mut server = Server::new();
+	launch(&mut server, &[16]);
+
+	let replies = request(&mut server, "continue", vec![("threadId", Json::from(1i64))]);
+	assert_eq!(events(&replies), vec!["stopped breakpoint"]);
+	assert_eq!(line(&mut server), 16);
+	assert_eq!(variables(&mut server, 1), vec!["A=2", "D=6", "M=0", "PC=13"]);
+	assert_eq!(variables(&mut server, 2), vec!["i=0", "R2=0", "R1=3", "R0=6"]);
+
+	let replies = request(&mut server, "evaluate", vec![("expression", Json::from("i"))]);
+	assert_eq!(replies[0].path(&["body", "result"]).as_str(), Some("i = RAM[16] = 0"));
+
+	let scopes = request(&mut server, "scopes", vec![("frameId", Json::from(0i64))]);
+	let names: Vec<&str> = scopes[0].path(&["body", "scopes"]).as_array().iter().map(|s| s.get("name").as_str().unwrap()).collect();
+	assert_eq!(names, vec!["Registers", "Variables"]);
+
+	let trace = request(&mut server, "stackTrace", vec![("threadId", Json::from(1i64))]);
+	let frame = &trace[0].path(&["body", "stackFrames"]).as_array()[0];
+	assert_eq!(frame.get("name").as_str(), Some("LOOP"));
+	assert_eq!(frame.path(&["source", "path"]).as_str(), Some(PROGRAM));
+}
+
+/// FEATURE
+/// As a student,
+/// I want to step forwards and backwards through the source, and see the program end.
+#[test]
+fn step_and_terminate() {
+	let mut server = Server::new();
+	launch(&mut server, &[]);
+
+	assert_eq!(events(&request(&mut server, "next", Vec::new())), vec!["stopped step"]);
+	assert_eq!(line(&mut server), 3);
+	request(&mut server, "stepIn", Vec::new());
+	assert_eq!(line(&mut server), 4);
+	assert_eq!(events(&request(&mut server, "stepBack", Vec::new())), vec!["stopped step"]);
+	assert_eq!(line(&mut server), 3);
+
+	// the whole of INC M is one step over
+	for _ in 0..14 {
+		request(&mut server, "next", Vec::new());
+	}
+	assert_eq!(line(&mut server), 18);
+	request(&mut server, "next", Vec::new());
+	assert_eq!(line(&mut server), 19);
+
+	let replies = request(&mut server, "continue", Vec::new());
+	assert_eq!(events(&replies), vec!["exited", "terminated"]);
+	assert_eq!(variables(&mut server, 2)[1], "R2=18");
+
+	// stepping out of DOUBLE stops at the return address
+	let mut server = Server::new();
+	request(&mut server, "launch", vec![("program", Json::from("tests/fixtures/Unreachable.asm"))]);
+	request(&mut server, "setBreakpoints", vec![("breakpoints", Json::Array(vec![Json::object(vec![("line", Json::from(17i64))])]))]);
+	assert_eq!(events(&request(&mut server, "configurationDone", Vec::new())), vec!["stopped breakpoint"]);
+	assert_eq!(events(&request(&mut server, "stepOut", Vec::new())), vec!["stopped step"]);
+	assert_eq!(line(&mut server), 9);
+	assert_eq!(events(&request(&mut server, "stepOut", Vec::new())), vec!["exited", "terminated"]);
+}
+
+/// FEATURE
+/// As a student,
+/// I want the program debugged as it's assembled with my -D, --strict, --extended, -O and --strip-unreachable options.
+#[test]
+fn launch_with_assembler_options() {
+	let verified = |program: &str, option: (&str, Json), lines: &[usize]| -> Vec<bool> {
+		let mut server = Server::new();
+		let replies = request(&mut server, "launch", vec![("program", Json::from(program)), option]);
+		assert_eq!(replies[0].get("success").as_bool(), Some(true));
+		let breakpoints = lines.iter().map(|&line| Json::object(vec![("line", Json::from(line))])).collect();
+		let replies = request(&mut server, "setBreakpoints", vec![("breakpoints", Json::Array(breakpoints))]);
+		replies[0].path(&["body", "breakpoints"]).as_array().iter().map(|b| b.get("verified").as_bool().unwrap()).collect()
+	};
+	assert_eq!(verified(CONDITIONAL, ("defines", Json::Array(vec![Json::from("DEBUG")])), &[5, 7]), vec![true, false]);
+	assert_eq!(verified(CONDITIONAL, ("defines", Json::Array(Vec::new())), &[5, 7]), vec![false, true]);
+
+	// the optimiser removes the reload of sum, and unreachable code can be stripped
+	assert_eq!(verified("tests/fixtures/Optimize.asm", ("optimize", Json::from(false)), &[16, 17]), vec![true, true]);
+	assert_eq!(verified("tests/fixtures/Optimize.asm", ("optimize", Json::from(true)), &[16, 17]), vec![true, false]);
+	assert_eq!(verified("tests/fixtures/Unreachable.asm", ("stripUnreachable", Json::from(false)), &[11, 17]), vec![true, true]);
+	assert_eq!(verified("tests/fixtures/Unreachable.asm", ("stripUnreachable", Json::from(true)), &[11, 17]), vec![false, true]);
+
+	let mut server = Server::new();
+	let replies = request(&mut server, "launch", vec![("program", Json::from(CONDITIONAL)), ("defines", Json::Array(vec![Json::from("DE-BUG")]))]);
+	assert_eq!(replies[0].get("message").as_str(), Some("invalid constant name 'DE-BUG'"));
+
+	// a source map has to fit the program
+	let map = env::temp_dir().join("hack-dap-Conditional.sourcemap.json");
+	fs::write(&map, "{\"version\":1,\"file\":\"Conditional.asm\",\"mappings\":[{\"address\":99,\"line\":5,\"column\":1}]}").unwrap();
+	let mut server = Server::new();
+	let replies = request(&mut server, "launch", vec![("program", Json::from(CONDITIONAL)), ("sourceMap", Json::from(map.to_str().unwrap()))]);
+	assert_eq!(replies[0].get("message").as_str(), Some(format!("{}: the source map maps ROM 99, but the program is 7 words", map.display()).as_str()));
+	fs::remove_file(&map).unwrap();
+}
+
+/// FEATURE
+/// As an editor,
+/// I want messages framed as the protocol describes, ending at disconnect.
+#[test]
+fn serve_over_stdio() {
+	let mut input = Vec::new();
+	for command in ["initialize", "disconnect", "threads"] {
+		let message = Json::object(vec![("seq", Json::from(1i64)), ("type", Json::from("request")), ("command", Json::from(command))]);
+		lsp::write_message(&mut input, &message).unwrap();
+	}
+
+	let mut output = Vec::new();
+	Server::new().run(&mut Cursor::new(input), &mut output).unwrap();
+
+	let mut output = Cursor::new(output);
+	let mut commands = Vec::new();
+	while let Some(message) = lsp::read_message(&mut output).unwrap() {
+		commands.push(message.get("command").as_str().or(message.get("event").as_str()).unwrap().to_string());
+	}
+	assert_eq!(commands, vec!["initialize", "initialized", "disconnect"]);
+}
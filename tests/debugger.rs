@@ -0,0 +1,68 @@
+extern crate hack;
+use hack::debugger::Debugger;
+use hack::program::Program;
+
+use std::fs;
+
+fn debugger() -> Debugger {
+	let source = fs::read_to_string("tests/fixtures/Mult.asm").unwrap();
+	let mut debugger = Debugger::new(Program::from_source(&source, true).unwrap(), &source);
+	debugger.execute("set R0 6");
+	debugger.execute("set R1 3");
+	debugger
+}
+
+/// FEATURE
+/// As a student,
+/// I want to stop at labels and source lines and inspect the program's state.
+#[test]
+fn break_on_labels_and_lines() {
+	let mut debugger = debugger();
+
+	assert_eq!(debugger.execute("break LOOP"), "breakpoint 1 at LOOP (ROM 4, line 7)");
+	assert_eq!(debugger.execute("break 16"), "breakpoint 2 at line 16 (ROM 13, line 16)");
+	assert_eq!(debugger.execute("continue"), "breakpoint 1 at LOOP\nROM 4, line 7: @R1");
+	assert_eq!(debugger.execute("c"), "breakpoint 2 at line 16\nROM 13, line 16: M=D+M");
+	assert_eq!(debugger.execute("registers"), "A=2 D=6 M=0 PC=13");
+	assert_eq!(debugger.execute("step"), "ROM 14, line 17: @i");
+	assert_eq!(debugger.execute("x 0 3"), "RAM[0] = 6\nRAM[1] = 3\nRAM[2] = 6");
+	assert_eq!(debugger.execute("x 32766 18446744073709551615"), "RAM[32766] = 0\nRAM[32767] = 0");
+
+	assert_eq!(debugger.execute("break 1"), "error: no instruction on line 1");
+	assert_eq!(debugger.execute("break NOWHERE"), "error: expected a label or line number but found 'NOWHERE'");
+}
+
+/// FEATURE
+/// As a student,
+/// I want to stop when a variable changes.
+#[test]
+fn watch_variables() {
+	let mut debugger = debugger();
+
+	assert_eq!(debugger.execute("watch i"), "watchpoint 1 on i (RAM[16])");
+	assert_eq!(debugger.execute("c"), "watchpoint 1: i (RAM[16]) changed from 0 to 1\nROM 16, line 19: @LOOP");
+	assert_eq!(debugger.execute("delete 1"), "deleted 1");
+	assert_eq!(debugger.execute("c"), "the program has halted\nROM 18, line 22: @END");
+	assert_eq!(debugger.execute("print R2"), "R2 = RAM[2] = 18");
+	assert_eq!(debugger.execute("print i"), "i = RAM[16] = 3");
+}
+
+/// FEATURE
+/// As a student,
+/// I want to step backwards through what the program did.
+#[test]
+fn reverse_step() {
+	let mut debugger = debugger();
+	debugger.execute("break END");
+	debugger.execute("c");
+
+	assert_eq!(debugger.execute("rs"), "ROM 9, line 12: D;JEQ");
+	assert_eq!(debugger.execute("rs"), "ROM 8, line 11: @END");
+	// back through the loop to before the INC M of the last iteration
+	for _ in 0..7 {
+		debugger.execute("rs");
+	}
+	assert_eq!(debugger.execute("print i"), "i = RAM[16] = 2");
+	assert_eq!(debugger.execute("next"), "ROM 16, line 19: @LOOP");
+	assert_eq!(debugger.execute("print i"), "i = RAM[16] = 3");
+}
@@ -0,0 +1,47 @@
+extern crate hack;
+use hack::explain;
+use hack::program::Program;
+
+/// FEATURE
+/// As a student,
+/// I want to see what each bit group of a C-instruction means.
+#[test]
+fn explain_c_instruction() {
+	let program = Program::from_source("AM=M-1;JNE\n", true).unwrap();
+
+	assert_eq!(explain::explain(&program), "ROM 0 (line 1): AM=M-1;JNE\n\
+		\x20 111 1 110010 101 101\n\
+		\x20 a    1       y is M\n\
+		\x20 comp 110010  zx nx f (x=0, x=!x, out=x+y)  x is D, out = M-1\n\
+		\x20 dest 101     stores out in A, M (RAM[A]), with M at the address A held before\n\
+		\x20 jump 101     jumps to ROM[A] if out != 0\n");
+}
+
+/// FEATURE
+/// As a student,
+/// I want to see how the symbol of an A-instruction was resolved.
+#[test]
+fn explain_symbol_resolution() {
+	let program = Program::from_source("(START)\n@x\n@x\n@KBD\n@START\n@7\n", true).unwrap();
+
+	let resolutions: Vec<String> = explain::explain(&program).lines()
+		.filter_map(|l| l.strip_prefix("  A-instruction: A = ").map(|r| r.to_string()))
+		.collect();
+	assert_eq!(resolutions, vec![
+		"new variable x, allocated at RAM 16",
+		"variable x, at RAM 16",
+		"predefined symbol KBD = 24576",
+		"label START, at ROM 0",
+		"constant 7",
+	]);
+}
+
+/// FEATURE
+/// As a student,
+/// I want the ALU's AND to be pointed out when f is clear.
+#[test]
+fn explain_and() {
+	let program = Program::from_source("D=D&A\n", true).unwrap();
+
+	assert!(explain::explain(&program).contains("  comp 000000  no control bits set (out=x&y)  x is D, out = D&A\n"));
+}
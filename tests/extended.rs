@@ -0,0 +1,88 @@
+extern crate hack;
+use hack::assembler::{self, Assembler};
+use hack::cpu::Cpu;
+use hack::disassembler;
+use hack::explain;
+use hack::gates;
+use hack::preprocessor::Preprocessor;
+use hack::program::Program;
+
+use std::env;
+use std::fs;
+
+fn assemble(source: &str, extended: bool) -> Result<Program, Vec<String>> {
+	let lines = Preprocessor::new().process(source).unwrap();
+	Program::assemble_extended(&lines, extended).map_err(|errors| errors.iter().map(|e| e.to_string()).collect())
+}
+
+/// FEATURE
+/// As a student using the nand2tetris CPU emulator,
+/// I want its shift instructions encoded when I opt in to them.
+#[test]
+fn encode_shifts() {
+	assert_eq!(assembler::encode_c(Some("D"), "D<<", None), 0b1010110000010000);
+	assert_eq!(assembler::encode_c(Some("M"), "M>>", Some("JGT")), 0b1011000000001001);
+	assert_eq!(assembler::encode_c(Some("A"), "A>>", None), 0b1010000000100000);
+	assert_eq!(assemble("D=D<<\n", true).unwrap().code, vec![0b1010110000010000]);
+	assert_eq!(assemble("D=D<<\n", false).err().unwrap(), vec!["line 1: error: D<< is an extended instruction; assemble with --extended"]);
+
+	let path = env::temp_dir().join("hack-extended.asm");
+	fs::write(&path, "@5\nD=A\nD=D<<\n").unwrap();
+	let mut assembler = Assembler::new(&path.to_string_lossy());
+	assembler.set_quiet(true);
+	assert_eq!(assembler.try_translate(), Err(format!("{}: line 3: D<< is an extended instruction; assemble with --extended", path.display())));
+	assembler = Assembler::new(&path.to_string_lossy());
+	assembler.set_quiet(true);
+	assembler.set_extended(true);
+	assert_eq!(assembler.try_translate(), Ok(()));
+	assert_eq!(fs::read_to_string(path.with_extension("hack")).unwrap(), "0000000000000101\n1110110000010000\n1010110000010000\n");
+	fs::remove_file(&path).unwrap();
+	fs::remove_file(path.with_extension("hack")).unwrap();
+}
+
+/// FEATURE
+/// As a student using the nand2tetris CPU emulator,
+/// I want the emulator to run the shift instructions.
+#[test]
+fn execute_shifts() {
+	let program = assemble("@5\nD=A\nD=D<<\n@100\nM=-1\nM=M<<\nAD=M>>\n", true).unwrap();
+	let mut cpu = Cpu::new();
+	for _ in 0..3 {
+		cpu.step(&program.code);
+	}
+	assert_eq!(cpu.d, 10);
+	for _ in 0..3 {
+		cpu.step(&program.code);
+	}
+	assert_eq!(cpu.ram[100] as i16, -2);
+
+	// shifting right keeps the sign
+	cpu.step(&program.code);
+	assert_eq!((cpu.a as i16, cpu.d as i16), (-1, -1));
+}
+
+/// FEATURE
+/// As a student,
+/// I want to turn machine code back into assembly, shifts included.
+#[test]
+fn disassemble_a_program() {
+	let source = "@21\nD=A\nAM=M+1;JGT\n0;JMP\nD=D<<\nM=M>>\nA=A>>;JLE\nMD=!D\n";
+	let program = assemble(source, true).unwrap();
+	assert_eq!(disassembler::disassemble_program(&program.code), Ok(source.to_string()));
+
+	assert_eq!(disassembler::disassemble(0b1110000001010000), None);
+	assert_eq!(disassembler::disassemble_program(&[5, 0b1100110000010000]), Err("ROM 1: 1100110000010000 isn't an instruction".to_string()));
+}
+
+/// FEATURE
+/// As a student,
+/// I want the shifts explained, and kept away from the book's CPU.
+#[test]
+fn explain_shifts() {
+	let program = assemble("M=M>>\n", true).unwrap();
+	assert!(explain::explain(&program).contains("  101 1 000000 001 000\n\
+		\x20 a    1       y is M\n\
+		\x20 comp 000000  shifts y right, keeping the sign (101 prefix: extended instruction set), out = M>>\n"));
+
+	assert_eq!(gates::cross_check(&program.code, 10), Err("ROM 0 holds a shift of the extended instruction set, which the book's CPU doesn't have".to_string()));
+}
@@ -0,0 +1,21 @@
+// Adds 2 and 3, or 2 and 5 in debug builds.
+.define BASE 2
+
+.ifdef DEBUG
+@5
+.else
+@3
+.endif
+D=A
+.if BASE == 2 && !defined(DEBUG)
+(RELEASE)
+.endif
+@BASE_VALUE
+.if BASE > 1
+@2
+.elif BASE > 0
+@1
+.endif
+D=D+A
+@0
+M=D
@@ -0,0 +1,49 @@
+// Every comp, dest and jump, with labels, variables and predefined symbols,
+// to check the encoding against the baseline assembler
+(START)
+	0
+	M=1;JGT
+	D=-1;JEQ
+	MD=D;JGE
+	@SCREEN
+	A=A;JLT
+	AM=!D;JNE
+	AD=!A;JLE
+	AMD=-D;JMP
+	-A;JGT
+	M=D+1;JEQ
+	D=A+1;JGE
+	@SP
+	MD=D-1;JLT
+	A=A-1;JNE
+	AM=D+A;JLE
+	AD=D-A;JMP
+	AMD=A-D
+	D&A;JEQ
+	M=D|A;JGE
+	@counter
+	D=M;JLT
+	MD=!M;JNE
+	A=-M;JLE
+	AM=M+1;JMP
+	AD=M-1
+	AMD=D+M;JGT
+	D-M;JGE
+	@SCREEN
+	M=M-D;JLT
+	D=D&M;JNE
+	MD=D|M;JLE
+(END)
+	@R15
+	@THAT
+	@ARG
+	@LCL
+	@THIS
+	@counter
+	@i
+	@24576
+	@START
+	@KBD
+	@R0
+	@END
+	0;JMP
@@ -0,0 +1,12 @@
+// Computes R2 = 3 * 4 using the MULT routine from LinkMult.asm.
+.import MULT
+.export RETURN
+	@3
+	D=A
+	@x
+	M=D
+	@MULT
+	0;JMP
+(RETURN)
+	@RETURN
+	0;JMP
@@ -0,0 +1,26 @@
+// R2 = x * 4, then jumps back to RETURN.
+.export MULT
+.import RETURN
+(MULT)
+	@R2
+	M=0
+	@4
+	D=A
+	@n
+	M=D
+(LOOP)
+	@n
+	D=M
+	@DONE
+	D;JEQ
+	@x
+	D=M
+	@R2
+	M=M+D
+	@n
+	M=M-1
+	@LOOP
+	0;JMP
+(DONE)
+	@RETURN
+	0;JMP
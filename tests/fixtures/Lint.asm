@@ -0,0 +1,24 @@
+// Suspicious patterns for hack lint
+@counter
+M=0
+(LOOP)
+@loop
+M=M+1
+@LOOP
+A=M
+0;JMP
+@ret
+0;JMP
+@SP
+AM=M-1
+D=M
+A=M
+AM=M-1 // hack-lint: allow(am-read-write)
+// hack-lint: allow(jump-to-variable)
+@ret
+0;JMP
+@sp
+M=0
+@R0
+A=M
+AM=M+1
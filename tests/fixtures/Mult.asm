@@ -0,0 +1,23 @@
+// Multiplies R0 by R1 into R2
+    @R2
+    M=0
+    @i
+    M=0
+(LOOP)
+    @R1
+    D=M
+    @i
+    D=D-M
+    @END
+    D;JEQ
+    @R0
+    D=M
+    @R2
+    M=D+M
+    @i
+    INC M
+    @LOOP
+    0;JMP
+(END)
+    @END
+    0;JMP
@@ -0,0 +1,29 @@
+// Sums i = 1..n into sum, with the redundancies a code generator leaves behind.
+	@i
+	M=1
+	@sum
+	M=0
+(LOOP)
+	@i
+	D=M
+	@n
+	D=D-M
+	@END
+	D;JGT
+	@i
+	D=M
+	@sum
+	M=M+D
+	@sum        // A already holds sum
+	D=M
+	M=D         // M already holds D
+	@i
+	M=M+1
+	@LOOP
+	0;JMP
+(END)
+	@DONE       // jumps to the next instruction
+	0;JMP
+(DONE)
+	@DONE
+	0;JMP
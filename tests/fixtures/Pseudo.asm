@@ -0,0 +1,12 @@
+// Counts down from 3, pushing each value onto the stack.
+	LOAD D, 3
+	@i
+	M=D
+(LOOP)
+	@i
+	D=M
+	PUSH D
+	DEC M           // i = i - 1
+	IF D>0 GOTO LOOP
+(END)
+	GOTO END
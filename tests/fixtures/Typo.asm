@@ -0,0 +1,15 @@
+// Counts down from 10, with a misspelt label
+@10
+D=A
+@count
+M=D
+(LOOP)
+@count
+MD=M-1
+@LOPP
+D;JGT
+@cuont
+M=0
+(END)
+@END
+0;JMP
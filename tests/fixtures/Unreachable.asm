@@ -0,0 +1,24 @@
+// Calls DOUBLE through a return address, then loops forever.
+	@RET
+	D=A
+	@R13
+	M=D
+	@DOUBLE
+	0;JMP
+(RET)
+	@END
+	0;JMP
+	@R0         // never executed
+	M=0
+(UNUSED)
+	@END
+	0;JMP
+(DOUBLE)
+	@R0
+	M=M+D
+	@R13
+	A=M
+	0;JMP
+(END)
+	@END
+	0;JMP
@@ -0,0 +1,80 @@
+extern crate hack;
+use hack::assembler::Assembler;
+use hack::diagnostic::Diagnostic;
+use hack::flow::Flow;
+use hack::instruction;
+use hack::preprocessor::SourceLine;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn statements(source: &str) -> Vec<instruction::Statement> {
+	let lines: Vec<SourceLine> = source.lines().enumerate().map(|(i, text)| SourceLine::new(i + 1, text)).collect();
+	instruction::parse(&lines)
+}
+
+/// Copies the Unreachable fixture into a directory of its own, so tests
+/// running at the same time don't share the `.hack` output.
+fn unreachable_fixture(test: &str) -> PathBuf {
+	let directory = env::temp_dir().join(format!("hack-flow-{}", test));
+	fs::create_dir_all(&directory).unwrap();
+	let path = directory.join("Unreachable.asm");
+	fs::copy("tests/fixtures/Unreachable.asm", &path).unwrap();
+	path
+}
+
+/// FEATURE
+/// As a user,
+/// I want to be warned about unreachable code and labels that are never used.
+#[test]
+fn warn_about_unreachable_code_and_unused_labels() {
+	// GIVEN a program with code after an unconditional jump and an unused label
+	let f_in = unreachable_fixture("warnings");
+
+	// WHEN I run the assembler
+	let mut assembler = Assembler::new(&f_in.to_string_lossy());
+	assembler.translate();
+
+	// THEN it warns about both, but the computed jump back to RET is followed
+	assert_eq!(assembler.warnings(), &[
+		Diagnostic::warning(11, "unreachable code at ROM 8-11 (lines 11-15)"),
+		Diagnostic::warning(13, "label (UNUSED) is never used"),
+	]);
+	assert_eq!(fs::read_to_string(f_in.with_extension("hack")).unwrap().lines().count(), 19);
+
+	fs::remove_dir_all(f_in.parent().unwrap()).unwrap();
+}
+
+/// FEATURE
+/// As a user,
+/// I want to be able to strip unreachable code from the output.
+#[test]
+fn strip_unreachable_code() {
+	let f_in = unreachable_fixture("strip");
+	let mut assembler = Assembler::new(&f_in.to_string_lossy());
+	assembler.set_strip_unreachable(true);
+	assembler.translate();
+
+	// THEN the 4 unreachable words are gone, and labels after them move up
+	let actual = fs::read_to_string(f_in.with_extension("hack")).unwrap();
+	let lines: Vec<&str> = actual.lines().collect();
+	assert_eq!(lines.len(), 15);
+	assert_eq!(lines[4], "0000000000001000"); // @DOUBLE
+	assert_eq!(lines[6], "0000000000001101"); // @END
+
+	fs::remove_dir_all(f_in.parent().unwrap()).unwrap();
+}
+
+/// FEATURE
+/// As a user,
+/// I want conditional jumps to keep both paths reachable.
+#[test]
+fn conditional_jumps_fall_through() {
+	let statements = statements("@x\nD=M\n@POS\nD;JGT\nD=-D\n(POS)\n@POS\n0;JMP\n");
+	let flow = Flow::new(&statements);
+
+	assert!(flow.reachable.iter().all(|&r| r));
+	assert_eq!(flow.successors(3), vec![4, 5]);
+	assert_eq!(flow.successors(6), vec![5]);
+}
@@ -0,0 +1,53 @@
+extern crate hack;
+use hack::formatter;
+
+use std::fs;
+
+/// FEATURE
+/// As a user,
+/// I want my programs rewritten in a canonical layout.
+#[test]
+fn format_layout() {
+	// GIVEN a program with inconsistent indentation and spacing
+	let source = "// Adds 1 to R0\n\n\n  @R0   // the counter\nM = M+1\n(END)   \n\t@END\n\t\t0 ; JMP // forever\n\n";
+
+	// WHEN I format it
+	// THEN labels are flush left, instructions indented and trailing comments aligned per paragraph
+	assert_eq!(formatter::format(source), "// Adds 1 to R0\n\
+		\n    @R0   // the counter\
+		\n    M=M+1\
+		\n(END)\
+		\n    @END\
+		\n    0;JMP // forever\n");
+}
+
+/// FEATURE
+/// As a user,
+/// I want operands spelt the way the book spells them.
+#[test]
+fn format_canonical_mnemonics() {
+	let source = "DM=M+D\nMA=1+M\nD=A&D\nAD=D|M\nLOAD   D ,  R1\nIF D>0   GOTO  END\n";
+
+	assert_eq!(formatter::format(source), "    MD=D+M\n    AM=M+1\n    D=D&A\n    AD=D|M\n    LOAD D, R1\n    IF D>0 GOTO END\n");
+}
+
+/// FEATURE
+/// As a user,
+/// I want comment lines indented like the code they describe.
+#[test]
+fn format_comment_lines() {
+	let source = "@R0\n// before the label\n(LOOP)\n// inside the loop\n@LOOP\n0;JMP\n";
+
+	assert_eq!(formatter::format(source), "    @R0\n// before the label\n(LOOP)\n    // inside the loop\n    @LOOP\n    0;JMP\n");
+}
+
+/// FEATURE
+/// As a user,
+/// I want formatting a formatted program to change nothing.
+#[test]
+fn format_is_idempotent() {
+	for file in &["tests/fixtures/Pseudo.asm", "tests/fixtures/Conditional.asm", "tests/fixtures/Optimize.asm"] {
+		let formatted = formatter::format(&fs::read_to_string(file).unwrap());
+		assert_eq!(formatter::format(&formatted), formatted, "{}", file);
+	}
+}
@@ -0,0 +1,72 @@
+extern crate hack;
+use hack::assembler;
+use hack::cpu;
+use hack::gates::{self, bus, value, GateCpu, Outputs};
+use hack::profiler::End;
+use hack::program::Program;
+
+/// FEATURE
+/// As a student building the CPU in HDL,
+/// I want a reference ALU built from Nand gates, with its zr and ng flags.
+#[test]
+fn compute_with_gates() {
+	let alu = |x: u16, y: u16, bits: [bool; 6]| {
+		let (out, zr, ng) = gates::alu(&bus(x), &bus(y), bits);
+		(value(&out) as i16, zr, ng)
+	};
+	// D+A, D-A and D&A
+	assert_eq!(alu(5, 3, [false, false, false, false, true, false]), (8, false, false));
+	assert_eq!(alu(3, 5, [false, true, false, false, true, true]), (-2, false, true));
+	assert_eq!(alu(4, 3, [false, false, false, false, false, false]), (0, true, false));
+
+	assert_eq!(value(&gates::add16(&bus(0x7fff), &bus(1))), 0x8000);
+	assert_eq!(value(&gates::inc16(&bus(0xffff))), 0);
+	assert_eq!((gates::xor(true, false), gates::mux(true, false, true), gates::or16way(&bus(0))), (true, false, false));
+}
+
+/// FEATURE
+/// As a student building the CPU in HDL,
+/// I want the gate-level CPU to behave like the book's CPU chip.
+#[test]
+fn run_the_cpu_chip() {
+	let mut chip = GateCpu::new();
+	chip.cycle(0, 20, false);
+	assert_eq!((value(&chip.a), value(&chip.pc)), (20, 1));
+
+	// AM=M+1 writes at the old address
+	let outputs = chip.cycle(6, assembler::encode_c(Some("AM"), "M+1", None), false);
+	assert_eq!(outputs, Outputs { out_m: 7, write_m: true, address_m: 20 });
+	assert_eq!((value(&chip.a), value(&chip.pc)), (7, 2));
+
+	chip.cycle(0, assembler::encode_c(None, "0", Some("JMP")), false);
+	assert_eq!(value(&chip.pc), 7);
+	chip.cycle(0, 3, true);
+	assert_eq!((value(&chip.a), value(&chip.pc)), (3, 0));
+}
+
+/// FEATURE
+/// As a teacher,
+/// I want the assembler's comp encoding and the emulator checked against the gates.
+#[test]
+fn cross_check_a_program() {
+	assert_eq!(gates::check_encoding(), Ok(()));
+
+	// R2 = R0 * R1
+	let source = "@6\nD=A\n@R0\nM=D\n@7\nD=A\n@R1\nM=D\n@R2\nM=0\n\
+		(LOOP)\n@R0\nD=M\n@END\nD;JEQ\n@R1\nD=M\n@R2\nM=D+M\n@R0\nM=M-1\n@LOOP\n0;JMP\n\
+		(END)\n@END\n0;JMP\n";
+	let rom = Program::from_source(source, false).unwrap().code;
+	assert_eq!(gates::cross_check(&rom, 1000), Ok((86, End::Halted)));
+	assert_eq!(gates::cross_check(&rom, 10), Ok((10, End::Limit)));
+	assert_eq!(gates::cross_check(&rom[..10], 1000), Ok((10, End::Ended)));
+}
+
+/// FEATURE
+/// As a teacher,
+/// I want to be told when a file isn't Hack machine code.
+#[test]
+fn read_hack_files() {
+	assert_eq!(cpu::parse_hack("0000000000000010\n1110110000010000\n\n"), Ok(vec![2, 0xec10]));
+	assert_eq!(cpu::parse_hack("0000000000000010\n111011000001000\n"), Err("line 2: expected 16 binary digits but found '111011000001000'".to_string()));
+	assert_eq!(cpu::parse_hack("@2\n"), Err("line 1: expected 16 binary digits but found '@2'".to_string()));
+}
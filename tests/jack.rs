@@ -0,0 +1,138 @@
+extern crate hack;
+use hack::compiler;
+use hack::jack;
+
+/// FEATURE
+/// As a student,
+/// I want the tokens of a Jack class as the XML project 10 compares.
+#[test]
+fn tokenize_to_xml() {
+	let tokens = jack::tokenize("/** doc */ let s = \"a < b\"; // done\nif (x < 10) {}").unwrap();
+	assert_eq!(tokens[3].line, 1);
+	assert_eq!(tokens[5].line, 2);
+	assert_eq!(jack::tokens_xml(&tokens[..5]), "<tokens>\n\
+		<keyword> let </keyword>\n\
+		<identifier> s </identifier>\n\
+		<symbol> = </symbol>\n\
+		<stringConstant> a &lt; b </stringConstant>\n\
+		<symbol> ; </symbol>\n\
+		</tokens>\n");
+
+	assert_eq!(jack::tokenize("let x = 32768;"), Err("line 1: integer constant 32768 is greater than 32767".to_string()));
+	assert_eq!(jack::tokenize("\n\"open"), Err("line 2: string constant is never closed".to_string()));
+	assert_eq!(jack::tokenize("/* never\nclosed"), Err("line 1: comment is never closed".to_string()));
+	assert_eq!(jack::tokenize("let x = #;"), Err("line 1: unexpected character '#'".to_string()));
+}
+
+/// FEATURE
+/// As a student,
+/// I want the parse tree of a Jack class as the XML project 10 compares.
+#[test]
+fn parse_to_xml() {
+	let tree = jack::parse("class Main {\n  function void main() {\n    do Output.printInt(-x);\n    return;\n  }\n}\n").unwrap();
+	assert_eq!(tree.to_xml(), "<class>\n\
+		\x20 <keyword> class </keyword>\n\
+		\x20 <identifier> Main </identifier>\n\
+		\x20 <symbol> { </symbol>\n\
+		\x20 <subroutineDec>\n\
+		\x20   <keyword> function </keyword>\n\
+		\x20   <keyword> void </keyword>\n\
+		\x20   <identifier> main </identifier>\n\
+		\x20   <symbol> ( </symbol>\n\
+		\x20   <parameterList>\n\
+		\x20   </parameterList>\n\
+		\x20   <symbol> ) </symbol>\n\
+		\x20   <subroutineBody>\n\
+		\x20     <symbol> { </symbol>\n\
+		\x20     <statements>\n\
+		\x20       <doStatement>\n\
+		\x20         <keyword> do </keyword>\n\
+		\x20         <identifier> Output </identifier>\n\
+		\x20         <symbol> . </symbol>\n\
+		\x20         <identifier> printInt </identifier>\n\
+		\x20         <symbol> ( </symbol>\n\
+		\x20         <expressionList>\n\
+		\x20           <expression>\n\
+		\x20             <term>\n\
+		\x20               <symbol> - </symbol>\n\
+		\x20               <term>\n\
+		\x20                 <identifier> x </identifier>\n\
+		\x20               </term>\n\
+		\x20             </term>\n\
+		\x20           </expression>\n\
+		\x20         </expressionList>\n\
+		\x20         <symbol> ) </symbol>\n\
+		\x20         <symbol> ; </symbol>\n\
+		\x20       </doStatement>\n\
+		\x20       <returnStatement>\n\
+		\x20         <keyword> return </keyword>\n\
+		\x20         <symbol> ; </symbol>\n\
+		\x20       </returnStatement>\n\
+		\x20     </statements>\n\
+		\x20     <symbol> } </symbol>\n\
+		\x20   </subroutineBody>\n\
+		\x20 </subroutineDec>\n\
+		\x20 <symbol> } </symbol>\n\
+		</class>\n");
+
+	assert_eq!(jack::parse("class Main {\n  function void main() {\n    let x = ;\n"), Err("line 3: expected an expression but found ';'".to_string()));
+	assert_eq!(jack::parse("class Main {\n  field int x"), Err("line 2: expected ';' but reached the end of the file".to_string()));
+	assert_eq!(jack::parse("class Main {} class"), Err("line 1: expected the end of the file but found 'class'".to_string()));
+}
+
+/// FEATURE
+/// As a student,
+/// I want Jack classes compiled to VM code as in project 11.
+#[test]
+fn compile_to_vm() {
+	let code = compiler::compile("class Point {\n\
+		field int x, y;\n\
+		static int count;\n\
+		constructor Point new(int ax, int ay) { let x = ax; let y = ay; let count = count + 1; return this; }\n\
+		method int dot(Point other) { return (x * other.getX()) + (y * other.getY()); }\n\
+		method int getX() { return x; }\n\
+		function void fill(Array a, int n) {\n\
+		    var int i;\n\
+		    while (i < n) { let a[i] = a[i - 1] + 1; let i = i + 1; }\n\
+		    if (~(n = 0)) { do Output.printString(\"ok\"); } else { return; }\n\
+		    return;\n\
+		}\n\
+		}").unwrap();
+	let lines: Vec<&str> = code.lines().map(|l| l.trim()).collect();
+
+	let new = lines.iter().position(|l| *l == "function Point.new 0").unwrap();
+	assert_eq!(lines[new..new + 13], [
+		"function Point.new 0",
+		"push constant 2", "call Memory.alloc 1", "pop pointer 0",
+		"push argument 0", "pop this 0",
+		"push argument 1", "pop this 1",
+		"push static 0", "push constant 1", "add", "pop static 0",
+		"push pointer 0",
+	]);
+
+	let dot = lines.iter().position(|l| *l == "function Point.dot 0").unwrap();
+	assert_eq!(lines[dot..dot + 8], [
+		"function Point.dot 0", "push argument 0", "pop pointer 0",
+		"push this 0", "push argument 1", "call Point.getX 1", "call Math.multiply 2", "push this 1",
+	]);
+
+	let fill = lines.iter().position(|l| *l == "function Point.fill 1").unwrap();
+	assert_eq!(lines[fill + 1..fill + 8], ["label WHILE_EXP0", "push local 0", "push argument 1", "lt", "not", "if-goto WHILE_END0", "push argument 0"]);
+	assert!(code.contains("    push constant 2\n    call String.new 1\n    push constant 111\n    call String.appendChar 2\n"));
+	assert!(code.contains("    pop temp 0\n    pop pointer 1\n    push temp 0\n    pop that 0\n"));
+	assert!(code.contains("    label IF_FALSE1\n    push constant 0\n    return\n    label IF_END1\n"));
+}
+
+/// FEATURE
+/// As a student,
+/// I want mistakes the compiler can find reported with their line.
+#[test]
+fn report_compile_errors() {
+	let error = |body: &str| compiler::compile(&format!("class Main {{\n field int f;\n{}\n}}", body)).unwrap_err();
+
+	assert_eq!(error("function void main() { let y = 1; return; }"), "line 3: undefined variable y");
+	assert_eq!(error("function int main() { return f; }"), "line 3: field f can't be used in a function");
+	assert_eq!(error("function Main main() { return this; }"), "line 3: this can't be used in a function");
+	assert_eq!(error("function void main() { do run(); return; }"), "line 3: method run can't be called from a function without an object");
+	assert_eq!(error("function void main(int a, int a) { return; }"), "line 3: a is already declared");
+}
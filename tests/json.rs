@@ -0,0 +1,27 @@
+extern crate hack;
+use hack::json::Json;
+
+/// FEATURE
+/// As a tool author,
+/// I want JSON parsed and printed back unchanged.
+#[test]
+fn parse_and_print() {
+	let text = "{\"id\":1,\"params\":{\"text\":\"@R0\\n\\\"D=M\\\"\",\"list\":[true,false,null,-2.5]}}";
+
+	let json = Json::parse(text).unwrap();
+
+	assert_eq!(json.path(&["params", "text"]).as_str(), Some("@R0\n\"D=M\""));
+	assert_eq!(json.get("id").as_i64(), Some(1));
+	assert_eq!(json.to_string(), text);
+}
+
+/// FEATURE
+/// As a tool author,
+/// I want escaped characters decoded and malformed input rejected.
+#[test]
+fn escapes_and_errors() {
+	assert_eq!(Json::parse("\"\\u00e9\\ud83d\\ude00\"").unwrap(), Json::string("é😀"));
+	assert!(Json::parse("{\"a\":1,}").is_err());
+	assert!(Json::parse("[1 2]").is_err());
+	assert!(Json::parse("{} x").is_err());
+}
@@ -0,0 +1,150 @@
+extern crate hack;
+use hack::assembler::Assembler;
+use hack::archive::Archive;
+use hack::linker::Linker;
+use hack::object::{ObjectModule, Relocation};
+
+use std::fs;
+
+/// FEATURE
+/// As a user,
+/// I want to assemble modules separately and link them into one program.
+#[test]
+fn assemble_and_link_modules() {
+	// GIVEN two modules that import each other's labels
+	let mut linker = Linker::new();
+	for name in &["LinkMain", "LinkMult"] {
+		// WHEN I assemble each one to an object file
+		let f_in = format!("tests/fixtures/{}.asm", name);
+		let f_out = format!("tests/fixtures/{}.o", name);
+		let mut assembler = Assembler::new(&f_in);
+		assembler.set_object(true);
+		assembler.translate();
+
+		linker.add_module(name, ObjectModule::load(&f_out).unwrap());
+		fs::remove_file(&f_out).unwrap();
+	}
+
+	// AND link them
+	let program = linker.link().unwrap();
+
+	// THEN the second module is placed after the first
+	assert_eq!(program.len(), 8 + 20);
+	assert_eq!(program[4], 8); // @MULT
+	assert_eq!(program[6], 6); // @RETURN in LinkMain
+	assert_eq!(program[26], 6); // @RETURN in LinkMult
+	assert_eq!(program[24], 14); // @LOOP is relocated
+
+	// AND variables are shared across modules, starting at 16
+	assert_eq!(program[2], 16); // @x in LinkMain
+	assert_eq!(program[18], 16); // @x in LinkMult
+	assert_eq!(program[12], 17); // @n
+	assert_eq!(program[8], 2); // @R2 stays absolute
+}
+
+/// FEATURE
+/// As a user,
+/// I want unresolved and duplicate symbols to be reported.
+#[test]
+fn report_unresolved_and_duplicate_symbols() {
+	let mut a = ObjectModule::new();
+	a.exports.push(("START".to_string(), 0));
+	a.imports.push("MISSING".to_string());
+	a.push(0, Relocation::Import("MISSING".to_string()));
+
+	let mut b = ObjectModule::new();
+	b.exports.push(("START".to_string(), 0));
+	b.push(0, Relocation::Label);
+
+	// a variable doesn't silently become another module's label
+	let mut c = ObjectModule::new();
+	c.push(0, Relocation::Variable("START".to_string()));
+	c.push(0, Relocation::Variable("START".to_string()));
+
+	let mut linker = Linker::new();
+	linker.add_module("a.o", a);
+	linker.add_module("b.o", b);
+	linker.add_module("c.o", c);
+
+	let errors = linker.link().unwrap_err();
+	assert_eq!(errors, vec![
+		"duplicate symbol START exported by a.o and b.o".to_string(),
+		"unresolved symbol MISSING imported by a.o".to_string(),
+		"START is a variable in c.o but a label exported by a.o; import it with .import START".to_string(),
+	]);
+}
+
+/// FEATURE
+/// As a user,
+/// I want object files to round trip through their textual format.
+#[test]
+fn object_format_round_trip() {
+	let mut module = ObjectModule::new();
+	module.exports.push(("MAIN".to_string(), 0));
+	module.imports.push("MULT".to_string());
+	module.push(3, Relocation::Label);
+	module.push(0, Relocation::Import("MULT".to_string()));
+	module.push(0, Relocation::Variable("i".to_string()));
+	module.push(0xEA87, Relocation::Absolute);
+
+	assert_eq!(ObjectModule::parse(&module.to_string()).unwrap(), module);
+	assert!(ObjectModule::parse("0000000000000000\n").is_err());
+}
+
+/// FEATURE
+/// As a user,
+/// I want the linker to pull in only the archive members my program needs.
+#[test]
+fn link_only_needed_archive_members() {
+	// GIVEN an archive with a member that needs another, and one that isn't used
+	let mut mult = ObjectModule::new();
+	mult.exports.push(("MULT".to_string(), 0));
+	mult.imports.push("ADD".to_string());
+	mult.push(0, Relocation::Import("ADD".to_string()));
+
+	let mut add = ObjectModule::new();
+	add.exports.push(("ADD".to_string(), 0));
+	add.push(0, Relocation::Label);
+
+	let mut screen = ObjectModule::new();
+	screen.exports.push(("DRAW".to_string(), 0));
+	screen.push(0, Relocation::Label);
+
+	let mut library = Archive::new();
+	library.add_member("screen.o", screen);
+	library.add_member("mult.o", mult);
+	library.add_member("add.o", add);
+	let library = Archive::parse(&library.to_string()).unwrap();
+
+	// WHEN I link a program that calls MULT against it
+	let mut main = ObjectModule::new();
+	main.imports.push("MULT".to_string());
+	main.push(0, Relocation::Import("MULT".to_string()));
+	let mut linker = Linker::new();
+	linker.add_module("main.o", main);
+	linker.add_archive("lib.a", library);
+
+	// THEN MULT and its dependency ADD are linked, but not DRAW
+	let names: Vec<String> = linker.selected_modules().into_iter().map(|(name, _)| name).collect();
+	assert_eq!(names, vec!["main.o", "lib.a(mult.o)", "lib.a(add.o)"]);
+	assert_eq!(linker.link().unwrap(), vec![1, 2, 2]);
+}
+
+/// FEATURE
+/// As a user,
+/// I want an archive whose index doesn't match its members to be rejected.
+#[test]
+fn reject_stale_archive_index() {
+	let mut math = ObjectModule::new();
+	math.exports.push(("MULT".to_string(), 0));
+	math.push(0, Relocation::Label);
+	let mut library = Archive::new();
+	library.add_member("math.o", math);
+	let text = library.to_string();
+	assert_eq!(Archive::parse(&text).unwrap().find("MULT"), Some(0));
+
+	let missing = text.replace("index MULT math.o\n", "");
+	assert_eq!(Archive::parse(&missing), Err("stale index: math.o exports MULT, which it doesn't list; rebuild the archive with hack ar".to_string()));
+	let extra = text.replace("index MULT math.o\n", "index MULT math.o\nindex DIV math.o\n");
+	assert_eq!(Archive::parse(&extra), Err("stale index: it lists DIV in math.o, which doesn't export it; rebuild the archive with hack ar".to_string()));
+}
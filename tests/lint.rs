@@ -0,0 +1,68 @@
+extern crate hack;
+use hack::assembler::Assembler;
+use hack::diagnostic::Diagnostic;
+use hack::lint;
+use hack::preprocessor::SourceLine;
+
+fn lint(source: &str) -> Vec<Diagnostic> {
+	let lines: Vec<SourceLine> = source.lines().enumerate().map(|(i, text)| SourceLine::new(i + 1, text)).collect();
+	lint::lint(&lines, &[])
+}
+
+/// FEATURE
+/// As a user,
+/// I want to be warned about code patterns that are likely bugs.
+#[test]
+fn warn_about_suspicious_patterns() {
+	// GIVEN a program with each of the suspicious patterns
+	let mut assembler = Assembler::new("tests/fixtures/Lint.asm");
+
+	// WHEN I lint it
+	let diagnostics = lint::lint(&assembler.read_source().unwrap(), assembler.imports());
+
+	// THEN each rule reports its line, except where it is allowed
+	assert_eq!(diagnostics, vec![
+		Diagnostic::warning(5, "variable loop differs only in case from label LOOP [case-mismatch]"),
+		Diagnostic::warning(8, "A=M overwrites A loaded with label LOOP before the jump [clobbered-jump-target]"),
+		Diagnostic::warning(10, "jump to variable ret, which is a RAM address; use A=M to jump to the address it holds [jump-to-variable]"),
+		Diagnostic::warning(20, "variable sp differs only in case from predefined symbol SP [case-mismatch]"),
+		Diagnostic::warning(24, "AM=M+1 reads and writes M at the address A held before this instruction [am-read-write]"),
+	]);
+
+	// AND pushing and popping through a pointer is fine
+	assert_eq!(lint("@SP\nAM=M+1\nA=A-1\nM=D\n@R13\nAM=M-1\nD=M\n"), vec![]);
+	let lines = vec![SourceLine::expanded(1, "@SP", "POP D"), SourceLine::expanded(1, "AM=M-1", "POP D"), SourceLine::expanded(1, "D=M", "POP D")];
+	assert_eq!(lint::lint(&lines, &[]), vec![]);
+}
+
+/// FEATURE
+/// As a user,
+/// I want rules suppressed only for the line they are allowed on.
+#[test]
+fn allow_rules_per_line() {
+	let source = "@x // hack-lint: allow(jump-to-variable, am-read-write)\n0;JMP\n@x\n0;JMP\n";
+
+	assert_eq!(lint(source), vec![
+		Diagnostic::warning(3, "jump to variable x, which is a RAM address; use A=M to jump to the address it holds [jump-to-variable]"),
+	]);
+}
+
+/// FEATURE
+/// As a user,
+/// I want to be told about misspelt rule names.
+#[test]
+fn warn_about_unknown_rules() {
+	assert_eq!(lint("// hack-lint: allow(jump-to-varaible)\n@R0\nM=0\n"), vec![
+		Diagnostic::warning(1, "unknown lint rule jump-to-varaible"),
+	]);
+}
+
+/// FEATURE
+/// As a user,
+/// I don't want warnings about jumps to labels imported from other modules.
+#[test]
+fn imports_are_labels() {
+	let lines = vec![SourceLine::new(1, "@mult"), SourceLine::new(2, "0;JMP")];
+
+	assert!(lint::lint(&lines, &["mult".to_string()]).is_empty());
+}
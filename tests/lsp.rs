@@ -0,0 +1,146 @@
+extern crate hack;
+use hack::json::Json;
+use hack::lsp::{self, Server};
+
+use std::io::Cursor;
+
+const URI: &str = "file:///Prog.asm";
+const SOURCE: &str = "@i\nM=0\n(LOOP)\n@i\nM=M+1\n@LOOP\n0;JMP\n";
+
+fn open(server: &mut Server, text: &str) -> Vec<Json> {
+	server.handle(&Json::object(vec![
+		("jsonrpc", Json::from("2.0")),
+		("method", Json::from("textDocument/didOpen")),
+		("params", Json::object(vec![("textDocument", Json::object(vec![("uri", Json::from(URI)), ("text", Json::from(text))]))])),
+	]))
+}
+
+fn request(server: &mut Server, method: &str, line: usize, character: usize, extra: Vec<(&str, Json)>) -> Json {
+	let mut params = vec![
+		("textDocument", Json::object(vec![("uri", Json::from(URI))])),
+		("position", Json::object(vec![("line", Json::from(line)), ("character", Json::from(character))])),
+	];
+	params.extend(extra);
+	let replies = server.handle(&Json::object(vec![
+		("jsonrpc", Json::from("2.0")),
+		("id", Json::from(1i64)),
+		("method", Json::from(method)),
+		("params", Json::object(params)),
+	]));
+	replies[0].get("result").clone()
+}
+
+fn range(line: i64, start: i64, end: i64) -> String {
+	format!("{{\"start\":{{\"line\":{0},\"character\":{1}}},\"end\":{{\"line\":{0},\"character\":{2}}}}}", line, start, end)
+}
+
+/// FEATURE
+/// As a user,
+/// I want errors and warnings shown in my editor as I type.
+#[test]
+fn publish_diagnostics() {
+	let mut server = Server::new();
+
+	// WHEN I open a program with an unknown comp
+	let replies = open(&mut server, "@R0\nD=Q\n");
+
+	// THEN the error is published for its line
+	assert_eq!(replies[0].path(&["params", "diagnostics"]).to_string(), format!(
+		"[{{\"range\":{},\"severity\":1,\"source\":\"hack\",\"message\":\"unknown comp Q\"}}]", range(1, 0, 3)));
+
+	// AND the range ends where my editor ends the line
+	let replies = open(&mut server, "@R0\nD=Q // é 𝄞\n");
+	assert_eq!(replies[0].path(&["params", "diagnostics"]).as_array()[0].get("range").to_string(), range(1, 0, 11));
+
+	// AND directives are read as the assembler reads them
+	assert!(lsp::diagnostics(".import mult\n@mult\n0;JMP\n").is_empty());
+	assert_eq!(lsp::diagnostics(".importx mult\n@mult\n0;JMP\n")[0].message, "unknown directive .importx");
+}
+
+/// FEATURE
+/// As a user,
+/// I want to jump to a label's definition and find where it is used.
+#[test]
+fn definition_and_references() {
+	let mut server = Server::new();
+	open(&mut server, SOURCE);
+
+	let location = request(&mut server, "textDocument/definition", 5, 2, vec![]);
+	assert_eq!(location.get("range").to_string(), range(2, 1, 5));
+
+	let references = request(&mut server, "textDocument/references", 0, 1, vec![
+		("context", Json::object(vec![("includeDeclaration", Json::from(true))])),
+	]);
+	let lines: Vec<i64> = references.as_array().iter().map(|r| r.path(&["range", "start", "line"]).as_i64().unwrap()).collect();
+	assert_eq!(lines, vec![0, 3]);
+}
+
+/// FEATURE
+/// As a user,
+/// I want to rename a label everywhere it appears, and see addresses and encodings on hover.
+#[test]
+fn rename_and_hover() {
+	let mut server = Server::new();
+	open(&mut server, SOURCE);
+
+	let edit = request(&mut server, "textDocument/rename", 2, 2, vec![("newName", Json::from("AGAIN"))]);
+	let edits = edit.path(&["changes", URI]).as_array();
+	assert_eq!(edits.len(), 2);
+	assert!(edits.iter().all(|e| e.get("newText").as_str() == Some("AGAIN")));
+
+	let hover = request(&mut server, "textDocument/hover", 5, 2, vec![]);
+	assert_eq!(hover.path(&["contents", "value"]).as_str(), Some("label `LOOP`: ROM 2"));
+	let hover = request(&mut server, "textDocument/hover", 4, 0, vec![]);
+	assert_eq!(hover.path(&["contents", "value"]).as_str(), Some("ROM 3: `M=M+1` = `1111110111001000`"));
+
+	let symbols = request(&mut server, "textDocument/documentSymbol", 0, 0, vec![]);
+	let names: Vec<(&str, &str)> = symbols.as_array().iter().map(|s| (s.get("name").as_str().unwrap(), s.get("detail").as_str().unwrap())).collect();
+	assert_eq!(names, vec![("i", "RAM 16"), ("LOOP", "ROM 2")]);
+}
+
+/// FEATURE
+/// As a user writing comments in my own language,
+/// I want columns counted as my editor counts them, in UTF-16 code units.
+#[test]
+fn count_columns_in_utf16() {
+	let mut server = Server::new();
+	open(&mut server, "// é 𝄞\n(LOOP)\nLOAD é, LOOP\n");
+
+	// a column inside a character doesn't crash the server
+	let items = request(&mut server, "textDocument/completion", 0, 4, vec![]);
+	assert!(!items.as_array().is_empty());
+	request(&mut server, "textDocument/completion", 0, 6, vec![]);
+
+	// LOOP starts after 8 UTF-16 code units, but 9 bytes
+	let references = request(&mut server, "textDocument/references", 1, 2, vec![
+		("context", Json::object(vec![("includeDeclaration", Json::from(false))])),
+	]);
+	assert_eq!(references.as_array()[0].get("range").to_string(), range(2, 8, 12));
+	let location = request(&mut server, "textDocument/definition", 2, 9, vec![]);
+	assert_eq!(location.get("range").to_string(), range(1, 1, 5));
+}
+
+/// FEATURE
+/// As an editor,
+/// I want to talk to the server with framed JSON-RPC messages over stdio.
+#[test]
+fn serve_over_streams() {
+	let messages = [
+		"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"initialize\",\"params\":{}}",
+		"{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"shutdown\"}",
+		"{\"jsonrpc\":\"2.0\",\"method\":\"exit\"}",
+	];
+	let input: String = messages.iter().map(|m| format!("Content-Length: {}\r\n\r\n{}", m.len(), m)).collect();
+
+	let mut server = Server::new();
+	let mut output = Vec::new();
+	server.run(&mut Cursor::new(input), &mut output).unwrap();
+
+	let mut replies = Cursor::new(output);
+	let initialize = lsp::read_message(&mut replies).unwrap().unwrap();
+	assert_eq!(initialize.path(&["result", "capabilities", "hoverProvider"]), &Json::Bool(true));
+	let shutdown = lsp::read_message(&mut replies).unwrap().unwrap();
+	assert_eq!(shutdown.to_string(), "{\"jsonrpc\":\"2.0\",\"id\":2,\"result\":null}");
+	assert_eq!(lsp::read_message(&mut replies).unwrap(), None);
+	assert!(server.is_shut_down());
+}
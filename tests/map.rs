@@ -0,0 +1,93 @@
+extern crate hack;
+use hack::assembler::Assembler;
+use hack::linker::Linker;
+use hack::map::{MemoryMap, Region};
+use hack::object::{ObjectModule, Relocation};
+
+use std::fs;
+
+/// FEATURE
+/// As a user,
+/// I want a map file showing the size of every label region and where variables live.
+#[test]
+fn translate_with_map_file() {
+	// GIVEN a program with three labels and two variables
+	let f_in = "tests/fixtures/Pseudo.asm";
+
+	// WHEN I run the assembler with a map
+	let mut assembler = Assembler::new(f_in);
+	assembler.set_map(true);
+	assembler.translate();
+
+	// THEN the map lists the regions, variables and utilisation
+	let map = fs::read_to_string("tests/fixtures/Pseudo.map").unwrap();
+	assert_eq!(map, "ROM\n\
+		\x20 start   size  region\n\
+		\x20 00000      4  (start)\n\
+		\x20 00004      9  LOOP\n\
+		\x20 00013      2  END\n\
+		\nRAM\n\
+		\x20  addr  variable\n\
+		\x20 00016  i\n\
+		\nSummary\n\
+		\x20 ROM     15 / 32768 words (0.0%)\n\
+		\x20 RAM      1 / 16368 variable words (0.0%)\n");
+
+	fs::remove_file("tests/fixtures/Pseudo.map").unwrap();
+	fs::remove_file("tests/fixtures/Pseudo.hack").unwrap();
+}
+
+/// FEATURE
+/// As a user,
+/// I want the linker map to show each module and the labels it exports.
+#[test]
+fn link_with_map() {
+	let mut a = ObjectModule::new();
+	a.push(0, Relocation::Variable("i".to_string()));
+	a.push(0, Relocation::Absolute);
+
+	let mut b = ObjectModule::new();
+	b.exports.push(("MULT".to_string(), 1));
+	for _ in 0..3 {
+		b.push(0, Relocation::Variable("j".to_string()));
+	}
+
+	let mut linker = Linker::new();
+	linker.add_module("a.o", a);
+	linker.add_module("b.o", b);
+	let (_, map) = linker.link_with_map().unwrap();
+
+	let region = |name: &str, module: &str, start, size| Region {
+		name: name.to_string(),
+		module: Some(module.to_string()),
+		start,
+		size,
+	};
+	assert_eq!(map.regions, vec![
+		region("(start)", "a.o", 0, 2),
+		region("(start)", "b.o", 2, 1),
+		region("MULT", "b.o", 3, 2),
+	]);
+	assert_eq!(map.variables, vec![("i".to_string(), 16), ("j".to_string(), 17)]);
+	assert_eq!(map.rom_size, 5);
+}
+
+/// FEATURE
+/// As a user,
+/// I want a hard error when my program doesn't fit in ROM.
+#[test]
+fn program_exceeding_rom_is_an_error() {
+	let mut module = ObjectModule::new();
+	for _ in 0..32769 {
+		module.push(0, Relocation::Absolute);
+	}
+	let mut linker = Linker::new();
+	linker.add_module("big.o", module);
+
+	let errors = linker.link().unwrap_err();
+	assert_eq!(errors, vec!["program is 32769 words, exceeding the ROM capacity of 32768 words by 1".to_string()]);
+
+	let mut map = MemoryMap::new();
+	map.rom_size = 32768;
+	assert!(map.check_capacity().is_ok());
+}
@@ -0,0 +1,85 @@
+extern crate hack;
+use hack::optimizer;
+use hack::preprocessor::SourceLine;
+
+fn optimize(source: &str) -> (Vec<String>, usize) {
+	let lines: Vec<SourceLine> = source.lines().enumerate().map(|(i, text)| SourceLine::new(i + 1, text)).collect();
+	let (lines, saved) = optimizer::optimize(lines);
+	(lines.into_iter().map(|line| line.text).collect(), saved)
+}
+
+/// FEATURE
+/// As a user,
+/// I want A-loads whose value is already in A, and dead stores, to be removed.
+#[test]
+fn remove_redundant_loads_and_stores() {
+	// GIVEN code that reloads @SP and stores back the value it just read
+	let source = "@SP\nM=M+1\n@SP\nD=M\nM=D\n@x\n@y\nD=A\nD=M\n";
+
+	// WHEN I optimise it
+	let (lines, saved) = optimize(source);
+
+	// THEN only the instructions that matter remain
+	assert_eq!(lines, vec!["@SP", "M=M+1", "@y", "D=M"]);
+	assert_eq!(saved, 5);
+}
+
+/// FEATURE
+/// As a user,
+/// I want jumps to the next instruction to be removed and jump chains shortened.
+#[test]
+fn remove_jumps_to_next_and_chains() {
+	let source = "@NEXT\n0;JMP\n(NEXT)\n@ONE\nD;JGT\n@END\n0;JMP\n(ONE)\n@TWO\n0;JMP\n(TWO)\n@END\n0;JMP\n(END)\n@END\n0;JMP\n";
+
+	let (lines, saved) = optimize(source);
+
+	// ONE and TWO only jump on to END, and A already holds END after D;JGT
+	assert_eq!(lines, vec!["(NEXT)", "@END", "D;JGT", "0;JMP", "(ONE)", "(TWO)", "(END)", "@END", "0;JMP"]);
+	assert_eq!(saved, 7);
+}
+
+/// FEATURE
+/// As a user,
+/// I want the optimiser to leave code alone when A or D is still needed.
+#[test]
+fn keep_live_values() {
+	// a label resets what is known about A, and D=A is live because M=D reads it
+	let source = "@x\n(L)\n@x\nD=A\n@y\nM=D\n@L\n0;JMP\n(L2)\nD=A\n";
+
+	let (lines, saved) = optimize(source);
+
+	assert_eq!(saved, 0);
+	assert_eq!(lines.len(), 10);
+
+	// M=D writes at the address the jump left in A, even after D=1
+	let (lines, saved) = optimize("@L\n0;JMP\n(L)\nD=1\nM=D\n");
+	assert_eq!(saved, 0);
+	assert_eq!(lines, vec!["@L", "0;JMP", "(L)", "D=1", "M=D"]);
+}
+
+/// FEATURE
+/// As a user,
+/// I want label addresses to be recomputed after optimising with -O.
+#[test]
+fn translate_optimized() {
+	use hack::assembler::Assembler;
+	use std::fs;
+
+	// GIVEN a program with redundant instructions
+	let mut assembler = Assembler::new("tests/fixtures/Optimize.asm");
+
+	// WHEN I run the assembler with the optimiser
+	assembler.set_optimize(true);
+	assembler.translate();
+
+	// THEN the program shrinks and the labels point at their new addresses
+	let actual = fs::read_to_string("tests/fixtures/Optimize.hack").unwrap();
+	let lines: Vec<&str> = actual.lines().collect();
+	assert_eq!(assembler.words_saved(), 4);
+	assert_eq!(lines.len(), 21);
+	assert_eq!(lines[8], "0000000000010011"); // @END now jumps straight to DONE, at 19
+	assert_eq!(lines[17], "0000000000000100"); // @LOOP
+	assert_eq!(lines[19], "0000000000010011"); // @DONE
+
+	fs::remove_file("tests/fixtures/Optimize.hack").unwrap();
+}
@@ -0,0 +1,98 @@
+extern crate hack;
+use hack::assembler::Assembler;
+use hack::preprocessor::{self, Preprocessor, SourceLine};
+
+use std::fs;
+
+/// FEATURE
+/// As a user,
+/// I want lines excluded by conditional directives to be left out of the program.
+#[test]
+fn conditional_directives_select_lines() {
+	// GIVEN a program with .if/.elif/.else/.endif blocks
+	let source = ".define LEVEL 2\n\
+		.if LEVEL == 1\n@1\n.elif LEVEL == 2\n@2\n.else\n@3\n.endif\n\
+		.ifndef LEVEL\n@4\n.endif\n";
+
+	// WHEN I preprocess it
+	let lines = Preprocessor::new().process(source).unwrap();
+
+	// THEN only the taken branch remains, with its original line number
+	assert_eq!(lines, vec![SourceLine::new(5, "@2")]);
+}
+
+/// FEATURE
+/// As a user,
+/// I want nested blocks inside an excluded branch to stay excluded.
+#[test]
+fn nested_conditionals_inside_excluded_branch() {
+	let source = ".if 0\n.if 1\n@1\n.else\n@2\n.endif\n.else\n@3\n.endif\n";
+	let lines = Preprocessor::new().process(source).unwrap();
+	assert_eq!(lines, vec![SourceLine::new(8, "@3")]);
+}
+
+/// FEATURE
+/// As a user,
+/// I want unbalanced directives and undefined constants to be reported.
+#[test]
+fn malformed_directives_are_errors() {
+	assert!(Preprocessor::new().process(".if 1\n@1\n").is_err());
+	assert!(Preprocessor::new().process(".endif\n").is_err());
+	assert!(Preprocessor::new().process(".if 1\n.else\n.else\n.endif\n").is_err());
+	assert!(Preprocessor::new().process(".if MISSING\n.endif\n").is_err());
+}
+
+/// FEATURE
+/// As a user,
+/// I want constants defined on the command line to select what gets assembled.
+#[test]
+fn translate_with_command_line_define() {
+	// GIVEN a program that assembles differently in debug builds
+	let f_in = "tests/fixtures/Conditional.asm";
+	let f_out = "tests/fixtures/Conditional.hack";
+
+	// WHEN I run the assembler with -D DEBUG
+	let mut assembler = Assembler::new(f_in);
+	assembler.define("DEBUG", 1);
+	assembler.translate();
+
+	// THEN the excluded lines take up no ROM, so @BASE_VALUE is a variable at 16
+	let actual = fs::read_to_string(f_out).unwrap();
+	let expected = "0000000000000101\n\
+		1110110000010000\n\
+		0000000000010000\n\
+		0000000000000010\n\
+		1110000010010000\n\
+		0000000000000000\n\
+		1110001100001000\n";
+	assert_eq!(actual, expected);
+
+	fs::remove_file(f_out).unwrap();
+}
+
+/// FEATURE
+/// As a user,
+/// I want -D to override a constant the source defines, to try other settings without editing it.
+#[test]
+fn command_line_define_overrides_source() {
+	let source = ".define LEVEL 2\n.if LEVEL == 3\n@3\n.else\n@2\n.endif\n";
+
+	let mut preprocessor = Preprocessor::new();
+	preprocessor.define("LEVEL", 3);
+	assert_eq!(preprocessor.process(source).unwrap(), vec![SourceLine::new(3, "@3")]);
+	assert_eq!(preprocessor.value("LEVEL"), Some(3));
+
+	assert_eq!(Preprocessor::new().process(source).unwrap(), vec![SourceLine::new(5, "@2")]);
+}
+
+/// FEATURE
+/// As a user,
+/// I want a -D that isn't NAME or NAME=value to be refused.
+#[test]
+fn parse_command_line_defines() {
+	assert_eq!(preprocessor::parse_define("DEBUG"), Ok(("DEBUG".to_string(), 1)));
+	assert_eq!(preprocessor::parse_define("LEVEL=-2"), Ok(("LEVEL".to_string(), -2)));
+	assert_eq!(preprocessor::parse_define("LEVEL=high"), Err("invalid value for LEVEL: high".to_string()));
+	assert_eq!(preprocessor::parse_define("2FAST=1"), Err("invalid constant name '2FAST'".to_string()));
+	assert_eq!(preprocessor::parse_define(""), Err("invalid constant name ''".to_string()));
+}
@@ -0,0 +1,90 @@
+extern crate hack;
+use hack::cpu::Cpu;
+use hack::profiler::{End, Profile};
+use hack::program::Program;
+use hack::sourcemap::SourceMap;
+
+use std::fs;
+
+fn mult() -> (Program, Profile) {
+	let source = fs::read_to_string("tests/fixtures/Mult.asm").unwrap();
+	let program = Program::from_source(&source, true).unwrap();
+	let mut cpu = Cpu::new();
+	cpu.ram[0] = 6;
+	cpu.ram[1] = 3;
+	let profile = Profile::run(&program, &mut cpu, 1000);
+	assert_eq!(cpu.ram[2], 18);
+	(program, profile)
+}
+
+/// FEATURE
+/// As a student,
+/// I want to know which parts of my program the cycles are spent in.
+#[test]
+fn count_cycles_per_region() {
+	let (program, profile) = mult();
+
+	assert_eq!(profile.end, End::Halted);
+	assert_eq!(profile.cycles, 4 + 4 * 6 + 3 * 8 + 2);
+	assert_eq!(profile.counts[4], 4);
+	assert_eq!(profile.regions(&program), vec![
+		("(start)".to_string(), 0, 4, 4),
+		("LOOP".to_string(), 4, 14, 48),
+		("END".to_string(), 18, 2, 2),
+	]);
+
+	let report = profile.report(&program, &SourceMap::from_program(&program, "Mult.asm"), "Mult");
+	assert!(report.starts_with("Profile of Mult: 54 cycles, halted\nCoverage: 20 / 20 instructions executed (100.0%)\n"));
+	assert!(report.contains("  00004     14          48   88.9%  LOOP\n"));
+	assert!(report.contains("  00004      7           4    7.4%  @R1\n"));
+}
+
+/// FEATURE
+/// As a student,
+/// I want to see the code my test inputs never reach.
+#[test]
+fn report_never_executed_code() {
+	let program = Program::from_source("@R0\nD=M\n@SKIP\nD;JEQ\n@R1\nM=1\n@R2\nM=1\n(SKIP)\n@SKIP\n0;JMP\n", false).unwrap();
+	let map = SourceMap::from_program(&program, "Skip.asm");
+	let profile = Profile::run(&program, &mut Cpu::new(), 1000);
+
+	assert_eq!(profile.covered(), 6);
+	assert!(profile.report(&program, &map, "Skip").ends_with("Never executed\n  ROM 00004-00007  lines 5-8\n"));
+
+	let mut cpu = Cpu::new();
+	cpu.ram[0] = 1;
+	let profile = Profile::run(&program, &mut cpu, 1000);
+	assert!(profile.report(&program, &map, "Skip").ends_with("Never executed\n  nothing, every instruction ran\n"));
+}
+
+/// FEATURE
+/// As a student,
+/// I want to profile programs that never halt, such as games.
+#[test]
+fn stop_at_the_cycle_limit() {
+	let program = Program::from_source("(LOOP)\n@i\nM=M+1\n@LOOP\n0;JMP\n", false).unwrap();
+	let profile = Profile::run(&program, &mut Cpu::new(), 10);
+
+	assert_eq!(profile.end, End::Limit);
+	assert_eq!(profile.counts, vec![3, 3, 2, 2]);
+	assert!(profile.report(&program, &SourceMap::from_program(&program, "Loop.asm"), "Loop").starts_with("Profile of Loop: 10 cycles, stopped after 10 cycles without halting\n"));
+
+	let program = Program::from_source("@i\nM=1\n", false).unwrap();
+	assert_eq!(Profile::run(&program, &mut Cpu::new(), 10).end, End::Ended);
+}
+
+/// FEATURE
+/// As a student,
+/// I want a flame graph of where the cycles went.
+#[test]
+fn write_collapsed_stacks() {
+	let (program, profile) = mult();
+	let collapsed = profile.collapsed(&program, &SourceMap::from_program(&program, "Mult.asm"), "Mult");
+	let lines: Vec<&str> = collapsed.lines().collect();
+
+	assert_eq!(lines[0], "Mult;(start);line 2 1");
+	assert!(lines.contains(&"Mult;LOOP;line 18 3"));
+	assert_eq!(lines.last(), Some(&"Mult;END;line 23 1"));
+	let total: u64 = lines.iter().map(|l| l.rsplit(' ').next().unwrap().parse::<u64>().unwrap()).sum();
+	assert_eq!(total, profile.cycles);
+}
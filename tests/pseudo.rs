@@ -0,0 +1,66 @@
+extern crate hack;
+use hack::assembler::Assembler;
+use hack::preprocessor::SourceLine;
+use hack::pseudo;
+
+use std::fs;
+
+/// FEATURE
+/// As a user,
+/// I want pseudo-instructions to expand into canonical Hack instructions.
+#[test]
+fn pseudo_instructions_expand() {
+	let lines = vec![
+		SourceLine::new(1, "LOAD D, 42"),
+		SourceLine::new(2, "IF D<=0 GOTO END // done"),
+		SourceLine::new(3, "POP D"),
+		SourceLine::new(4, "D=M"),
+	];
+
+	let expanded: Vec<String> = pseudo::expand(lines).unwrap().into_iter().map(|l| l.text).collect();
+
+	assert_eq!(expanded, vec!["@42", "D=A", "@END", "D;JLE", "@SP", "AM=M-1", "D=M", "D=M"]);
+}
+
+/// FEATURE
+/// As a user,
+/// I want malformed pseudo-instructions to be reported.
+#[test]
+fn malformed_pseudo_instructions_are_errors() {
+	assert!(pseudo::expand(vec![SourceLine::new(1, "LOAD M, 1")]).is_err());
+	assert!(pseudo::expand(vec![SourceLine::new(1, "IF D?0 GOTO END")]).is_err());
+	assert!(pseudo::expand(vec![SourceLine::new(1, "PUSH A")]).is_err());
+}
+
+/// FEATURE
+/// As a user,
+/// I want the listing file to show what each pseudo-instruction expanded to.
+#[test]
+fn translate_pseudo_instructions_with_listing() {
+	// GIVEN a program written with pseudo-instructions
+	let f_in = "tests/fixtures/Pseudo.asm";
+	let f_out = "tests/fixtures/Pseudo.hack";
+	let f_lst = "tests/fixtures/Pseudo.lst";
+
+	// WHEN I run the assembler with a listing
+	let mut assembler = Assembler::new(f_in);
+	assembler.set_listing(true);
+	assembler.translate();
+
+	// THEN labels account for the expanded instructions
+	let actual = fs::read_to_string(f_out).unwrap();
+	let lines: Vec<&str> = actual.lines().collect();
+	assert_eq!(lines.len(), 15);
+	assert_eq!(lines[13], "0000000000001101"); // @END is ROM 13
+	assert_eq!(lines[11], "0000000000000100"); // @LOOP is ROM 4
+
+	// AND the listing shows each expansion under its pseudo-instruction
+	let listing = fs::read_to_string(f_lst).unwrap();
+	assert!(listing.contains("                             2  LOAD D, 3\n\
+		00000  0000000000000011      2    + @3\n\
+		00001  1110110000010000      2    + D=A\n"));
+	assert!(listing.contains("                            11  (END)\n"));
+
+	fs::remove_file(f_out).unwrap();
+	fs::remove_file(f_lst).unwrap();
+}
@@ -0,0 +1,41 @@
+extern crate hack;
+use hack::repl::Repl;
+
+/// FEATURE
+/// As a student,
+/// I want to see how an instruction is encoded, field by field.
+#[test]
+fn show_encoding() {
+	let mut repl = Repl::new();
+
+	assert_eq!(repl.eval("D=D+M;JGT"), "D=D+M;JGT  111 1 000010 010 001   0xf091");
+	assert_eq!(repl.eval("@17"), "@17        0 000000000010001      0x0011");
+	assert_eq!(repl.eval("@SCREEN"), "@SCREEN    0 100000000000000      0x4000");
+	assert_eq!(repl.eval("D=Q"), "error: unknown comp Q");
+}
+
+/// FEATURE
+/// As a student,
+/// I want to execute instructions and see the registers change.
+#[test]
+fn execute_instructions() {
+	let mut repl = Repl::new();
+	repl.set_run(true);
+
+	repl.eval("@x");
+	repl.eval(":set M 5");
+	let output = repl.eval("MD=M+1");
+
+	assert_eq!(output.lines().last(), Some("A=16 D=6 M=6 PC=2"));
+	assert_eq!(repl.eval(":reset"), "A=0 D=0 M=0 PC=0");
+}
+
+/// FEATURE
+/// As a student,
+/// I want pseudo-instructions shown as the instructions they expand to.
+#[test]
+fn expand_pseudo_instructions() {
+	let mut repl = Repl::new();
+
+	assert_eq!(repl.eval("LOAD D, 5"), "@5         0 000000000000101      0x0005\nD=A        111 0 110000 010 000   0xec10");
+}
@@ -0,0 +1,84 @@
+extern crate hack;
+use hack::assembler::Assembler;
+use hack::preprocessor::SourceLine;
+use hack::program::Program;
+use hack::sourcemap::{Mapping, SourceMap};
+
+use std::fs;
+
+/// FEATURE
+/// As a tool author,
+/// I want a source map telling me which source line produced each ROM word.
+#[test]
+fn translate_with_source_map() {
+	// GIVEN a program using pseudo-instructions
+	let f_in = "tests/fixtures/Pseudo.asm";
+
+	// WHEN I run the assembler with a source map
+	let mut assembler = Assembler::new(f_in);
+	assembler.set_quiet(true);
+	assembler.set_source_map(true);
+	assembler.translate();
+
+	// THEN every ROM word maps back to its line, expansions to their pseudo-instruction
+	let map = SourceMap::parse(&fs::read_to_string("tests/fixtures/Pseudo.sourcemap.json").unwrap()).unwrap();
+	assert_eq!(map.file, f_in);
+	assert_eq!(map.mappings.len(), 15);
+	assert_eq!(map.lookup(0), Some(&Mapping {
+		address: 0,
+		file: f_in.to_string(),
+		line: 2,
+		column: 2,
+		expanded_from: Some("LOAD D, 3".to_string()),
+	}));
+	assert_eq!(map.lookup(2).map(|m| (m.line, m.expanded_from.clone())), Some((3, None)));
+	assert_eq!(map.addresses(f_in, 8), vec![6, 7, 8, 9]);
+	assert_eq!(map.lookup(15), None);
+
+	fs::remove_file("tests/fixtures/Pseudo.sourcemap.json").unwrap();
+	fs::remove_file("tests/fixtures/Pseudo.hack").unwrap();
+
+	// the linker moves a module, so its addresses aren't final
+	let mut assembler = Assembler::new(f_in);
+	assembler.set_quiet(true);
+	assembler.set_object(true);
+	assembler.set_source_map(true);
+	assert_eq!(assembler.try_translate(), Err(format!("{}: a source map can't be written for an object module, whose addresses change when it's linked", f_in)));
+}
+
+/// FEATURE
+/// As a tool author,
+/// I want the same source map from a program assembled in memory, and to read it back.
+#[test]
+fn build_and_read_source_map() {
+	let program = Program::from_source("@i\n    M=0\n  INC M // one\n", true).unwrap();
+	let map = SourceMap::from_program(&program, "Prog.asm");
+	let columns: Vec<(u16, usize, usize)> = map.mappings.iter().map(|m| (m.address, m.line, m.column)).collect();
+	assert_eq!(columns, vec![(0, 1, 1), (1, 2, 5), (2, 3, 3)]);
+	assert_eq!(map.mappings[2].expanded_from.as_deref(), Some("INC M // one"));
+
+	assert_eq!(SourceMap::parse(&map.to_string()), Ok(map));
+
+	// mappings needn't be in order of address
+	let map = SourceMap::parse("{\"version\":1,\"mappings\":[{\"address\":1,\"line\":4,\"column\":1},{\"address\":0,\"line\":2,\"column\":1}]}").unwrap();
+	assert_eq!(map.lookup(0).map(|m| m.line), Some(2));
+	assert_eq!(map.lookup(1).map(|m| m.line), Some(4));
+	assert_eq!(map.lookup(2), None);
+}
+
+/// FEATURE
+/// As a tool author,
+/// I want to be told when a source map can't be read.
+#[test]
+fn reject_bad_source_maps() {
+	let mut map = SourceMap::new("Prog.asm");
+	map.push(0, &SourceLine::new(1, "@0"));
+	assert!(map.to_string().starts_with("{\"version\":1,"));
+
+	assert_eq!(SourceMap::parse("{\"version\":2,\"mappings\":[]}"), Err("unsupported source map version 2".to_string()));
+	assert_eq!(SourceMap::parse("{\"mappings\":[]}"), Err("not a source map: missing version".to_string()));
+	assert_eq!(
+		SourceMap::parse("{\"version\":1,\"mappings\":[{\"address\":0,\"column\":1}]}"),
+		Err("mapping 0: missing line".to_string()),
+	);
+}
@@ -0,0 +1,60 @@
+extern crate hack;
+use hack::cpu::Cpu;
+use hack::program::Program;
+use hack::trace::{self, Step, Trace};
+
+fn record(source: &str, cycles: u64) -> Trace {
+	Trace::record(&Program::from_source(source, true).unwrap(), &mut Cpu::new(), cycles)
+}
+
+/// FEATURE
+/// As a student,
+/// I want a compact record of every cycle my program ran.
+#[test]
+fn record_a_trace() {
+	let trace = record("@i\nM=1\n(LOOP)\n@LOOP\n0;JMP\n", 100);
+
+	// stops at the halting loop
+	assert_eq!(trace.steps, vec![
+		Step { pc: 0, instruction: 16, a: 16, d: 0, write: None },
+		Step { pc: 1, instruction: 0xefc8, a: 16, d: 0, write: Some((16, 1)) },
+	]);
+	assert_eq!(trace.to_string(), "hack-trace 1\nlabel LOOP 2\nvariable i 16\n0000 0010 0010 0000\n0001 efc8 0010 0000 0010=0001\n");
+	assert_eq!(Trace::parse(&trace.to_string()), Ok(trace));
+
+	assert_eq!(record("(LOOP)\n@i\nM=M+1\n@LOOP\n0;JMP\n", 7).steps.len(), 7);
+}
+
+/// FEATURE
+/// As a student,
+/// I want to see the first cycle where two versions of my program diverge, by name.
+#[test]
+fn find_the_first_difference() {
+	let a = record("@3\nD=A\n@i\nM=D\n(LOOP)\n@i\nM=M-1\n@LOOP\nM;JGT\n", 100);
+	let b = record("@3\nD=A\n@i\nM=D\n(LOOP)\n@i\nM=M-1\n@LOOP\nM;JGE\n", 100);
+
+	assert_eq!(trace::diff(&a, &a), None);
+	assert_eq!(trace::diff(&a, &b).unwrap(), "first difference at cycle 7, in instruction\n\
+		\x20 a: PC=7 (LOOP+3) instruction=0xfc01 A=4 D=3\n\
+		\x20 b: PC=7 (LOOP+3) instruction=0xfc03 A=4 D=3\n\
+		last matching cycle 6: PC=6 (LOOP+2) instruction=0x0004 A=4 D=3");
+
+	let c = record("@3\nD=A\n@i\nM=D\n(LOOP)\n@i\nM=M-1\n@LOOP\nM;JGT\n", 5);
+	assert_eq!(trace::diff(&c, &a).unwrap(), "the traces match for 5 cycles, then only b continues:\n\
+		\x20 b: PC=5 (LOOP+1) instruction=0xfc88 A=16 D=3, RAM[16] (i) = 2");
+}
+
+/// FEATURE
+/// As a student,
+/// I want to be told when a file isn't a trace.
+#[test]
+fn reject_bad_traces() {
+	assert_eq!(Trace::parse("0000 0010 0010 0000\n"), Err("not a trace: expected 'hack-trace 1' on the first line".to_string()));
+	assert_eq!(Trace::parse("hack-trace 1\n0000 0010 zz 0000\n"), Err("line 2: can't read '0000 0010 zz 0000'".to_string()));
+	assert_eq!(Trace::parse("hack-trace 1\n0001 efc8 0010 0000 0010\n"), Err("line 2: can't read '0001 efc8 0010 0000 0010'".to_string()));
+
+	let trace = Trace::parse("hack-trace 1\nvariable i 16\n").unwrap();
+	assert_eq!(trace.name_ram(16), "RAM[16] (i)");
+	assert_eq!(trace.name_ram(16390), "RAM[16390] (SCREEN+6)");
+	assert_eq!(trace.name_ram(17), "RAM[17]");
+}
@@ -0,0 +1,57 @@
+extern crate hack;
+use hack::assembler::Assembler;
+use hack::diagnostic::Diagnostic;
+use hack::lint;
+use hack::preprocessor::SourceLine;
+
+use std::fs;
+
+fn check(source: &str) -> Vec<Diagnostic> {
+	let lines: Vec<SourceLine> = source.lines().enumerate().map(|(i, text)| SourceLine::new(i + 1, text)).collect();
+	lint::check(&lines, &[], &lint::VARIABLE_RULES)
+}
+
+/// FEATURE
+/// As a user,
+/// I want to be warned when a misspelt label becomes a new variable.
+#[test]
+fn warn_about_misspelt_symbols() {
+	// GIVEN a program with a misspelt label and a misspelt variable
+	let f_in = "tests/fixtures/Typo.asm";
+
+	// WHEN I run the assembler
+	let mut assembler = Assembler::new(f_in);
+	assembler.translate();
+
+	// THEN it suggests the symbols they are close to, and warns about the jump to a variable
+	assert_eq!(assembler.warnings(), &[
+		Diagnostic::warning(6, "label (LOOP) is never used"),
+		Diagnostic::warning(9, "jump to variable LOPP, which is a RAM address; use A=M to jump to the address it holds [jump-to-variable]"),
+		Diagnostic::warning(9, "variable LOPP is used only once; did you mean label LOOP? [possible-typo]"),
+		Diagnostic::warning(11, "variable cuont is used only once; did you mean variable count? [possible-typo]"),
+	]);
+
+	fs::remove_file("tests/fixtures/Typo.hack").unwrap();
+}
+
+/// FEATURE
+/// As a user,
+/// I don't want warnings about variables used more than once or with distinct names.
+#[test]
+fn ignore_distinct_variables() {
+	assert!(check("@sum\nM=0\n@sum\nM=M+1\n@sun\nM=0\n@sun\nD=M\n").is_empty());
+	assert!(check("@i\nM=0\n@j\nM=0\n").is_empty());
+
+	// unknown rules in allow comments are left for hack lint to report
+	assert!(check("// hack-lint: allow(jump-to-varaible)\n@i\nM=0\n").is_empty());
+}
+
+/// FEATURE
+/// As a user,
+/// I want swapped letters to count as a single typo.
+#[test]
+fn detect_swapped_letters() {
+	assert_eq!(check("(LOOP)\n@OLOP\nD=A\n"), vec![
+		Diagnostic::warning(2, "variable OLOP is used only once; did you mean label LOOP? [possible-typo]"),
+	]);
+}
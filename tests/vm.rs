@@ -0,0 +1,115 @@
+extern crate hack;
+use hack::cpu::Cpu;
+use hack::profiler::{End, Profile};
+use hack::program::Program;
+use hack::vm::{self, Translator};
+
+use std::path::Path;
+
+/// Translates `source` as `Test.vm` and runs it to the end with SP = 256
+/// and the given segment bases.
+fn run(source: &str, bases: &[(usize, u16)]) -> Cpu {
+	let mut translator = Translator::new();
+	translator.translate("Test.vm", source).unwrap();
+	let program = Program::from_source(&translator.output(), false).unwrap();
+	let mut cpu = Cpu::new();
+	cpu.ram[0] = 256;
+	for &(address, value) in bases {
+		cpu.ram[address] = value;
+	}
+	while cpu.step(&program.code) {}
+	cpu
+}
+
+/// FEATURE
+/// As a student,
+/// I want stack arithmetic and comparisons translated to Hack assembly.
+#[test]
+fn translate_arithmetic() {
+	let cpu = run("push constant 7\npush constant 8\nadd\n", &[]);
+	assert_eq!((cpu.ram[0], cpu.ram[256]), (257, 15));
+
+	let cpu = run("push constant 17\npush constant 17\neq\n\
+		push constant 892\npush constant 891\nlt\n\
+		push constant 32767\npush constant 32766\ngt\n\
+		push constant 57\npush constant 31\nsub\nneg\n\
+		push constant 82\npush constant 112\nand\n\
+		push constant 82\nnot\npush constant 1\nor // comment\n", &[]);
+	let stack: Vec<i16> = cpu.ram[256..cpu.ram[0] as usize].iter().map(|&w| w as i16).collect();
+	assert_eq!(stack, vec![-1, 0, -1, -26, 82 & 112, !82 | 1]);
+
+	// x - y overflows for operands of opposite signs
+	let cpu = run("push constant 20000\npush constant 20000\nneg\ngt\n\
+		push constant 20000\npush constant 20000\nneg\nlt\n\
+		push constant 20000\nneg\npush constant 20000\ngt\n\
+		push constant 20000\nneg\npush constant 20000\nlt\n\
+		push constant 32767\nneg\npush constant 1\nsub\npush constant 1\ngt\n\
+		push constant 5\nneg\npush constant 3\nneg\nlt\n", &[]);
+	let stack: Vec<i16> = cpu.ram[256..cpu.ram[0] as usize].iter().map(|&w| w as i16).collect();
+	assert_eq!(stack, vec![-1, 0, 0, -1, 0, -1]);
+}
+
+/// FEATURE
+/// As a student,
+/// I want every memory segment translated.
+#[test]
+fn translate_memory_segments() {
+	let cpu = run("push constant 10\npop local 0\n\
+		push constant 21\npush constant 22\npop argument 2\npop argument 1\n\
+		push constant 3030\npop pointer 0\npush constant 3040\npop pointer 1\n\
+		push constant 36\npop this 6\npush constant 42\npop that 5\n\
+		push constant 510\npop temp 6\npush constant 111\npop static 8\n\
+		push local 0\npush that 5\nadd\npush argument 1\nsub\npush this 6\npush this 6\nadd\nsub\n\
+		push temp 6\nadd\npush static 8\nadd\n", &[(1, 300), (2, 400)]);
+
+	assert_eq!(cpu.ram[300], 10);
+	assert_eq!((cpu.ram[401], cpu.ram[402]), (21, 22));
+	assert_eq!((cpu.ram[3], cpu.ram[4]), (3030, 3040));
+	assert_eq!((cpu.ram[3036], cpu.ram[3045]), (36, 42));
+	assert_eq!(cpu.ram[11], 510);
+	assert_eq!(cpu.ram[256] as i16, 10 + 42 - 21 - (36 + 36) + 510 + 111);
+	assert_eq!(cpu.ram[0], 257);
+}
+
+/// FEATURE
+/// As a student,
+/// I want a directory of VM files, with calls and returns, translated into one program.
+#[test]
+fn translate_a_directory_with_bootstrap() {
+	let (output, assembly) = vm::translate_path("tests/fixtures/Fib").unwrap();
+	assert_eq!(output, Path::new("tests/fixtures/Fib/Fib.asm"));
+	assert!(assembly.starts_with("    // bootstrap: SP = 256, call Sys.init\n    @256\n"));
+
+	// strict mode: only book instructions
+	let program = Program::from_source(&assembly, false).unwrap();
+	let mut cpu = Cpu::new();
+	assert_eq!(Profile::run(&program, &mut cpu, 100_000).end, End::Halted);
+	assert_eq!(cpu.ram[program.symbol("Sys.0").unwrap() as usize], 8);
+	assert_eq!(cpu.ram[0], 261);
+
+	let (output, assembly) = vm::translate_path("tests/fixtures/Fib/Main.vm").unwrap();
+	assert_eq!(output, Path::new("tests/fixtures/Fib/Main.asm"));
+	assert!(assembly.starts_with("    // Main.vm\n"));
+
+	// a file calling functions of the other files translates on its own
+	let (_, assembly) = vm::translate_path("tests/fixtures/Fib/Sys.vm").unwrap();
+	assert!(assembly.contains("@Main.fibonacci\n"));
+}
+
+/// FEATURE
+/// As a student,
+/// I want mistakes in my VM code reported with the file and line.
+#[test]
+fn report_errors() {
+	let error = |source: &str| Translator::new().translate("Bad.vm", source).unwrap_err();
+
+	assert_eq!(error("push constant 1\npop constant 0\n"), "Bad.vm: line 2: can't pop to the constant segment");
+	assert_eq!(error("push stack 1"), "Bad.vm: line 1: unknown segment stack");
+	assert_eq!(error("push temp 8"), "Bad.vm: line 1: temp 8 is out of range");
+	assert_eq!(error("push constant 32768"), "Bad.vm: line 1: expected a number from 0 to 32767 but found '32768'");
+	assert_eq!(error("add 1"), "Bad.vm: line 1: add expects 0 operands but found 1");
+	assert_eq!(error("goto"), "Bad.vm: line 1: goto expects 1 operand but found 0");
+	assert_eq!(error("label 1st"), "Bad.vm: line 1: invalid name '1st'");
+	assert_eq!(error("jump"), "Bad.vm: line 1: unknown command jump");
+	assert_eq!(vm::translate_path("tests/fixtures/Missing.vm").unwrap_err().split(':').next(), Some("couldn't read tests/fixtures/Missing.vm"));
+}
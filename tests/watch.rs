@@ -0,0 +1,80 @@
+extern crate hack;
+use hack::assembler::Assembler;
+use hack::watch::Watcher;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn scratch_directory(name: &str) -> PathBuf {
+	let directory = env::temp_dir().join(format!("hack-watch-{}-{}", name, std::process::id()));
+	let _ = fs::remove_dir_all(&directory);
+	fs::create_dir_all(directory.join("sub")).unwrap();
+	directory
+}
+
+/// FEATURE
+/// As a student,
+/// I want only the files I edited to be reassembled.
+#[test]
+fn report_changed_files() {
+	// GIVEN a directory with two programs, one in a subdirectory, and a file that isn't assembly
+	let directory = scratch_directory("changed");
+	let (first, second) = (directory.join("First.asm"), directory.join("sub/Second.asm"));
+	fs::write(&first, "@R0\n").unwrap();
+	fs::write(&second, "@R1\n").unwrap();
+	fs::write(directory.join("notes.txt"), "").unwrap();
+	let mut watcher = Watcher::new(directory.to_str().unwrap());
+
+	// THEN the first poll finds both programs, and the next one nothing
+	assert_eq!(watcher.changed().unwrap(), vec![first.clone(), second.clone()]);
+	assert!(watcher.changed().unwrap().is_empty());
+
+	// WHEN I edit one of them
+	fs::write(&second, "@R1\nD=M\n").unwrap();
+
+	// THEN only that one is reported
+	assert_eq!(watcher.changed().unwrap(), vec![second]);
+
+	fs::remove_dir_all(&directory).unwrap();
+}
+
+/// FEATURE
+/// As a student,
+/// I want errors reported without stopping the watch.
+#[test]
+fn translate_returns_errors() {
+	let directory = scratch_directory("errors");
+	let program = directory.join("Bad.asm");
+	fs::write(&program, ".if 1\n@R0\n").unwrap();
+
+	let mut assembler = Assembler::new(program.to_str().unwrap());
+	assembler.set_quiet(true);
+
+	assert_eq!(assembler.try_translate(), Err(format!("{}: line 1: .if without .endif", program.display())));
+
+	fs::write(&program, "@R0\nM=D+2\n").unwrap();
+	let mut assembler = Assembler::new(program.to_str().unwrap());
+	assembler.set_quiet(true);
+	assert_eq!(assembler.try_translate(), Err(format!("{}: line 2: unknown comp D+2", program.display())));
+
+	fs::remove_dir_all(&directory).unwrap();
+}
+
+/// FEATURE
+/// As a student,
+/// I want successful builds to report their size.
+#[test]
+fn translate_quietly() {
+	let directory = scratch_directory("quiet");
+	let program = directory.join("Good.asm");
+	fs::write(&program, "@R0\nD=M\n(END)\n@END\n0;JMP\n").unwrap();
+
+	let mut assembler = Assembler::new(program.to_str().unwrap());
+	assembler.set_quiet(true);
+
+	assert_eq!(assembler.try_translate(), Ok(()));
+	assert_eq!(assembler.rom_size(), 4);
+
+	fs::remove_dir_all(&directory).unwrap();
+}